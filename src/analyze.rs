@@ -0,0 +1,355 @@
+//! The `analyze` subcommand, which sizes up a pattern without enumerating it.
+
+use std::{collections::BTreeSet, fmt::Write};
+
+use num_bigint::BigUint;
+use num_traits::{ToPrimitive, Zero};
+use passwd_gen::{count, error::GenError, space::Space};
+use regex_syntax::hir::{self, Class, ClassUnicode, ClassUnicodeRange, Hir, HirKind};
+
+/// A report of the number of results of `hir` between `min_length` and
+/// `max_length` bytes, how many are distinct and the bits of entropy in
+/// picking one of those, their shortest and longest, and how many characters
+/// can appear at each position.
+pub fn analyze(
+    hir: &Hir,
+    min_length: usize,
+    max_length: Option<usize>,
+) -> Result<String, GenError> {
+    let space = Space::new(hir, max_length)?;
+    let total = space.count(min_length);
+    let mut report = String::new();
+    writeln!(report, "results: {total}").unwrap();
+    let distinct = count::count_distinct(hir, min_length, max_length)?;
+    writeln!(report, "distinct: {distinct}").unwrap();
+    writeln!(report, "entropy: {:.2} bits", log2(&distinct).max(0.0)).unwrap();
+    let longest = max_length
+        .or(hir.properties().maximum_len())
+        .ok_or(GenError::Unbounded)?;
+    // Results of exactly a length are those of at least it, less those longer.
+    let lengths: Vec<_> = (min_length..=longest)
+        .filter(|&length| space.count(length) > space.count(length + 1))
+        .collect();
+    match (lengths.first(), lengths.last()) {
+        (Some(shortest), Some(longest)) => {
+            writeln!(report, "lengths: {shortest} to {longest} bytes").unwrap()
+        }
+        _ => writeln!(report, "lengths: none").unwrap(),
+    }
+    let positions: Vec<_> = match distinct.is_zero() {
+        true => vec!["none".to_string()],
+        false => positions(hir, longest)
+            .iter()
+            .map(|class| {
+                let chars: u32 = class
+                    .iter()
+                    .map(|range| u32::from(range.end()) - u32::from(range.start()) + 1)
+                    .sum();
+                chars.to_string()
+            })
+            .collect(),
+    };
+    writeln!(report, "characters per position: {}", positions.join(" ")).unwrap();
+    Ok(report)
+}
+
+/// The characters that can appear at each position of a result of at most
+/// `longest` bytes, whatever its length. Bytes that aren't UTF-8 count as a
+/// character each.
+fn positions(hir: &Hir, longest: usize) -> Vec<ClassUnicode> {
+    let mut positions = Vec::new();
+    visit(hir, BTreeSet::from([(0, 0)]), longest, &mut positions);
+    positions
+}
+
+/// A node part way through [`visit`].
+enum Visiting<'a> {
+    /// Visiting the rest of a concatenation's parts in turn.
+    Concat(std::slice::Iter<'a, Hir>),
+    /// Visiting the rest of an alternation's branches from `starts`.
+    Alternation {
+        rest: std::slice::Iter<'a, Hir>,
+        starts: BTreeSet<(usize, usize)>,
+        ends: BTreeSet<(usize, usize)>,
+    },
+    /// Visiting round `round` of a repetition.
+    Repetition {
+        repetition: &'a hir::Repetition,
+        round: u32,
+        visited: BTreeSet<(usize, usize)>,
+        ends: BTreeSet<(usize, usize)>,
+    },
+}
+
+/// Adds what `hir` can put at each position to `positions`, starting from
+/// any of `starts`, and returns where it can end. Both are a position in
+/// characters paired with one in bytes.
+fn visit(
+    hir: &Hir,
+    starts: BTreeSet<(usize, usize)>,
+    longest: usize,
+    positions: &mut Vec<ClassUnicode>,
+) -> BTreeSet<(usize, usize)> {
+    let mut stack = Vec::new();
+    let mut next = Some((hir, starts));
+    let mut current = BTreeSet::new();
+    loop {
+        if let Some((hir, starts)) = next.take() {
+            current = match hir.kind() {
+                HirKind::Literal(literal) => match std::str::from_utf8(&literal.0) {
+                    Ok(text) => text.chars().fold(starts, |starts, c| {
+                        let class = ClassUnicode::new([ClassUnicodeRange::new(c, c)]);
+                        place(&class, true, &starts, longest, positions)
+                    }),
+                    Err(_) => literal.0.iter().fold(starts, |starts, &byte| {
+                        let c = char::from(byte);
+                        let class = ClassUnicode::new([ClassUnicodeRange::new(c, c)]);
+                        place(&class, false, &starts, longest, positions)
+                    }),
+                },
+                HirKind::Class(Class::Unicode(class)) => {
+                    place(class, true, &starts, longest, positions)
+                }
+                HirKind::Class(Class::Bytes(class)) => {
+                    let class = ClassUnicode::new(class.iter().map(|range| {
+                        ClassUnicodeRange::new(char::from(range.start()), char::from(range.end()))
+                    }));
+                    place(&class, false, &starts, longest, positions)
+                }
+                HirKind::Repetition(repetition) => {
+                    stack.push(Visiting::Repetition {
+                        repetition,
+                        round: 0,
+                        visited: BTreeSet::new(),
+                        ends: match repetition.min {
+                            0 => starts.clone(),
+                            _ => BTreeSet::new(),
+                        },
+                    });
+                    starts
+                }
+                HirKind::Capture(capture) => {
+                    next = Some((&capture.sub, starts));
+                    continue;
+                }
+                HirKind::Concat(hirs) => {
+                    stack.push(Visiting::Concat(hirs.iter()));
+                    starts
+                }
+                HirKind::Alternation(hirs) => {
+                    stack.push(Visiting::Alternation {
+                        rest: hirs.iter(),
+                        starts,
+                        ends: BTreeSet::new(),
+                    });
+                    BTreeSet::new()
+                }
+                HirKind::Empty | HirKind::Look(_) => starts,
+            };
+            continue;
+        }
+        // Hand where the last node ended to the one waiting on it.
+        let Some(visiting) = stack.last_mut() else {
+            return current;
+        };
+        match visiting {
+            Visiting::Concat(rest) => match rest.next() {
+                Some(hir) => next = Some((hir, std::mem::take(&mut current))),
+                None => {
+                    stack.pop();
+                }
+            },
+            Visiting::Alternation { rest, starts, ends } => {
+                ends.append(&mut current);
+                match rest.next() {
+                    Some(hir) => next = Some((hir, starts.clone())),
+                    None => {
+                        current = std::mem::take(ends);
+                        stack.pop();
+                    }
+                }
+            }
+            Visiting::Repetition {
+                repetition,
+                round,
+                visited,
+                ends,
+            } => {
+                // Round 0 is where the repetition starts, before any copies.
+                if *round >= repetition.min.max(1) {
+                    ends.extend(current.iter().copied());
+                }
+                // Once a round starts only from where earlier ones did, it
+                // and every round after it add nothing new.
+                let fresh = !current.is_subset(visited);
+                let last = *round > 0 && repetition.max == Some(*round);
+                if last || current.is_empty() || (!fresh && *round >= repetition.min) {
+                    current = std::mem::take(ends);
+                    stack.pop();
+                } else {
+                    *round += 1;
+                    visited.extend(current.iter().copied());
+                    next = Some((&repetition.sub, std::mem::take(&mut current)));
+                }
+            }
+        }
+    }
+}
+
+/// How fast results are tried, for `--at-rate`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rate {
+    PerSecond(f64),
+    /// As fast as this machine generates the pattern.
+    Local,
+}
+
+/// Parses a rate such as 500kH/s, 2.5G or 1200, in results per second, or
+/// `local`.
+pub fn parse_rate(rate: &str) -> Result<Rate, String> {
+    if rate == "local" {
+        return Ok(Rate::Local);
+    }
+    let number = ["H/s", "h/s", "/s"]
+        .iter()
+        .find_map(|unit| rate.strip_suffix(unit))
+        .unwrap_or(rate);
+    let (number, scale) = match number.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&number[..i], 1e3),
+        Some((i, 'M')) => (&number[..i], 1e6),
+        Some((i, 'G')) => (&number[..i], 1e9),
+        Some((i, 'T')) => (&number[..i], 1e12),
+        _ => (number, 1.0),
+    };
+    match number.parse::<f64>() {
+        Ok(number) if number > 0.0 && number.is_finite() => Ok(Rate::PerSecond(number * scale)),
+        _ => Err(format!(
+            "{rate:?} isn't a positive rate such as 500kH/s, or local"
+        )),
+    }
+}
+
+/// Roughly how long `seconds` is, in the largest unit that fits.
+fn roughly(seconds: f64) -> String {
+    const YEAR: f64 = 365.25 * 86400.0;
+    match seconds {
+        ..1.0 => "under a second".to_string(),
+        ..60.0 => format!("{seconds:.0} seconds"),
+        ..3600.0 => format!("{:.1} minutes", seconds / 60.0),
+        ..86400.0 => format!("{:.1} hours", seconds / 3600.0),
+        ..YEAR => format!("{:.1} days", seconds / 86400.0),
+        _ if seconds / YEAR < 1e6 => format!("{:.1} years", seconds / YEAR),
+        _ if seconds.is_finite() => format!("{:.2e} years", seconds / YEAR),
+        _ => "longer than any number of years".to_string(),
+    }
+}
+
+/// How long trying all `total` results would take at `rate` per second.
+pub fn exhaustion(total: &BigUint, rate: f64) -> String {
+    let seconds = total.to_f64().unwrap_or(f64::INFINITY) / rate;
+    format!("time at {rate:.0} results/s: {}\n", roughly(seconds))
+}
+
+/// Puts one of `class` at each of `starts`, returning where that leaves off.
+/// Its characters take their UTF-8 length in bytes if `utf8`, or else stand
+/// for bytes and take one each.
+fn place(
+    class: &ClassUnicode,
+    utf8: bool,
+    starts: &BTreeSet<(usize, usize)>,
+    longest: usize,
+    positions: &mut Vec<ClassUnicode>,
+) -> BTreeSet<(usize, usize)> {
+    let widths: &[(usize, char, char)] = match utf8 {
+        true => &[
+            (1, '\0', '\x7F'),
+            (2, '\u{80}', '\u{7FF}'),
+            (3, '\u{800}', '\u{FFFF}'),
+            (4, '\u{10000}', char::MAX),
+        ],
+        false => &[(1, '\0', char::MAX)],
+    };
+    let mut ends = BTreeSet::new();
+    for &(bytes, first, last) in widths {
+        let mut class = class.clone();
+        class.intersect(&ClassUnicode::new([ClassUnicodeRange::new(first, last)]));
+        if class.ranges().is_empty() {
+            continue;
+        }
+        for &(start, offset) in starts
+            .iter()
+            .filter(|&&(_, offset)| offset + bytes <= longest)
+        {
+            if positions.len() <= start {
+                positions.resize(start + 1, ClassUnicode::empty());
+            }
+            positions[start].union(&class);
+            ends.insert((start + 1, offset + bytes));
+        }
+    }
+    ends
+}
+
+/// The base 2 logarithm of `n`, from its top 64 bits so it holds for counts
+/// too large for a float.
+fn log2(n: &BigUint) -> f64 {
+    let shift = n.bits().saturating_sub(64);
+    (n >> shift).to_f64().unwrap_or_default().log2() + shift as f64
+}
+
+#[test]
+fn test_analyze() {
+    let hir = regex_syntax::Parser::new().parse("[ab]{2,4}|a").unwrap();
+    assert_eq!(
+        analyze(&hir, 0, None).unwrap(),
+        "results: 29\ndistinct: 29\nentropy: 4.86 bits\nlengths: 1 to 4 bytes\n\
+         characters per position: 2 2 2 2\n"
+    );
+    assert_eq!(
+        analyze(&hir, 3, Some(3)).unwrap(),
+        "results: 8\ndistinct: 8\nentropy: 3.00 bits\nlengths: 3 to 3 bytes\n\
+         characters per position: 2 2 2\n"
+    );
+    let hir = regex_syntax::Parser::new().parse("a|a").unwrap();
+    assert_eq!(
+        analyze(&hir, 2, None).unwrap(),
+        "results: 0\ndistinct: 0\nentropy: 0.00 bits\nlengths: none\n\
+         characters per position: none\n"
+    );
+    let hir = regex_syntax::Parser::new()
+        .parse("[a-z]{2}[0-9]|é(?:x|yz)*")
+        .unwrap();
+    assert!(
+        analyze(&hir, 0, Some(4))
+            .unwrap()
+            .ends_with("characters per position: 27 26 13\n")
+    );
+    let hir = regex_syntax::Parser::new().parse("[a-z]{300}").unwrap();
+    assert!(
+        analyze(&hir, 0, None)
+            .unwrap()
+            .contains("\nentropy: 1410.13 bits\n")
+    );
+}
+
+#[test]
+fn test_exhaustion() {
+    assert_eq!(parse_rate("500kH/s"), Ok(Rate::PerSecond(500e3)));
+    assert_eq!(parse_rate("2.5G"), Ok(Rate::PerSecond(2.5e9)));
+    assert_eq!(parse_rate("1200/s"), Ok(Rate::PerSecond(1200.0)));
+    assert_eq!(parse_rate("local"), Ok(Rate::Local));
+    assert!(parse_rate("fast").is_err() && parse_rate("0kH/s").is_err());
+    let total = BigUint::from(26u64.pow(8));
+    assert_eq!(
+        exhaustion(&total, 500e3),
+        "time at 500000 results/s: 4.8 days\n"
+    );
+    assert_eq!(
+        exhaustion(&BigUint::from(10u8), 1e3),
+        "time at 1000 results/s: under a second\n"
+    );
+    assert_eq!(
+        exhaustion(&(BigUint::from(1u8) << 128), 1e9),
+        "time at 1000000000 results/s: 1.08e22 years\n"
+    );
+}
@@ -0,0 +1,331 @@
+//! Backreferences such as `([a-z]{3})\1`, which `regex-syntax` can't parse.
+//!
+//! Each `\1` to `\9` is swapped for a placeholder character before parsing.
+//! The pattern is then generated piece by piece, and the placeholders in each
+//! result are replaced by whatever the group they refer to produced.
+//!
+//! ```
+//! use passwd_gen::backref::{self, Backrefs};
+//!
+//! let pattern = backref::rewrite(r"([ab])-\1").unwrap().unwrap();
+//! let hir = regex_syntax::Parser::new().parse(&pattern).unwrap();
+//! let backrefs = Backrefs::new(&hir).unwrap();
+//! let results: Vec<_> = backrefs.iter().map(|result| result.to_vec()).collect();
+//! assert_eq!(results, [b"a-a", b"b-b"]);
+//! ```
+
+use std::iter::once;
+
+use regex_syntax::hir::{self, Class, ClassUnicode, ClassUnicodeRange, Hir, HirKind::*};
+
+use crate::{
+    Candidate, ClassOrder, MultiCartesianProduct, error::GenError, is_unbounded, iterate_all,
+    map_leaves,
+};
+
+/// Backreference `\N` is written as this codepoint plus `N`, from the
+/// supplementary private use area so it stays out of real patterns.
+const MARKER: u32 = 0xF0000;
+
+fn marker(group: u32) -> char {
+    char::from_u32(MARKER + group).unwrap()
+}
+
+/// Every placeholder character, for keeping them through transformations
+/// that narrow classes.
+pub fn placeholders() -> ClassUnicode {
+    ClassUnicode::new([ClassUnicodeRange::new(marker(1), marker(9))])
+}
+
+/// The group referred to by the placeholder at the start of `bytes`.
+fn marker_at(bytes: &[u8]) -> Option<usize> {
+    let c = std::str::from_utf8(bytes.get(..4)?).ok()?.chars().next()?;
+    let group = (c as u32).checked_sub(MARKER)?;
+    (1..=9).contains(&group).then_some(group as usize)
+}
+
+/// `pattern` with each backreference outside a character class replaced by
+/// its placeholder, ready for parsing, or `None` if it has none.
+pub fn rewrite(pattern: &str) -> Result<Option<String>, GenError> {
+    let mut rewritten = String::with_capacity(pattern.len());
+    let mut found = false;
+    // Nesting depth of character classes.
+    let mut depth = 0;
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        if (MARKER + 1..=MARKER + 9).contains(&(c as u32)) {
+            Err(GenError::Backref(format!(
+                "the pattern contains U+{:X}, which is reserved for backreferences",
+                c as u32
+            )))?
+        }
+        match c {
+            '\\' => match chars.next() {
+                Some(digit @ '1'..='9') if depth == 0 => {
+                    rewritten.push(marker(digit.to_digit(10).unwrap()));
+                    found = true;
+                    continue;
+                }
+                Some(escaped) => {
+                    rewritten.push('\\');
+                    rewritten.push(escaped);
+                    continue;
+                }
+                None => {}
+            },
+            '[' => {
+                depth += 1;
+                rewritten.push('[');
+                // A `]` straight after the opening bracket is a literal.
+                if chars.peek() == Some(&'^') {
+                    rewritten.push(chars.next().unwrap());
+                }
+                if chars.peek() == Some(&']') {
+                    rewritten.push(chars.next().unwrap());
+                }
+                continue;
+            }
+            ']' if depth > 0 => depth -= 1,
+            _ => {}
+        }
+        rewritten.push(c);
+    }
+    Ok(found.then_some(rewritten))
+}
+
+/// Every backreference placeholder in `hir`.
+fn markers(hir: &Hir, found: &mut Vec<usize>) {
+    let mut pending = vec![hir];
+    while let Some(hir) = pending.pop() {
+        match hir.kind() {
+            Literal(literal) => {
+                found.extend((0..literal.0.len()).filter_map(|i| marker_at(&literal.0[i..])))
+            }
+            Class(Class::Unicode(class)) => {
+                for group in 1..=9 {
+                    let c = marker(group);
+                    if class
+                        .ranges()
+                        .iter()
+                        .any(|range| (range.start()..=range.end()).contains(&c))
+                    {
+                        found.push(group as usize);
+                    }
+                }
+            }
+            Repetition(hir::Repetition { sub, .. }) | Capture(hir::Capture { sub, .. }) => {
+                pending.push(sub)
+            }
+            Concat(hirs) | Alternation(hirs) => pending.extend(hirs.iter().rev()),
+            _ => {}
+        }
+    }
+}
+
+/// A pattern containing backreferences, split into the pieces of its
+/// top-level concatenation.
+pub struct Backrefs {
+    parts: Vec<Hir>,
+    /// Whether each part contains a placeholder.
+    marked: Vec<bool>,
+    /// The part that is each group, by group number.
+    groups: [Option<usize>; 10],
+    min_length: usize,
+    max_length: Option<usize>,
+    class_order: ClassOrder,
+}
+
+impl Backrefs {
+    /// Splits a pattern parsed from the output of [`rewrite`]. Each group
+    /// referred to must be a part of its own at the top level, and any part
+    /// with a backreference in it must be bounded.
+    pub fn new(hir: &Hir) -> Result<Self, GenError> {
+        let parts = match hir.kind() {
+            Concat(hirs) => hirs.clone(),
+            _ => vec![hir.clone()],
+        };
+        let mut groups = [None; 10];
+        for (i, part) in parts.iter().enumerate() {
+            if let Capture(capture) = part.kind()
+                && let Some(group) = groups.get_mut(capture.index as usize)
+            {
+                *group = Some(i);
+            }
+        }
+        let mut marked = Vec::new();
+        for (i, part) in parts.iter().enumerate() {
+            let mut found = Vec::new();
+            markers(part, &mut found);
+            for &group in &found {
+                match groups[group] {
+                    None => Err(GenError::Backref(format!(
+                        "\\{group} must refer to a group at the top level of the pattern"
+                    )))?,
+                    Some(j) if j == i => Err(GenError::Backref(format!(
+                        "\\{group} is inside the group it refers to"
+                    )))?,
+                    Some(_) => {}
+                }
+            }
+            if !found.is_empty() && is_unbounded(part) {
+                Err(GenError::Backref(
+                    "a backreference can't be repeated without limit".to_string(),
+                ))?
+            }
+            marked.push(!found.is_empty());
+        }
+        Ok(Self {
+            parts,
+            marked,
+            groups,
+            min_length: 0,
+            max_length: None,
+            class_order: ClassOrder::default(),
+        })
+    }
+
+    /// Only yield results of at least this many bytes.
+    pub fn min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    /// Only yield results of at most this many bytes.
+    pub fn max_length(mut self, max_length: Option<usize>) -> Self {
+        self.max_length = max_length;
+        self
+    }
+
+    pub fn class_order(mut self, class_order: ClassOrder) -> Self {
+        self.class_order = class_order;
+        self
+    }
+
+    /// `hir` with each placeholder replaced by a copy of its group, so it
+    /// matches every result and more. It stands in for the pattern wherever
+    /// only a regex will do.
+    pub fn superset(&self, hir: &Hir) -> Hir {
+        let group = |group: usize| self.parts[self.groups[group].unwrap()].clone();
+        map_leaves(hir, |hir| match hir.kind() {
+            Literal(literal) => {
+                let mut pieces = Vec::new();
+                let mut start = 0;
+                let mut i = 0;
+                while i < literal.0.len() {
+                    match marker_at(&literal.0[i..]) {
+                        Some(n) => {
+                            pieces.push(Hir::literal(&literal.0[start..i]));
+                            pieces.push(group(n));
+                            i += 4;
+                            start = i;
+                        }
+                        None => i += 1,
+                    }
+                }
+                pieces.push(Hir::literal(&literal.0[start..]));
+                Hir::concat(pieces)
+            }
+            Class(Class::Unicode(class)) => {
+                let mut found = Vec::new();
+                markers(hir, &mut found);
+                if found.is_empty() {
+                    return hir.clone();
+                }
+                let mut rest = class.clone();
+                rest.difference(&placeholders());
+                Hir::alternation(
+                    once(Hir::class(Class::Unicode(rest)))
+                        .chain(found.into_iter().map(group))
+                        .collect(),
+                )
+            }
+            _ => hir.clone(),
+        })
+    }
+
+    /// Joins one result of each part, with the placeholders filled in.
+    fn substitute(&self, parts: &[Candidate]) -> Option<Candidate> {
+        let mut joined = Candidate::new();
+        for (part, &marked) in parts.iter().zip(&self.marked) {
+            if !marked {
+                joined.extend_from_slice(part);
+                continue;
+            }
+            let mut rest = &part[..];
+            while let Some(&byte) = rest.first() {
+                match marker_at(rest) {
+                    Some(group) => {
+                        joined.extend_from_slice(&parts[self.groups[group].unwrap()]);
+                        rest = &rest[4..];
+                    }
+                    None => {
+                        joined.push(byte);
+                        rest = &rest[1..];
+                    }
+                }
+            }
+        }
+        (joined.len() >= self.min_length
+            && self
+                .max_length
+                .is_none_or(|max_length| joined.len() <= max_length))
+        .then_some(joined)
+    }
+
+    /// Iterates over results as raw bytes, with the first part varying
+    /// fastest as in an ordinary pattern.
+    pub fn iter(&self) -> impl Iterator<Item = Candidate> + '_ {
+        // A part's placeholders stand for results of unknown length, so only
+        // the others can be cut short by the max length.
+        MultiCartesianProduct::new(
+            self.parts
+                .iter()
+                .zip(&self.marked)
+                .map(|(part, &marked)| {
+                    let max_length = self.max_length.filter(|_| !marked);
+                    move || iterate_all(part, max_length, self.class_order)
+                })
+                .collect(),
+        )
+        .filter_map(|parts| self.substitute(&parts))
+    }
+}
+
+#[test]
+fn test_backrefs() {
+    let backrefs = |pattern: &str| {
+        let rewritten = rewrite(pattern)?.expect("no backreferences");
+        Backrefs::new(&regex_syntax::Parser::new().parse(&rewritten).unwrap())
+    };
+    let results = |backrefs: &Backrefs| -> Vec<String> {
+        backrefs
+            .iter()
+            .map(|result| String::from_utf8(result.to_vec()).unwrap())
+            .collect()
+    };
+    assert_eq!(results(&backrefs(r"(a|bc)x\1").unwrap()), ["axa", "bcxbc"]);
+    assert_eq!(
+        results(&backrefs(r"([ab])([01])\2\1?").unwrap()),
+        ["a00", "b00", "a11", "b11", "a00a", "b00b", "a11a", "b11b"]
+    );
+    assert_eq!(
+        results(
+            &backrefs(r"([ab]+)\1")
+                .unwrap()
+                .min_length(3)
+                .max_length(Some(4))
+        ),
+        ["aaaa", "baba", "abab", "bbbb"]
+    );
+    assert_eq!(rewrite(r"[\1]\\1").unwrap(), None);
+    assert!(backrefs(r"(a)|\1").is_err());
+    assert!(backrefs(r"(a\1)").is_err());
+    assert!(backrefs(r"(a)\1+").is_err());
+    assert!(rewrite("\u{F0001}\\1").is_err());
+    let hir = regex_syntax::Parser::new()
+        .parse(&rewrite(r"(a|b)(?:x|\1)\1").unwrap().unwrap())
+        .unwrap();
+    let superset = Backrefs::new(&hir).unwrap().superset(&hir);
+    let results: Vec<_> = crate::iterate_all(&superset, None, ClassOrder::Asc).collect();
+    assert_eq!(results.len(), 12);
+}
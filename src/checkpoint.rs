@@ -0,0 +1,31 @@
+use std::{error::Error, fs, io, path::Path};
+
+use num_bigint::BigUint;
+
+/// Reads the index of the next candidate to generate from a checkpoint file.
+pub fn read(path: &Path) -> Result<BigUint, Box<dyn Error>> {
+    let contents = fs::read_to_string(path)?;
+    contents
+        .trim()
+        .parse()
+        .map_err(|_| format!("{} is not a valid checkpoint file.", path.display()).into())
+}
+
+/// Records the index of the next candidate to generate. The file is replaced
+/// atomically, so an interrupted write leaves the previous checkpoint intact.
+pub fn write(path: &Path, next: &BigUint) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    fs::write(&temporary, format!("{next}\n"))?;
+    fs::rename(&temporary, path)
+}
+
+#[test]
+fn test_checkpoint() {
+    let path = std::env::temp_dir().join(format!("passwd-gen-checkpoint-{}", std::process::id()));
+    write(&path, &BigUint::from(12345u32)).unwrap();
+    assert_eq!(read(&path).unwrap(), BigUint::from(12345u32));
+    fs::write(&path, "twelve").unwrap();
+    assert!(read(&path).is_err());
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,62 @@
+use std::{error::Error, fs};
+
+use clap::Args;
+use passwd_gen::template::{self, NamedSet};
+
+/// Enumerate every pairing of a result of LEFT with a result of RIGHT, like
+/// hashcat's combinator attack. The left half varies fastest
+#[derive(Args)]
+pub struct CombineArgs {
+    /// Pattern for the first half, or a wordlist file with --files
+    pub left: String,
+
+    /// Pattern for the second half, or a wordlist file with --files
+    pub right: String,
+
+    /// Put this between the two halves
+    #[clap(long, default_value = "")]
+    pub separator: String,
+
+    /// Read LEFT and RIGHT as wordlist files, one word per line
+    #[clap(long)]
+    pub files: bool,
+}
+
+impl CombineArgs {
+    fn half(&self, half: &str, sets: &[NamedSet]) -> Result<String, Box<dyn Error>> {
+        if !self.files {
+            return Ok(format!("(?:{})", template::expand_sets(half, sets)?));
+        }
+        let words: Vec<_> = fs::read_to_string(half)
+            .map_err(|error| format!("{half}: {error}"))?
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect();
+        Ok(template::alternation(&words))
+    }
+
+    pub fn pattern(&self, sets: &[NamedSet]) -> Result<String, Box<dyn Error>> {
+        Ok(format!(
+            "{}{}{}",
+            self.half(&self.left, sets)?,
+            regex_syntax::escape(&self.separator),
+            self.half(&self.right, sets)?
+        ))
+    }
+}
+
+#[test]
+fn test_combine_pattern() {
+    let combine = CombineArgs {
+        left: "a|b{{n}}".to_string(),
+        right: "[0-9]".to_string(),
+        separator: "-".to_string(),
+        files: false,
+    };
+    let sets = [template::parse_set("n=1,2").unwrap()];
+    assert_eq!(
+        combine.pattern(&sets).unwrap(),
+        "(?:a|b(?:1|2))\\-(?:[0-9])"
+    );
+}
@@ -0,0 +1,89 @@
+use num_bigint::BigUint;
+use regex_syntax::hir::{Hir, HirKind::*};
+
+use crate::{error::GenError, sorted::Automaton, space::Space};
+
+/// Whether `hir` produces each of its results only once, by a check that
+/// passes some unambiguous patterns but no ambiguous ones. A concatenation
+/// is unambiguous if its parts are and at most one varies in length, since
+/// the lengths of the rest then fix where each part starts.
+fn unambiguous(hir: &Hir) -> bool {
+    let fixed = |hir: &Hir| {
+        let properties = hir.properties();
+        properties.minimum_len().is_some() && properties.minimum_len() == properties.maximum_len()
+    };
+    let mut pending = vec![hir];
+    while let Some(hir) = pending.pop() {
+        match hir.kind() {
+            Empty | Literal(_) | Class(_) => {}
+            Capture(capture) => pending.push(&capture.sub),
+            Repetition(repetition)
+                if fixed(&repetition.sub)
+                    && repetition.sub.properties().minimum_len() != Some(0) =>
+            {
+                pending.push(&repetition.sub)
+            }
+            Concat(hirs) if hirs.iter().filter(|hir| !fixed(hir)).count() <= 1 => {
+                pending.extend(hirs)
+            }
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// Counts the distinct results with a byte length in `min_length..=max_length`.
+/// Patterns found to be unambiguous are counted by their derivations; anything
+/// else by the paths through its DFA, without enumerating them. Fails where
+/// the DFA can't be built, or the pattern is unbounded and there is no max
+/// length.
+pub fn count_distinct(
+    hir: &Hir,
+    min_length: usize,
+    max_length: Option<usize>,
+) -> Result<BigUint, GenError> {
+    if unambiguous(hir)
+        && let Ok(space) = Space::new(hir, max_length)
+    {
+        return Ok(space.count(min_length));
+    }
+    let max_length = max_length
+        .or(hir.properties().maximum_len())
+        .ok_or(GenError::Unbounded)?;
+    let counts = Automaton::new(hir)?.counts(max_length);
+    Ok(counts.into_iter().skip(min_length).sum())
+}
+
+#[test]
+fn test_count_distinct() {
+    let parse = |pattern| regex_syntax::Parser::new().parse(pattern).unwrap();
+    let count = |pattern, min_length, max_length| {
+        let distinct = count_distinct(&parse(pattern), min_length, max_length).unwrap();
+        u128::try_from(distinct).unwrap()
+    };
+    assert_eq!(count("ab|cd|ab|(ab)", 0, None), 2);
+    assert_eq!(count("ab|cd|abc", 3, None), 1);
+    assert_eq!(count("[a-z]{3}[0-9]x", 0, None), 26 * 26 * 26 * 10);
+    assert_eq!(count("[a-z]{3}", 4, None), 0);
+    assert_eq!(count("(a|ab)b?", 0, None), 3);
+    assert_eq!(count("[aé]{2}", 0, Some(3)), 3);
+    assert_eq!(
+        count("[A-Z][a-z]{5,7}[0-9]{2}", 0, None),
+        26 * (26u128.pow(5) + 26u128.pow(6) + 26u128.pow(7)) * 100
+    );
+    assert_eq!(count("[a-z]{1,2}[a-z]{1,2}", 0, None), 475228);
+    // Ambiguous patterns with too many results to enumerate.
+    assert_eq!(count("a|ab|[a-z]{9}", 0, None), 26u128.pow(9) + 2);
+    assert_eq!(count("(a|b|ab)[a-z]{12}", 0, None), 3 * 26u128.pow(12));
+    assert_eq!(
+        count_distinct(&parse("[a-z]{30}"), 0, None).unwrap(),
+        BigUint::from(26u8).pow(30)
+    );
+    assert_eq!(count("(a|ab|a)b?", 0, None), 3);
+    assert_eq!(count("[a-z]{3}|[a-z]{2,3}", 3, None), 26u128.pow(3));
+    assert_eq!(count("(a|bb)*", 0, Some(3)), 7);
+    assert!(matches!(
+        count_distinct(&parse("a*"), 0, None),
+        Err(GenError::Unbounded)
+    ));
+}
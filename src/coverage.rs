@@ -0,0 +1,69 @@
+use std::collections::{BTreeMap, HashMap};
+
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+use passwd_gen::space::Space;
+
+/// Distinct results seen per length and per top-level branch. Drawing the
+/// same result twice only counts it once, so coverage never passes 100%.
+#[derive(Default)]
+pub struct Tally {
+    lengths: BTreeMap<usize, usize>,
+    branches: BTreeMap<usize, usize>,
+    /// The branches each result seen was recorded from.
+    seen: HashMap<Vec<u8>, Vec<usize>>,
+}
+
+impl Tally {
+    pub fn record(&mut self, branch: usize, result: &[u8]) {
+        let branches = match self.seen.get_mut(result) {
+            Some(branches) => branches,
+            None => {
+                *self.lengths.entry(result.len()).or_default() += 1;
+                self.seen.entry(result.to_vec()).or_default()
+            }
+        };
+        if !branches.contains(&branch) {
+            branches.push(branch);
+            *self.branches.entry(branch).or_default() += 1;
+        }
+    }
+}
+
+fn table(title: &str, hit: &BTreeMap<usize, usize>, total: &BTreeMap<usize, BigUint>) -> String {
+    let mut table = format!(
+        "{title:>8} {:>12} {:>12} {:>9}\n",
+        "hit", "total", "coverage"
+    );
+    for (key, total) in total {
+        let hit = hit.get(key).copied().unwrap_or(0);
+        let coverage = match total.to_f64() {
+            Some(total) if total > 0.0 => 100.0 * hit as f64 / total,
+            _ => 0.0,
+        };
+        table += &format!("{key:>8} {hit:>12} {total:>12} {coverage:>8.2}%\n");
+    }
+    table
+}
+
+/// Formats a report of how much of each length and top-level branch of
+/// `space` was covered by `hit`.
+pub fn report(hit: &Tally, space: &Space) -> String {
+    let lengths = space.lengths().into_iter().collect();
+    let branches = space.branches().into_iter().enumerate().collect();
+    table("length", &hit.lengths, &lengths) + &table("branch", &hit.branches, &branches)
+}
+
+#[test]
+fn test_report() {
+    let hir = regex_syntax::Parser::new().parse("a|[bc]{2}|b").unwrap();
+    let space = Space::new(&hir, None).unwrap();
+    let mut hit = Tally::default();
+    for (branch, result) in [(1, "bc"), (1, "bc"), (1, "cc"), (2, "b")] {
+        hit.record(branch, result.as_bytes());
+    }
+    assert_eq!(
+        report(&hit, &space),
+        "  length          hit        total  coverage\n       1            1            2    50.00%\n       2            2            4    50.00%\n  branch          hit        total  coverage\n       0            0            1     0.00%\n       1            2            4    50.00%\n       2            1            1   100.00%\n"
+    );
+}
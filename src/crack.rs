@@ -0,0 +1,242 @@
+//! The `crack` subcommand: hashes every result of a pattern on several threads
+//! until the plaintexts of the target digests are found.
+
+use std::{
+    collections::HashSet,
+    error::Error,
+    fs,
+    path::PathBuf,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use clap::Args;
+use num_bigint::BigUint;
+use passwd_gen::{Candidate, Pattern};
+use rayon::{ThreadPoolBuilder, prelude::*};
+
+use crate::{
+    PatternArgs, compile,
+    format::{self, Encoding},
+    hash::{self, HashType},
+};
+
+/// Hash every result of a pattern until the plaintexts of the target digests are found
+#[derive(Args)]
+pub struct CrackArgs {
+    #[clap(flatten)]
+    pub pattern: PatternArgs,
+
+    /// Algorithm the targets were hashed with
+    #[clap(long, value_enum)]
+    pub hash_type: HashType,
+
+    /// Hex digest to find the plaintext of. Repeatable
+    #[clap(long, value_name = "DIGEST", required_unless_present = "target_file")]
+    pub target: Vec<String>,
+
+    /// File of hex digests, one per line
+    #[clap(long, value_name = "FILE")]
+    pub target_file: Option<PathBuf>,
+
+    /// Hash on this many threads; defaults to one per core
+    #[clap(long, value_name = "N", default_value_t = 0)]
+    pub threads: usize,
+}
+
+/// Results each thread hashes between checks for whether to stop.
+const CHUNK: u64 = 1 << 14;
+
+fn parse_digest(digest: &str, hash_type: HashType) -> Result<Vec<u8>, String> {
+    let digest = digest.trim();
+    let bytes = (0..digest.len())
+        .step_by(2)
+        .map(|i| {
+            digest
+                .get(i..i + 2)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        })
+        .collect::<Option<Vec<u8>>>()
+        .filter(|bytes| bytes.len() == hash_type.digest_len())
+        .ok_or_else(|| {
+            format!(
+                "{digest:?} isn't a {}-digit hex digest",
+                2 * hash_type.digest_len()
+            )
+        })?;
+    Ok(bytes)
+}
+
+impl CrackArgs {
+    fn targets(&self) -> Result<HashSet<Vec<u8>>, Box<dyn Error>> {
+        let mut digests = self.target.clone();
+        if let Some(path) = &self.target_file {
+            digests.extend(
+                fs::read_to_string(path)?
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .map(String::from),
+            );
+        }
+        Ok(digests
+            .iter()
+            .map(|digest| parse_digest(digest, self.hash_type))
+            .collect::<Result<_, _>>()?)
+    }
+}
+
+/// A target digest along with the result it's the hash of.
+pub struct Cracked {
+    pub digest: Vec<u8>,
+    pub plaintext: Vec<u8>,
+}
+
+/// Finds the results of `pattern` whose digests are in `targets`, stopping
+/// once all are found.
+pub fn find(
+    pattern: &Pattern,
+    hash_type: HashType,
+    targets: HashSet<Vec<u8>>,
+    threads: usize,
+) -> Result<Vec<Cracked>, Box<dyn Error>> {
+    let space = pattern.space()?;
+    let chunks = u64::try_from((space.total() + CHUNK - 1u32) / CHUNK)
+        .map_err(|_| "The pattern has too many results to crack.")?;
+    let done = AtomicBool::new(targets.is_empty());
+    let found = Mutex::new(Vec::new());
+    ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()?
+        .install(|| {
+            (0..chunks).into_par_iter().for_each(|chunk| {
+                if done.load(Ordering::Relaxed) {
+                    return;
+                }
+                let start = BigUint::from(chunk) * CHUNK;
+                for result in space
+                    .iterate_from(pattern.hir(), &start)
+                    .take(CHUNK as usize)
+                {
+                    if record(&result, hash_type, &targets, &found) {
+                        done.store(true, Ordering::Relaxed);
+                        return;
+                    }
+                }
+            })
+        });
+    Ok(found.into_inner().unwrap())
+}
+
+/// Like [`find`], but hashes `results` in turn on this thread, for those that
+/// can't be seeked into.
+pub fn find_in(
+    results: impl Iterator<Item = Candidate>,
+    hash_type: HashType,
+    targets: HashSet<Vec<u8>>,
+) -> Vec<Cracked> {
+    let found = Mutex::new(Vec::new());
+    if !targets.is_empty() {
+        for result in results {
+            if record(&result, hash_type, &targets, &found) {
+                break;
+            }
+        }
+    }
+    found.into_inner().unwrap()
+}
+
+/// Records `result` in `found` if it's the plaintext of a target not found
+/// yet, returning whether every target now is.
+fn record(
+    result: &[u8],
+    hash_type: HashType,
+    targets: &HashSet<Vec<u8>>,
+    found: &Mutex<Vec<Cracked>>,
+) -> bool {
+    let digest = hash::digest(result, hash_type);
+    if !targets.contains(&digest) {
+        return false;
+    }
+    let mut found = found.lock().unwrap();
+    // An ambiguous pattern can produce the same result twice.
+    if found.iter().any(|cracked| cracked.digest == digest) {
+        return false;
+    }
+    found.push(Cracked {
+        digest,
+        plaintext: result.to_vec(),
+    });
+    found.len() == targets.len()
+}
+
+pub fn crack(args: &mut CrackArgs) -> Result<(), Box<dyn Error>> {
+    // Digests are of the exact bytes, so byte patterns are let through.
+    let compiled = compile(&mut args.pattern, None, true)?;
+    let pattern = &compiled.pattern;
+    let (mode, max_length) = (args.pattern.mode, args.pattern.max_length);
+    if pattern.is_unbounded() && max_length.is_none() {
+        Err("crack on an infinite range requires a max length to be specified.")?
+    }
+    let targets = args.targets()?;
+    let count = targets.len();
+    // Whether a result is kept depends on nothing but the result, so only
+    // the plaintexts found need checking, rather than every result.
+    // Backreferences are substituted as results are generated, so can't be
+    // split between threads by index.
+    let found = match &compiled.backrefs {
+        Some(backrefs) => find_in(backrefs.iter(), args.hash_type, targets),
+        None => find(pattern, args.hash_type, targets, args.threads)?,
+    };
+    let found: Vec<_> = found
+        .into_iter()
+        .filter(|cracked| compiled.keeps(mode, max_length, &cracked.plaintext))
+        .collect();
+    for Cracked { digest, plaintext } in &found {
+        let plaintext = String::from_utf8_lossy(plaintext);
+        match count {
+            1 => println!("{plaintext}"),
+            _ => println!(
+                "{}:{plaintext}",
+                String::from_utf8_lossy(&format::encode(digest, Encoding::Hex))
+            ),
+        }
+    }
+    if found.len() < count {
+        Err(format!(
+            "found {} of {count} plaintexts in the pattern.",
+            found.len()
+        ))?
+    }
+    Ok(())
+}
+
+#[test]
+fn test_find() {
+    let pattern = Pattern::new("[a-z]{1,3}").unwrap();
+    let targets = ["pas", "ab"]
+        .iter()
+        .map(|plaintext| hash::digest(plaintext.as_bytes(), HashType::Sha256))
+        .collect();
+    let found = find(&pattern, HashType::Sha256, targets, 4).unwrap();
+    let mut plaintexts: Vec<_> = found.into_iter().map(|cracked| cracked.plaintext).collect();
+    plaintexts.sort();
+    assert_eq!(plaintexts, [b"ab".to_vec(), b"pas".to_vec()]);
+    let missing = [vec![0; 32]].into_iter().collect();
+    assert!(
+        find(&pattern, HashType::Sha256, missing, 2)
+            .unwrap()
+            .is_empty()
+    );
+    let targets = [hash::digest(b"abab", HashType::Sha256)]
+        .into_iter()
+        .collect();
+    let results = ["a", "abab", "abab"].map(|result| Candidate::from_slice(result.as_bytes()));
+    let found = find_in(results.into_iter(), HashType::Sha256, targets);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].plaintext, b"abab");
+    assert!(parse_digest("900150983cd24fb0d6963f7d28e17f72", HashType::Md5).is_ok());
+    assert!(parse_digest("900150983cd24fb0d6963f7d28e17f7", HashType::Md5).is_err());
+    assert!(parse_digest("900150983cd24fb0d6963f7d28e17f72", HashType::Sha1).is_err());
+}
@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use passwd_gen::template::class;
+
+const LOWER: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPER: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const NUMBERS: &str = "0123456789";
+const SYMBOLS: &str = "!@#$%^&*()-_+=~`[]{}|\\:;\"'<>,.?/ ";
+
+/// Enumerate like crunch: every string of MIN to MAX characters from a charset,
+/// shortest first
+#[derive(Args)]
+pub struct CrunchArgs {
+    /// Fewest characters in each result
+    pub min: usize,
+
+    /// Most characters in each result
+    pub max: usize,
+
+    /// Charsets for the @ , % and ^ placeholders of -t, in that order; `+`
+    /// keeps a default. Without -t, results are made from the first
+    pub charsets: Vec<String>,
+
+    /// Template such as @@,%% where @ stands for a lowercase letter, `,` an
+    /// uppercase one, % a digit and ^ a symbol, and anything else for itself
+    #[clap(short = 't', value_name = "PATTERN")]
+    pub template: Option<String>,
+
+    /// Write results to this file
+    #[clap(short = 'o', value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Start at this string
+    #[clap(short = 's', value_name = "STRING")]
+    pub start: Option<String>,
+}
+
+impl CrunchArgs {
+    fn charset(&self, index: usize, default: &'static str) -> &str {
+        match self.charsets.get(index).map(String::as_str) {
+            Some("+") | None => default,
+            Some(charset) => charset,
+        }
+    }
+
+    pub fn pattern(&self) -> Result<String, String> {
+        if self.min > self.max {
+            Err("crunch's MIN can't be greater than MAX.")?
+        }
+        if self.charsets.iter().any(String::is_empty) {
+            Err("crunch charsets can't be empty.")?
+        }
+        let Some(template) = &self.template else {
+            return Ok(format!(
+                "{}{{{},{}}}",
+                class(self.charset(0, LOWER)),
+                self.min,
+                self.max
+            ));
+        };
+        let length = template.chars().count();
+        if self.min != length || self.max != length {
+            Err(format!(
+                "crunch's MIN and MAX must both be {length}, the length of the -t template."
+            ))?
+        }
+        Ok(template
+            .chars()
+            .map(|c| match c {
+                '@' => class(self.charset(0, LOWER)),
+                ',' => class(self.charset(1, UPPER)),
+                '%' => class(self.charset(2, NUMBERS)),
+                '^' => class(self.charset(3, SYMBOLS)),
+                c => regex_syntax::escape(c.encode_utf8(&mut [0; 4])),
+            })
+            .collect())
+    }
+}
+
+#[test]
+fn test_crunch_pattern() {
+    let crunch = |min, max, charsets: &[&str], template: Option<&str>| CrunchArgs {
+        min,
+        max,
+        charsets: charsets.iter().map(|c| c.to_string()).collect(),
+        template: template.map(String::from),
+        output: None,
+        start: None,
+    };
+    assert_eq!(crunch(1, 3, &["ab"], None).pattern().unwrap(), "[ab]{1,3}");
+    assert_eq!(
+        crunch(4, 4, &["+", "XY"], Some("@,-%")).pattern().unwrap(),
+        format!("{}[XY]\\-{}", class(LOWER), class(NUMBERS))
+    );
+    assert!(crunch(3, 4, &[], Some("@@@")).pattern().is_err());
+    assert!(crunch(4, 3, &[], None).pattern().is_err());
+}
@@ -0,0 +1,102 @@
+//! `--output sqlite:FILE`, which stores results in a queryable table rather
+//! than a flat file.
+
+use std::path::Path;
+
+use num_bigint::BigUint;
+use rusqlite::{
+    Connection, params,
+    types::{ToSqlOutput, Value, ValueRef},
+};
+
+/// Rows inserted per transaction.
+const BATCH: usize = 10_000;
+
+/// A `candidates` table with each result's index, text and length in bytes.
+/// Results that aren't valid UTF-8 are stored as blobs, and indices too large
+/// for an SQLite integer as text, which is why `index` has no type: an
+/// INTEGER column would round them to floats.
+pub struct Database {
+    connection: Connection,
+    pending: usize,
+}
+
+impl Database {
+    /// Opens `path`, replacing any `candidates` it has unless `append` is set.
+    pub fn open(path: &Path, append: bool) -> rusqlite::Result<Self> {
+        let connection = Connection::open(path)?;
+        if !append {
+            connection.execute_batch("DROP TABLE IF EXISTS candidates")?;
+        }
+        connection.execute_batch(
+            r#"CREATE TABLE IF NOT EXISTS candidates ("index", candidate TEXT, length INTEGER)"#,
+        )?;
+        Ok(Self {
+            connection,
+            pending: 0,
+        })
+    }
+
+    pub fn insert(&mut self, index: &BigUint, candidate: &[u8]) -> rusqlite::Result<()> {
+        if self.pending == 0 {
+            self.connection.execute_batch("BEGIN")?;
+        }
+        let index = match i64::try_from(index) {
+            Ok(index) => Value::Integer(index),
+            Err(_) => Value::Text(index.to_string()),
+        };
+        let text = match std::str::from_utf8(candidate) {
+            Ok(text) => ToSqlOutput::Borrowed(ValueRef::Text(text.as_bytes())),
+            Err(_) => ToSqlOutput::Borrowed(ValueRef::Blob(candidate)),
+        };
+        self.connection
+            .prepare_cached(
+                r#"INSERT INTO candidates ("index", candidate, length) VALUES (?1, ?2, ?3)"#,
+            )?
+            .execute(params![index, text, candidate.len() as i64])?;
+        self.pending += 1;
+        if self.pending >= BATCH {
+            self.commit()?;
+        }
+        Ok(())
+    }
+
+    /// Commits the rows inserted since the last commit.
+    pub fn commit(&mut self) -> rusqlite::Result<()> {
+        if self.pending > 0 {
+            self.connection.execute_batch("COMMIT")?;
+            self.pending = 0;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_database() {
+    let path = std::env::temp_dir().join(format!("passwd-gen-{}.db", std::process::id()));
+    let big = BigUint::from(1u32) << 70u32;
+    let mut database = Database::open(&path, false).unwrap();
+    database.insert(&3u32.into(), b"pw").unwrap();
+    database.insert(&big, b"\xff").unwrap();
+    database.commit().unwrap();
+    let mut database = Database::open(&path, true).unwrap();
+    database.insert(&4u32.into(), "é".as_bytes()).unwrap();
+    database.commit().unwrap();
+    let rows: Vec<(String, i64)> = database
+        .connection
+        .prepare(r#"SELECT CAST("index" AS TEXT), length FROM candidates"#)
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        rows,
+        [
+            ("3".to_string(), 2),
+            (big.to_string(), 1),
+            ("4".to_string(), 2)
+        ]
+    );
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,75 @@
+//! Common decorations wrapped around every result of a pattern, built as HIR
+//! around the parsed pattern so counting and seeking see them too.
+
+use std::ops::RangeInclusive;
+
+use regex_syntax::hir::{Class, ClassUnicode, ClassUnicodeRange, Hir};
+
+/// Parses a range of years such as `1970-2025`.
+pub fn parse_years(range: &str) -> Result<RangeInclusive<u32>, String> {
+    let (start, end) = range.split_once('-').ok_or("expected FIRST-LAST")?;
+    let year = |year: &str| {
+        year.parse::<u32>()
+            .map_err(|error| format!("{year:?} isn't a year: {error}"))
+    };
+    let (start, end) = (year(start)?, year(end)?);
+    if start > end {
+        Err(format!("{start} comes after {end}"))?
+    }
+    Ok(start..=end)
+}
+
+/// Matches the empty string or any one of `hir`, so the undecorated result
+/// comes first.
+fn optional(hir: Hir) -> Hir {
+    Hir::alternation(vec![Hir::empty(), hir])
+}
+
+fn symbols(symbols: &str) -> Hir {
+    Hir::class(Class::Unicode(ClassUnicode::new(
+        symbols.chars().map(|c| ClassUnicodeRange::new(c, c)),
+    )))
+}
+
+/// Wraps `hir` with an optional symbol from `prepend` before it, and an
+/// optional year from `years` then an optional symbol from `append` after it.
+pub fn decorate(
+    hir: Hir,
+    prepend: Option<&str>,
+    years: Option<RangeInclusive<u32>>,
+    append: Option<&str>,
+) -> Hir {
+    let mut parts = Vec::new();
+    parts.extend(prepend.map(|prepend| optional(symbols(prepend))));
+    parts.push(hir);
+    parts.extend(years.map(|years| {
+        optional(Hir::alternation(
+            years
+                .map(|year| Hir::literal(year.to_string().into_bytes()))
+                .collect(),
+        ))
+    }));
+    parts.extend(append.map(|append| optional(symbols(append))));
+    Hir::concat(parts)
+}
+
+#[test]
+fn test_decorate() {
+    let results = |hir: Hir| -> Vec<String> {
+        passwd_gen::iterate_branches(&hir, 0, None, Default::default())
+            .map(|(_, result)| String::from_utf8(result.to_vec()).unwrap())
+            .collect()
+    };
+    let hir = || Hir::literal(*b"pw");
+    assert_eq!(
+        results(decorate(hir(), Some("#"), None, Some("!?"))),
+        ["pw", "#pw", "pw!", "#pw!", "pw?", "#pw?"]
+    );
+    assert_eq!(
+        results(decorate(hir(), None, Some(1999..=2001), None)),
+        ["pw", "pw1999", "pw2000", "pw2001"]
+    );
+    assert_eq!(parse_years("1970-2025").unwrap(), 1970..=2025);
+    assert!(parse_years("2025-1970").is_err());
+    assert!(parse_years("1970").is_err());
+}
@@ -0,0 +1,107 @@
+use std::{error::Error, fmt};
+
+use regex_syntax::ast::Span;
+
+#[derive(Debug)]
+pub enum GenError {
+    Parse(Box<regex_syntax::Error>),
+    UnknownSet(String),
+    Mask(String),
+    Unbounded,
+    /// The pattern can't be compiled to the automaton sorted output needs.
+    Unsorted(String),
+    /// A backreference the generator can't substitute.
+    Backref(String),
+    /// A malformed `(?N:FIRST-LAST)` numeric range.
+    Range(String),
+    /// A malformed `{date:FORMAT:FIRST-LAST}` token.
+    Date(String),
+}
+
+impl From<regex_syntax::Error> for GenError {
+    fn from(error: regex_syntax::Error) -> Self {
+        GenError::Parse(Box::new(error))
+    }
+}
+
+fn underline(f: &mut fmt::Formatter<'_>, pattern: &str, span: &Span) -> fmt::Result {
+    let line = pattern.lines().nth(span.start.line - 1).unwrap_or("");
+    let start = span.start.column - 1;
+    let end = if span.end.line == span.start.line {
+        span.end.column - 1
+    } else {
+        line.chars().count()
+    };
+    writeln!(f, "  |")?;
+    writeln!(f, "  | {line}")?;
+    write!(
+        f,
+        "  | {}{}",
+        " ".repeat(start),
+        "^".repeat(end.saturating_sub(start).max(1))
+    )
+}
+
+impl fmt::Display for GenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenError::Parse(error) => match error.as_ref() {
+                regex_syntax::Error::Parse(error) => {
+                    writeln!(f, "invalid pattern: {}", error.kind())?;
+                    underline(f, error.pattern(), error.span())
+                }
+                regex_syntax::Error::Translate(error) => {
+                    writeln!(f, "invalid pattern: {}", error.kind())?;
+                    underline(f, error.pattern(), error.span())
+                }
+                error => write!(f, "invalid pattern: {error}"),
+            },
+            GenError::Unbounded => write!(
+                f,
+                "pattern contains an infinite repetition, so a max length must be specified"
+            ),
+            GenError::Mask(error) => write!(f, "invalid mask: {error}"),
+            GenError::Unsorted(error) => {
+                write!(f, "pattern can't be enumerated in sorted order: {error}")
+            }
+            GenError::Backref(error) => write!(f, "invalid backreference: {error}"),
+            GenError::Range(error) => write!(f, "invalid numeric range: {error}"),
+            GenError::Date(error) => write!(f, "invalid date: {error}"),
+            GenError::UnknownSet(name) => {
+                write!(
+                    f,
+                    "pattern references {{{{{name}}}}} but no --set {name}=... was given"
+                )
+            }
+        }
+    }
+}
+
+impl Error for GenError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GenError::Parse(error) => Some(error.as_ref()),
+            GenError::UnknownSet(_)
+            | GenError::Unbounded
+            | GenError::Mask(_)
+            | GenError::Unsorted(_)
+            | GenError::Backref(_)
+            | GenError::Range(_)
+            | GenError::Date(_) => None,
+        }
+    }
+}
+
+#[test]
+fn test_parse_error_display() {
+    let error = GenError::from(regex_syntax::Parser::new().parse("ab{3,1}c").unwrap_err());
+    assert_eq!(
+        error.to_string(),
+        "invalid pattern: invalid repetition count range, the start must be <= the end\n  |\n  | ab{3,1}c\n  |   ^^^^^"
+    );
+    let error = GenError::from(regex_syntax::Parser::new().parse("x\ny(z").unwrap_err());
+    assert_eq!(
+        error.to_string(),
+        "invalid pattern: unclosed group\n  |\n  | y(z\n  |  ^"
+    );
+}
@@ -0,0 +1,153 @@
+//! `--exec`, which runs a command for each result instead of printing it.
+
+use std::{
+    io,
+    process::{Child, Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+/// Splits `command` into words at whitespace, except inside single or double
+/// quotes. A backslash outside single quotes escapes the next character.
+pub fn parse_command(command: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = None::<String>;
+    let mut quote = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None | Some('"'), '\\') => {
+                let escaped = chars
+                    .next()
+                    .ok_or_else(|| format!("{command:?} ends with a backslash"))?;
+                word.get_or_insert_default().push(escaped);
+            }
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_default();
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (_, c) => word.get_or_insert_default().push(c),
+        }
+    }
+    if quote.is_some() {
+        Err(format!("{command:?} has an unterminated quote"))?
+    }
+    words.extend(word);
+    if words.is_empty() {
+        Err("--exec needs a command")?
+    }
+    Ok(words)
+}
+
+/// Runs a command per result, up to `jobs` at once. Each `{}` in its words is
+/// replaced by the result, which is passed as a final argument if there is none.
+pub struct Exec {
+    words: Vec<String>,
+    jobs: usize,
+    stop_on_success: bool,
+    running: Vec<(Child, String)>,
+}
+
+impl Exec {
+    pub fn new(words: Vec<String>, jobs: usize, stop_on_success: bool) -> Self {
+        Self {
+            words,
+            jobs: jobs.max(1),
+            stop_on_success,
+            running: Vec::new(),
+        }
+    }
+
+    fn command(&self, candidate: &str) -> Command {
+        let mut args: Vec<String> = self
+            .words
+            .iter()
+            .map(|word| word.replace("{}", candidate))
+            .collect();
+        if !self.words.iter().any(|word| word.contains("{}")) {
+            args.push(candidate.to_string());
+        }
+        let mut command = Command::new(&args[0]);
+        command.args(&args[1..]).stdin(Stdio::null());
+        command
+    }
+
+    /// Starts the command for `candidate` once fewer than `jobs` are running.
+    /// With `stop_on_success`, returns the result a command succeeded for, after
+    /// killing the others.
+    pub fn run(&mut self, candidate: &str) -> io::Result<Option<String>> {
+        while self.running.len() >= self.jobs {
+            if let Some(found) = self.reap()? {
+                return Ok(Some(found));
+            }
+            if self.running.len() >= self.jobs {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        let child = self.command(candidate).spawn()?;
+        self.running.push((child, candidate.to_string()));
+        Ok(None)
+    }
+
+    /// Waits for every running command, as [`Exec::run`] does for one.
+    pub fn finish(&mut self) -> io::Result<Option<String>> {
+        while !self.running.is_empty() {
+            if let Some(found) = self.reap()? {
+                return Ok(Some(found));
+            }
+            if !self.running.is_empty() {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Removes the commands that have exited.
+    fn reap(&mut self) -> io::Result<Option<String>> {
+        let mut i = 0;
+        while i < self.running.len() {
+            let Some(status) = self.running[i].0.try_wait()? else {
+                i += 1;
+                continue;
+            };
+            let (_, candidate) = self.running.swap_remove(i);
+            if self.stop_on_success && status.success() {
+                for (mut child, _) in self.running.drain(..) {
+                    // The command may have exited since its last check.
+                    let _ = child.kill();
+                    child.wait()?;
+                }
+                return Ok(Some(candidate));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[test]
+fn test_parse_command() {
+    assert_eq!(
+        parse_command(r#"curl -u 'admin:{}' "http://x/a b" c\ d"#).unwrap(),
+        ["curl", "-u", "admin:{}", "http://x/a b", "c d"]
+    );
+    assert_eq!(parse_command("a ''").unwrap(), ["a", ""]);
+    assert!(parse_command("'a").is_err());
+    assert!(parse_command("  ").is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_exec() {
+    let mut exec = Exec::new(parse_command("test {} = c").unwrap(), 2, true);
+    let mut found = None;
+    for candidate in ["a", "b", "c", "d", "e"] {
+        found = exec.run(candidate).unwrap();
+        if found.is_some() {
+            break;
+        }
+    }
+    let found = found.or_else(|| exec.finish().unwrap());
+    assert_eq!(found.as_deref(), Some("c"));
+}
@@ -0,0 +1,305 @@
+use std::fmt::{Display, Write};
+
+use clap::ValueEnum;
+
+/// How each result line is laid out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// The result as it is, or inside the --format template
+    #[default]
+    Plain,
+    /// One JSON object per line: {"index": n, "candidate": "...", "len": k}
+    Jsonl,
+    /// Comma-separated values with a header row, in the order of --columns
+    Csv,
+}
+
+/// How `--encode` writes the bytes of each result.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Encoding {
+    /// Two lowercase hex digits per byte
+    Hex,
+    /// Standard base64 with padding
+    Base64,
+}
+
+/// A field of `--output-format csv`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Column {
+    /// The result's index
+    Index,
+    /// The result itself
+    Candidate,
+    /// Its length in bytes
+    Length,
+    /// Which top-level alternative of the pattern, such as the line of
+    /// --mask-file, it came from
+    Branch,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Piece {
+    Text(String),
+    Result,
+    Index,
+}
+
+/// A `--format` line template, where `{}` (or `%s`) stands for the result and
+/// `{i}` for its index. `{{` and `}}` are literal braces, and `%%` a percent sign.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineFormat(Vec<Piece>);
+
+pub fn parse_format(format: &str) -> Result<LineFormat, String> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut rest = format;
+    while let Some(c) = rest.chars().next() {
+        let (piece, len) = match rest {
+            _ if rest.starts_with("{}") || rest.starts_with("%s") => (Some(Piece::Result), 2),
+            _ if rest.starts_with("{i}") => (Some(Piece::Index), 3),
+            _ if rest.starts_with("{{") || rest.starts_with("}}") || rest.starts_with("%%") => {
+                text.push(c);
+                (None, 2)
+            }
+            _ if c == '{' || c == '}' => Err(format!(
+                "unmatched {c:?} in {format:?}; write {c}{c} for a literal brace"
+            ))?,
+            _ => {
+                text.push(c);
+                (None, c.len_utf8())
+            }
+        };
+        if let Some(piece) = piece {
+            if !text.is_empty() {
+                pieces.push(Piece::Text(std::mem::take(&mut text)));
+            }
+            pieces.push(piece);
+        }
+        rest = &rest[len..];
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(LineFormat(pieces))
+}
+
+impl LineFormat {
+    /// Writes `result` in the template to `line`. `index` is only called if
+    /// the template contains `{i}`.
+    pub fn render<D: Display>(&self, result: &[u8], index: impl Fn() -> D, line: &mut Vec<u8>) {
+        line.clear();
+        for piece in &self.0 {
+            match piece {
+                Piece::Text(text) => line.extend_from_slice(text.as_bytes()),
+                Piece::Result => line.extend_from_slice(result),
+                Piece::Index => line.extend_from_slice(index().to_string().as_bytes()),
+            }
+        }
+    }
+}
+
+/// Appends `text` as a quoted JSON string.
+fn json_string(text: &str, line: &mut String) {
+    line.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => line.push_str("\\\""),
+            '\\' => line.push_str("\\\\"),
+            '\n' => line.push_str("\\n"),
+            '\r' => line.push_str("\\r"),
+            '\t' => line.push_str("\\t"),
+            c if c.is_control() => write!(line, "\\u{:04x}", c as u32).unwrap(),
+            c => line.push(c),
+        }
+    }
+    line.push('"');
+}
+
+/// Writes a JSON Lines record for `result` to `line`. `len` is in bytes, like
+/// --min-length, and invalid UTF-8 is replaced with U+FFFD in `candidate`.
+pub fn jsonl(result: &[u8], index: impl Display, line: &mut Vec<u8>) {
+    let mut record = format!("{{\"index\": {index}, \"candidate\": ");
+    json_string(&String::from_utf8_lossy(result), &mut record);
+    write!(record, ", \"len\": {}}}", result.len()).unwrap();
+    line.clear();
+    line.extend_from_slice(record.as_bytes());
+}
+
+/// Appends `field`, quoted if it contains a comma, quote or line break.
+fn csv_field(field: &str, line: &mut String) {
+    if field.contains([',', '"', '\r', '\n']) {
+        line.push('"');
+        line.push_str(&field.replace('"', "\"\""));
+        line.push('"');
+    } else {
+        line.push_str(field);
+    }
+}
+
+/// The header row naming `columns`.
+pub fn csv_header(columns: &[Column]) -> Vec<u8> {
+    let names: Vec<_> = columns
+        .iter()
+        .map(|column| column.to_possible_value().unwrap().get_name().to_string())
+        .collect();
+    names.join(",").into_bytes()
+}
+
+/// Writes a CSV row of `columns` for `result` to `line`.
+pub fn csv(
+    columns: &[Column],
+    result: &[u8],
+    index: impl Display,
+    branch: usize,
+    line: &mut Vec<u8>,
+) {
+    let mut row = String::new();
+    for (i, column) in columns.iter().enumerate() {
+        if i > 0 {
+            row.push(',');
+        }
+        match column {
+            Column::Index => write!(row, "{index}").unwrap(),
+            Column::Candidate => csv_field(&String::from_utf8_lossy(result), &mut row),
+            Column::Length => write!(row, "{}", result.len()).unwrap(),
+            Column::Branch => write!(row, "{branch}").unwrap(),
+        }
+    }
+    line.clear();
+    line.extend_from_slice(row.as_bytes());
+}
+
+/// The bytes ending each result line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Delimiter(pub Vec<u8>);
+
+/// Parses a `--delimiter`, where `\n`, `\r`, `\t`, `\0`, `\\` and `\xNN` are
+/// escapes.
+pub fn parse_delimiter(delimiter: &str) -> Result<Delimiter, String> {
+    let mut bytes = Vec::new();
+    let mut rest = delimiter.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        rest = tail;
+        if b != b'\\' {
+            bytes.push(b);
+            continue;
+        }
+        let (&escape, tail) = rest
+            .split_first()
+            .ok_or_else(|| format!("{delimiter:?} ends in the middle of an escape"))?;
+        rest = tail;
+        bytes.push(match escape {
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b't' => b'\t',
+            b'0' => b'\0',
+            b'\\' => b'\\',
+            b'x' => {
+                let hex = rest
+                    .get(..2)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| {
+                        format!("\\x in {delimiter:?} must be followed by two hex digits")
+                    })?;
+                rest = &rest[2..];
+                hex
+            }
+            escape => Err(format!(
+                "unknown escape \\{} in {delimiter:?}",
+                escape as char
+            ))?,
+        });
+    }
+    Ok(Delimiter(bytes))
+}
+
+pub fn encode(bytes: &[u8], encoding: Encoding) -> Vec<u8> {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    match encoding {
+        Encoding::Hex => bytes
+            .iter()
+            .flat_map(|&b| [HEX[usize::from(b >> 4)], HEX[usize::from(b & 15)]])
+            .collect(),
+        Encoding::Base64 => {
+            let mut encoded = Vec::with_capacity(bytes.len().div_ceil(3) * 4);
+            for chunk in bytes.chunks(3) {
+                let group = chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0u32, |group, (i, &b)| group | u32::from(b) << (16 - 8 * i));
+                for i in 0..4 {
+                    encoded.push(match i <= chunk.len() {
+                        true => BASE64[(group >> (18 - 6 * i) & 63) as usize],
+                        false => b'=',
+                    });
+                }
+            }
+            encoded
+        }
+    }
+}
+
+#[test]
+fn test_format() {
+    let render = |format: &str| {
+        let mut line = Vec::new();
+        parse_format(format).unwrap().render(b"pw", || 7, &mut line);
+        String::from_utf8(line).unwrap()
+    };
+    assert_eq!(render("admin:{}"), "admin:pw");
+    assert_eq!(render("{i},%s,{}"), "7,pw,pw");
+    assert_eq!(render("{{}} 100%% é"), "{} 100% é");
+    assert!(parse_format("{x}").is_err());
+    assert!(parse_format("}").is_err());
+}
+
+#[test]
+fn test_jsonl() {
+    let mut line = Vec::new();
+    jsonl("a\"\\\n\u{1}é".as_bytes(), 3, &mut line);
+    assert_eq!(
+        String::from_utf8(line).unwrap(),
+        r#"{"index": 3, "candidate": "a\"\\\n\u0001é", "len": 7}"#
+    );
+}
+
+#[test]
+fn test_csv() {
+    let columns = [
+        Column::Index,
+        Column::Candidate,
+        Column::Length,
+        Column::Branch,
+    ];
+    assert_eq!(csv_header(&columns), b"index,candidate,length,branch");
+    let row = |result: &str| {
+        let mut line = Vec::new();
+        csv(&columns, result.as_bytes(), 4, 1, &mut line);
+        String::from_utf8(line).unwrap()
+    };
+    assert_eq!(row("pw"), "4,pw,2,1");
+    assert_eq!(row("a,b"), "4,\"a,b\",3,1");
+    assert_eq!(row("say \"hi\""), "4,\"say \"\"hi\"\"\",8,1");
+}
+
+#[test]
+fn test_encode() {
+    assert_eq!(encode(b"\x00\xffA", Encoding::Hex), b"00ff41");
+    assert_eq!(encode(b"", Encoding::Base64), b"");
+    assert_eq!(encode(b"f", Encoding::Base64), b"Zg==");
+    assert_eq!(encode(b"fo", Encoding::Base64), b"Zm8=");
+    assert_eq!(encode(b"foo", Encoding::Base64), b"Zm9v");
+    assert_eq!(encode(b"\xfb\xff", Encoding::Base64), b"+/8=");
+}
+
+#[test]
+fn test_parse_delimiter() {
+    assert_eq!(parse_delimiter("\\n").unwrap().0, b"\n");
+    assert_eq!(parse_delimiter(", \\x00\\\\").unwrap().0, b", \0\\");
+    assert_eq!(parse_delimiter("").unwrap().0, b"");
+    assert!(parse_delimiter("\\").is_err());
+    assert!(parse_delimiter("\\xg0").is_err());
+    assert!(parse_delimiter("\\q").is_err());
+}
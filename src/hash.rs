@@ -0,0 +1,67 @@
+use clap::ValueEnum;
+use md4::Md4;
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum HashType {
+    Md5,
+    Sha1,
+    Sha256,
+    /// MD4 of the UTF-16LE encoding, as Windows stores passwords
+    Ntlm,
+}
+
+impl HashType {
+    /// Length of the digest in bytes.
+    pub fn digest_len(self) -> usize {
+        match self {
+            HashType::Md5 | HashType::Ntlm => 16,
+            HashType::Sha1 => 20,
+            HashType::Sha256 => 32,
+        }
+    }
+}
+
+pub fn digest(candidate: &[u8], hash_type: HashType) -> Vec<u8> {
+    match hash_type {
+        HashType::Md5 => Md5::digest(candidate).to_vec(),
+        HashType::Sha1 => Sha1::digest(candidate).to_vec(),
+        HashType::Sha256 => Sha256::digest(candidate).to_vec(),
+        HashType::Ntlm => {
+            let utf16: Vec<u8> = String::from_utf8_lossy(candidate)
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes)
+                .collect();
+            Md4::digest(utf16).to_vec()
+        }
+    }
+}
+
+#[test]
+fn test_digest() {
+    let hex = |candidate: &str, hash_type| {
+        String::from_utf8(crate::format::encode(
+            &digest(candidate.as_bytes(), hash_type),
+            crate::format::Encoding::Hex,
+        ))
+        .unwrap()
+    };
+    assert_eq!(
+        hex("abc", HashType::Md5),
+        "900150983cd24fb0d6963f7d28e17f72"
+    );
+    assert_eq!(
+        hex("abc", HashType::Sha1),
+        "a9993e364706816aba3e25717850c26c9cd0d89d"
+    );
+    assert_eq!(
+        hex("abc", HashType::Sha256),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert_eq!(
+        hex("password", HashType::Ntlm),
+        "8846f7eaee8fb117ad06bdd830b7586c"
+    );
+}
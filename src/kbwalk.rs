@@ -0,0 +1,138 @@
+use clap::{Args, ValueEnum};
+use passwd_gen::template;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Layout {
+    #[default]
+    Qwerty,
+    Azerty,
+    Qwertz,
+}
+
+impl Layout {
+    /// The unshifted keys of each row, top to bottom. Each row sits half a
+    /// key to the right of the one above it.
+    fn rows(self) -> [&'static str; 4] {
+        match self {
+            Layout::Qwerty => ["1234567890-=", "qwertyuiop[]", "asdfghjkl;'", "zxcvbnm,./"],
+            Layout::Azerty => ["1234567890)=", "azertyuiop^$", "qsdfghjklmù", "wxcvbn,;:!"],
+            Layout::Qwertz => ["1234567890ß", "qwertzuiopü", "asdfghjklöä", "yxcvbnm,.-"],
+        }
+    }
+}
+
+/// Steps to a neighbouring key as (row, column) offsets: left, right, up
+/// left, up right, down left and down right.
+const DIRECTIONS: [(isize, isize); 6] = [(0, -1), (0, 1), (-1, 0), (-1, 1), (1, -1), (1, 0)];
+
+/// Enumerate walks across neighbouring keys of a keyboard, such as qwer or
+/// 1qaz, optionally between a prefix and suffix pattern
+#[derive(Args)]
+pub struct KbwalkArgs {
+    /// Keyboard layout to walk across
+    #[clap(long, value_enum, default_value_t)]
+    pub layout: Layout,
+
+    /// Fewest keys in a walk
+    #[clap(long, value_name = "N", default_value_t = 4)]
+    pub min_keys: usize,
+
+    /// Most keys in a walk
+    #[clap(long, value_name = "N", default_value_t = 6)]
+    pub max_keys: usize,
+
+    /// Most times a walk may change direction
+    #[clap(long, value_name = "N", default_value_t = 1)]
+    pub max_turns: usize,
+
+    /// Pattern for what comes before each walk
+    #[clap(long, value_name = "PATTERN")]
+    pub prefix: Option<String>,
+
+    /// Pattern for what comes after each walk
+    #[clap(long, value_name = "PATTERN")]
+    pub suffix: Option<String>,
+}
+
+impl KbwalkArgs {
+    /// Every walk, shortest first, then by the key it starts from. No walk
+    /// presses a key twice.
+    fn walks(&self) -> Vec<String> {
+        let rows: Vec<Vec<char>> = self
+            .layout
+            .rows()
+            .iter()
+            .map(|row| row.chars().collect())
+            .collect();
+        let key = |(row, column): (isize, isize)| {
+            let row = rows.get(usize::try_from(row).ok()?)?;
+            row.get(usize::try_from(column).ok()?).copied()
+        };
+        // Each walk in progress is its keys, its position, its last
+        // direction and how many turns it has taken.
+        let mut walks = Vec::new();
+        for (row, keys) in rows.iter().enumerate() {
+            for (column, c) in keys.iter().enumerate() {
+                walks.push((c.to_string(), (row as isize, column as isize), None, 0));
+            }
+        }
+        let mut found = Vec::new();
+        for length in 1..=self.max_keys {
+            if length >= self.min_keys {
+                found.extend(walks.iter().map(|(walk, ..)| walk.clone()));
+            }
+            let mut longer = Vec::new();
+            for (walk, (row, column), last, turns) in &walks {
+                for (direction, (down, right)) in DIRECTIONS.iter().enumerate() {
+                    let turns = turns + usize::from(last.is_some_and(|last| last != direction));
+                    let position = (row + down, column + right);
+                    if turns > self.max_turns {
+                        continue;
+                    }
+                    if let Some(c) = key(position)
+                        && !walk.contains(c)
+                    {
+                        longer.push((format!("{walk}{c}"), position, Some(direction), turns));
+                    }
+                }
+            }
+            walks = longer;
+        }
+        found
+    }
+
+    pub fn pattern(&self) -> String {
+        let wrap = |pattern: &Option<String>| match pattern {
+            Some(pattern) => format!("(?:{pattern})"),
+            None => String::new(),
+        };
+        format!(
+            "{}{}{}",
+            wrap(&self.prefix),
+            template::alternation(&self.walks()),
+            wrap(&self.suffix)
+        )
+    }
+}
+
+#[test]
+fn test_walks() {
+    let kbwalk = |min_keys, max_keys, max_turns| KbwalkArgs {
+        layout: Layout::Qwerty,
+        min_keys,
+        max_keys,
+        max_turns,
+        prefix: None,
+        suffix: Some("[0-9]".to_string()),
+    };
+    let straight = kbwalk(4, 4, 0).walks();
+    for walk in ["qwer", "rewq", "1qaz", "zaq1", "2wsx", "mju7"] {
+        assert!(straight.contains(&walk.to_string()), "{walk}");
+    }
+    assert!(!straight.contains(&"qwea".to_string()));
+    let turning = kbwalk(3, 5, 1).walks();
+    assert!(turning.contains(&"qwesz".to_string()) && turning.contains(&"asd".to_string()));
+    assert!(!turning.contains(&"qwsxc".to_string()));
+    assert!(!turning.iter().any(|walk| walk.chars().count() < 3));
+    assert!(kbwalk(4, 4, 0).pattern().ends_with(")(?:[0-9])"));
+}
@@ -0,0 +1,895 @@
+//! Enumerates every string matched by a regular expression.
+//!
+//! ```
+//! let pattern = passwd_gen::Pattern::new("[ab][0-1]").unwrap();
+//! let results: Vec<String> = pattern.iter().collect();
+//! assert_eq!(results, ["a0", "b0", "a1", "b1"]);
+//! ```
+
+pub mod backref;
+pub mod count;
+pub mod error;
+pub mod mangle;
+pub mod mask;
+pub mod matcher;
+pub mod parallel;
+pub mod rules;
+pub mod sample;
+pub mod sorted;
+pub mod space;
+#[cfg(feature = "async")]
+pub mod stream;
+pub mod template;
+mod walker;
+
+use std::{cell::OnceCell, convert::Infallible};
+
+use clap::ValueEnum;
+use num_bigint::BigUint;
+use num_traits::Zero;
+use regex_syntax::{
+    ParserBuilder,
+    hir::{self, Hir, HirKind::*},
+};
+use smallvec::SmallVec;
+
+use error::GenError;
+use sorted::Sorted;
+use space::Space;
+use walker::Walker;
+
+/// A generated result; most are short enough to stay off the heap.
+pub type Candidate = SmallVec<[u8; 24]>;
+
+struct MultiCartesianProduct<I, F>
+where
+    I: Iterator,
+    F: Fn() -> I,
+{
+    factories: Vec<F>,
+    iters: Vec<I>,
+    heads: Vec<I::Item>,
+    done: bool,
+}
+
+impl<I, F> MultiCartesianProduct<I, F>
+where
+    I: Iterator,
+    F: Fn() -> I,
+{
+    fn new(factories: Vec<F>) -> Self {
+        let mut iters: Vec<_> = factories.iter().map(|f| (f)()).collect();
+        let mut heads = Vec::new();
+        let mut done = false;
+        for iter in &mut iters {
+            if let Some(head) = iter.next() {
+                heads.push(head);
+            } else {
+                done = true;
+                break;
+            }
+        }
+        Self {
+            factories,
+            iters,
+            heads,
+            done,
+        }
+    }
+}
+
+impl<I, F> MultiCartesianProduct<I, F>
+where
+    I: Iterator,
+    F: Fn() -> I,
+{
+    /// Passes the current combination to `f`, then advances to the next one.
+    fn step<R>(&mut self, f: impl FnOnce(&[I::Item]) -> R) -> Option<R> {
+        if self.done {
+            return None;
+        }
+        let result = f(&self.heads);
+        for ((head, iter), factory) in self
+            .heads
+            .iter_mut()
+            .zip(&mut self.iters)
+            .zip(&self.factories)
+        {
+            if let Some(next) = iter.next() {
+                *head = next;
+                return Some(result);
+            } else {
+                *iter = (factory)();
+                *head = iter.next().unwrap();
+            }
+        }
+        self.done = true;
+        Some(result)
+    }
+}
+
+impl<I, F> Iterator for MultiCartesianProduct<I, F>
+where
+    I: Iterator,
+    I::Item: Clone,
+    F: Fn() -> I,
+{
+    type Item = Vec<I::Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.step(<[I::Item]>::to_vec)
+    }
+}
+
+#[test]
+fn test_cartesian() {
+    for item in MultiCartesianProduct::new(vec![
+        || ['a', 'b'].into_iter(),
+        || ['f', 'g'].into_iter(),
+        || ['y', 'z'].into_iter(),
+    ]) {
+        println!("{:?}", item);
+    }
+}
+
+#[test]
+fn test_cartesian_2() {
+    for item in MultiCartesianProduct::new(vec![|| ['a', 'b', 'c'].into_iter(), || {
+        ['f', 'g', 'h'].into_iter()
+    }]) {
+        println!("{:?}", item);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ClassOrder {
+    /// Ascending codepoints
+    #[default]
+    Asc,
+    /// Descending codepoints
+    Desc,
+}
+
+/// Order in which results are yielded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Order {
+    /// The order the pattern is written in
+    #[default]
+    Pattern,
+    /// Shortest first, then lexicographically, with each distinct result once
+    Shortlex,
+    /// Lexicographically, with each distinct result once
+    Lex,
+}
+
+fn ordered<'a, I>(iter: I, order: ClassOrder) -> Box<dyn Iterator<Item = I::Item> + 'a>
+where
+    I: DoubleEndedIterator + 'a,
+{
+    match order {
+        ClassOrder::Asc => Box::new(iter),
+        ClassOrder::Desc => Box::new(iter.rev()),
+    }
+}
+
+/// The most repeats worth generating, since any more would only produce
+/// results longer than `max_length`. `None` means there is no limit.
+pub(crate) fn max_repeats(
+    repetition: &hir::Repetition,
+    max_length: Option<usize>,
+) -> Option<usize> {
+    let min = repetition.min as usize;
+    let limit = max_length.map(
+        |max_length| match repetition.sub.properties().minimum_len() {
+            Some(0) | None => min.max(max_length),
+            Some(sub_length) => max_length / sub_length,
+        },
+    );
+    match (repetition.max, limit) {
+        (Some(max), Some(limit)) => Some((max as usize).min(limit)),
+        (Some(max), None) => Some(max as usize),
+        (None, limit) => limit,
+    }
+}
+
+pub fn iterate_all(
+    hir: &Hir,
+    max_length: Option<usize>,
+    class_order: ClassOrder,
+) -> Box<dyn Iterator<Item = Candidate> + '_> {
+    Box::new(Walker::new(hir, max_length, class_order).map(|(_, x)| x))
+}
+
+/// Like [`iterate_all`], but tags each result with the index of the
+/// top-level alternation branch that produced it, and skips results shorter
+/// than `min_length` without generating them where it can.
+pub fn iterate_branches(
+    hir: &Hir,
+    min_length: usize,
+    max_length: Option<usize>,
+    class_order: ClassOrder,
+) -> Box<dyn Iterator<Item = (usize, Candidate)> + '_> {
+    Box::new(Walker::new(hir, max_length, class_order).min_length(min_length))
+}
+
+/// Like [`iterate_branches`], but from the last result back to the first.
+/// Fails if the pattern is unbounded and there is no max length.
+pub fn iterate_branches_rev(
+    hir: &Hir,
+    min_length: usize,
+    max_length: Option<usize>,
+    class_order: ClassOrder,
+) -> Result<Box<dyn Iterator<Item = (usize, Candidate)> + '_>, GenError> {
+    if max_length.is_none() && is_unbounded(hir) {
+        return Err(GenError::Unbounded);
+    }
+    Ok(Box::new(
+        Walker::new(hir, max_length, class_order)
+            .min_length(min_length)
+            .reverse(),
+    ))
+}
+
+#[test]
+fn test_iterate_branches() {
+    let hir = regex_syntax::Parser::new().parse("(a[0-1]|bc)").unwrap();
+    let tagged: Vec<_> = iterate_branches(&hir, 0, None, ClassOrder::Asc)
+        .map(|(branch, x)| (branch, x.to_vec()))
+        .collect();
+    assert_eq!(
+        tagged,
+        [
+            (0, b"a0".to_vec()),
+            (0, b"a1".to_vec()),
+            (1, b"bc".to_vec())
+        ]
+    );
+}
+
+pub fn is_unbounded(hir: &Hir) -> bool {
+    let mut pending = vec![hir];
+    while let Some(hir) = pending.pop() {
+        match hir.kind() {
+            Repetition(repetition) if repetition.max.is_none() => return true,
+            Repetition(hir::Repetition { sub, .. }) | Capture(hir::Capture { sub, .. }) => {
+                pending.push(sub)
+            }
+            Concat(hirs) | Alternation(hirs) => pending.extend(hirs),
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Whether `hir` contains an assertion such as `^`, `$` or `\b`, which the
+/// generator treats as matching nothing.
+pub fn has_looks(hir: &Hir) -> bool {
+    let mut pending = vec![hir];
+    while let Some(hir) = pending.pop() {
+        match hir.kind() {
+            Look(_) => return true,
+            Repetition(hir::Repetition { sub, .. }) | Capture(hir::Capture { sub, .. }) => {
+                pending.push(sub)
+            }
+            Concat(hirs) | Alternation(hirs) => pending.extend(hirs),
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Rebuilds a pattern from the bottom up without recursing, with each leaf
+/// replaced by what `leaf` makes of it.
+struct MapLeaves<F> {
+    leaf: F,
+    stack: Vec<Hir>,
+}
+
+impl<F: FnMut(&Hir) -> Hir> hir::Visitor for MapLeaves<F> {
+    type Output = Hir;
+    type Err = Infallible;
+
+    fn finish(mut self) -> Result<Hir, Infallible> {
+        Ok(self
+            .stack
+            .pop()
+            .expect("the pattern rebuilds to one expression"))
+    }
+
+    fn visit_post(&mut self, hir: &Hir) -> Result<(), Infallible> {
+        let mut pop = |count: usize| self.stack.split_off(self.stack.len() - count);
+        let rebuilt = match hir.kind() {
+            Repetition(repetition) => Hir::repetition(hir::Repetition {
+                min: repetition.min,
+                max: repetition.max,
+                greedy: repetition.greedy,
+                sub: Box::new(pop(1).remove(0)),
+            }),
+            Capture(capture) => Hir::capture(hir::Capture {
+                index: capture.index,
+                name: capture.name.clone(),
+                sub: Box::new(pop(1).remove(0)),
+            }),
+            Concat(hirs) => Hir::concat(pop(hirs.len())),
+            Alternation(hirs) => Hir::alternation(pop(hirs.len())),
+            _ => (self.leaf)(hir),
+        };
+        self.stack.push(rebuilt);
+        Ok(())
+    }
+}
+
+pub(crate) fn map_leaves(hir: &Hir, leaf: impl FnMut(&Hir) -> Hir) -> Hir {
+    let Ok(hir) = hir::visit(
+        hir,
+        MapLeaves {
+            leaf,
+            stack: Vec::new(),
+        },
+    );
+    hir
+}
+
+/// `hir` with each assertion replaced by the empty string, so it matches
+/// everything `hir` does, ignoring where the assertions hold.
+pub fn strip_looks(hir: &Hir) -> Hir {
+    map_leaves(hir, |hir| match hir.kind() {
+        Look(_) => Hir::empty(),
+        _ => hir.clone(),
+    })
+}
+
+/// `hir` with every class narrowed to the characters in `allowed`. Literals
+/// are left as they are, since they were written out on purpose.
+pub fn restrict_classes(hir: &Hir, allowed: &hir::ClassUnicode) -> Hir {
+    let bytes = hir::ClassBytes::new(allowed.ranges().iter().filter_map(|range| {
+        let start = u8::try_from(range.start()).ok()?;
+        let end = u8::try_from(range.end()).unwrap_or(u8::MAX);
+        Some(hir::ClassBytesRange::new(start, end))
+    }));
+    map_leaves(hir, |hir| match hir.kind() {
+        Class(hir::Class::Unicode(class)) => {
+            let mut class = class.clone();
+            class.intersect(allowed);
+            Hir::class(hir::Class::Unicode(class))
+        }
+        Class(hir::Class::Bytes(class)) => {
+            let mut class = class.clone();
+            class.intersect(&bytes);
+            Hir::class(hir::Class::Bytes(class))
+        }
+        _ => hir.clone(),
+    })
+}
+
+/// `hir` with its top-level branches reordered from the heaviest down, ties
+/// kept in order, along with the weight of each. A branch weighs as much as
+/// the first of `weights` whose name it matches, so a literal branch is named
+/// by itself, and 1 if it matches none.
+pub fn weigh_branches(hir: &Hir, weights: &[(String, u32)]) -> (Hir, Vec<u32>) {
+    let mut hir = hir;
+    while let Capture(capture) = hir.kind() {
+        hir = &capture.sub;
+    }
+    let Alternation(hirs) = hir.kind() else {
+        return (hir.clone(), vec![1]);
+    };
+    let mut weighed: Vec<_> = hirs
+        .iter()
+        .map(|branch| {
+            let weight = weights
+                .iter()
+                .find(|(name, _)| matcher::matches(branch, name.as_bytes(), None))
+                .map_or(1, |&(_, weight)| weight);
+            (branch.clone(), weight)
+        })
+        .collect();
+    weighed.sort_by_key(|&(_, weight)| std::cmp::Reverse(weight));
+    let (hirs, weights) = weighed.into_iter().unzip();
+    (Hir::alternation(hirs), weights)
+}
+
+#[test]
+fn test_weigh_branches() {
+    let hir = regex_syntax::Parser::new()
+        .parse("(?:admin|root|password|[0-9]{4})")
+        .unwrap();
+    let weights = [("password".to_string(), 10), ("1234".to_string(), 5)];
+    let (weighed, weights) = weigh_branches(&hir, &weights);
+    let results: Vec<_> = iterate_all(&weighed, None, ClassOrder::Asc).collect();
+    assert_eq!(&results[0][..], b"password");
+    assert_eq!(&results[1][..], b"0000");
+    assert_eq!(&results[10_001][..], b"admin");
+    assert_eq!(weights, [10, 5, 1, 1]);
+    let (single, weights) = weigh_branches(&regex_syntax::Parser::new().parse("abc").unwrap(), &[]);
+    assert_eq!(single, regex_syntax::Parser::new().parse("abc").unwrap());
+    assert_eq!(weights, [1]);
+}
+
+#[test]
+fn test_restrict_classes() {
+    let ascii = hir::ClassUnicode::new([hir::ClassUnicodeRange::new('\0', '\x7F')]);
+    let hir = regex_syntax::Parser::new().parse(r"\d\w|é.").unwrap();
+    let restricted = restrict_classes(&hir, &ascii);
+    assert_eq!(
+        iterate_all(&restricted, None, ClassOrder::Asc).count(),
+        10 * 63 + 127
+    );
+    let hir = regex_syntax::ParserBuilder::new()
+        .utf8(false)
+        .build()
+        .parse(r"(?-u:[\x00-\xFF])")
+        .unwrap();
+    assert_eq!(
+        iterate_all(&restrict_classes(&hir, &ascii), None, ClassOrder::Asc).count(),
+        128
+    );
+}
+
+#[test]
+fn test_strip_looks() {
+    let hir = regex_syntax::Parser::new().parse(r"^(a\b|b)$").unwrap();
+    assert!(has_looks(&hir));
+    assert_eq!(iterate_all(&hir, None, ClassOrder::Asc).count(), 0);
+    let stripped = strip_looks(&hir);
+    assert!(!has_looks(&stripped));
+    let results: Vec<_> = iterate_all(&stripped, None, ClassOrder::Asc)
+        .map(|x| x.to_vec())
+        .collect();
+    assert_eq!(results, [b"a", b"b"]);
+    let depth = 5000;
+    let pattern = format!("{}^a{}", "(".repeat(depth), ")".repeat(depth));
+    let deep = Pattern::new(&pattern).unwrap();
+    let stripped = strip_looks(deep.hir());
+    let results: Vec<_> = iterate_all(&stripped, None, ClassOrder::Asc).collect();
+    assert_eq!(results, [Candidate::from(&b"a"[..])]);
+}
+
+#[test]
+fn test_unbounded() {
+    let hir = regex_syntax::Parser::new().parse("a*b*").unwrap();
+    let patterns: Vec<_> = iterate_all(&hir, Some(5), ClassOrder::Asc)
+        .map(|s| String::from_utf8_lossy(&s).into_owned())
+        .collect();
+    assert_eq!(
+        patterns,
+        [
+            "", "a", "aa", "aaa", "aaaa", "aaaaa", "b", "ab", "aab", "aaab", "aaaab", "bb", "abb",
+            "aabb", "aaabb", "bbb", "abbb", "aabbb", "bbbb", "abbbb", "bbbbb"
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>()
+    )
+}
+
+#[test]
+fn test_repetition_within_max_length() {
+    let hir = regex_syntax::Parser::new().parse("(a|bb)*").unwrap();
+    let results: Vec<_> = iterate_all(&hir, Some(3), ClassOrder::Asc)
+        .map(|x| x.to_vec())
+        .collect();
+    assert_eq!(
+        results,
+        [&b""[..], b"a", b"bb", b"aa", b"bba", b"abb", b"aaa"]
+    );
+    let hir = regex_syntax::Parser::new().parse("a(?:)b|").unwrap();
+    assert_eq!(iterate_all(&hir, None, ClassOrder::Asc).count(), 2);
+}
+
+#[test]
+fn test_class_order() {
+    let hir = regex_syntax::Parser::new().parse("[a-b][x-y]").unwrap();
+    let desc: Vec<_> = iterate_all(&hir, None, ClassOrder::Desc)
+        .map(|x| x.to_vec())
+        .collect();
+    assert_eq!(desc, [b"by", b"ay", b"bx", b"ax"]);
+    let hir = regex_syntax::Parser::new().parse("[0-24-5]").unwrap();
+    let desc: Vec<_> = iterate_all(&hir, None, ClassOrder::Desc)
+        .map(|x| x.to_vec())
+        .collect();
+    assert_eq!(desc, [b"5", b"4", b"2", b"1", b"0"]);
+}
+
+#[test]
+fn test_large_multibyte_repetition() {
+    let hir = regex_syntax::Parser::new().parse("é{1,1000000}").unwrap();
+    let results: Vec<_> = iterate_all(&hir, Some(5), ClassOrder::Asc)
+        .map(|x| x.to_vec())
+        .collect();
+    assert_eq!(results, ["é".as_bytes(), "éé".as_bytes()]);
+    let hir = regex_syntax::Parser::new().parse("[éü]{100000}").unwrap();
+    assert_eq!(iterate_all(&hir, Some(16), ClassOrder::Asc).count(), 0);
+}
+
+/// A parsed pattern along with the options that control its enumeration.
+pub struct Pattern {
+    hir: Hir,
+    min_length: usize,
+    max_length: Option<usize>,
+    class_order: ClassOrder,
+}
+
+impl Pattern {
+    /// Parses `pattern`, nested as deeply as memory allows.
+    pub fn new(pattern: &str) -> Result<Self, GenError> {
+        let hir = ParserBuilder::new()
+            .nest_limit(u32::MAX)
+            .build()
+            .parse(pattern)?;
+        Ok(Self::from_hir(hir))
+    }
+
+    pub fn from_hir(hir: Hir) -> Self {
+        Self {
+            hir,
+            min_length: 0,
+            max_length: None,
+            class_order: ClassOrder::default(),
+        }
+    }
+
+    /// Only yield results of at least this many bytes.
+    pub fn min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    /// Only yield results of at most this many bytes.
+    pub fn max_length(mut self, max_length: Option<usize>) -> Self {
+        self.max_length = max_length;
+        self
+    }
+
+    pub fn class_order(mut self, class_order: ClassOrder) -> Self {
+        self.class_order = class_order;
+        self
+    }
+
+    pub fn hir(&self) -> &Hir {
+        &self.hir
+    }
+
+    /// Whether the pattern matches infinitely many strings, in which case
+    /// iteration never ends unless a max length is set.
+    pub fn is_unbounded(&self) -> bool {
+        is_unbounded(&self.hir)
+    }
+
+    /// Compiles the per-length count model used for counting, sampling and
+    /// random access. Fails if the pattern is unbounded with no max length.
+    pub fn space(&self) -> Result<Space, GenError> {
+        Ok(Space::new(&self.hir, self.max_length)?
+            .min_length(self.min_length)
+            .class_order(self.class_order))
+    }
+
+    /// The result at `index` in enumeration order, without generating the
+    /// ones before it. Each call compiles the count model, so use
+    /// [`Pattern::space`] for repeated lookups.
+    pub fn nth_string(&self, index: u128) -> Result<Option<String>, GenError> {
+        Ok(self
+            .space()?
+            .unrank(&index.into())
+            .map(|result| String::from_utf8_lossy(&result).into_owned()))
+    }
+
+    /// The index at which `s` is first produced, the inverse of
+    /// [`Pattern::nth_string`]. `None` if the pattern never produces it, or
+    /// if the index doesn't fit in a `u128`.
+    pub fn rank(&self, s: &str) -> Result<Option<u128>, GenError> {
+        Ok(self
+            .space()?
+            .rank(s.as_bytes())
+            .and_then(|rank| u128::try_from(&rank).ok()))
+    }
+
+    /// Iterates over the distinct results as raw bytes, shortest first and
+    /// then in byte order, or in descending byte order within each length
+    /// if the class order is descending.
+    pub fn shortlex(&self) -> Result<Sorted, GenError> {
+        Sorted::shortlex(
+            &self.hir,
+            self.min_length,
+            self.max_length,
+            self.class_order,
+        )
+    }
+
+    /// Iterates over the distinct results as raw bytes in byte order, or in
+    /// descending byte order if the class order is descending. Fails if the
+    /// pattern is unbounded and there is no max length.
+    pub fn lex(&self) -> Result<Sorted, GenError> {
+        Sorted::lex(
+            &self.hir,
+            self.min_length,
+            self.max_length,
+            self.class_order,
+        )
+    }
+
+    /// Iterates over results as strings, replacing invalid UTF-8 with U+FFFD.
+    pub fn iter(&self) -> RegexIterator<'_> {
+        RegexIterator {
+            inner: self.bytes(),
+        }
+    }
+
+    /// Iterates over results as raw bytes.
+    pub fn bytes(&self) -> RegexBytesIterator<'_> {
+        RegexBytesIterator {
+            pattern: self,
+            front: Box::new(self.walker()),
+            back: None,
+            taken: 0,
+            remaining: None,
+            space: OnceCell::new(),
+        }
+    }
+
+    fn walker(&self) -> Walker {
+        Walker::new(&self.hir, self.max_length, self.class_order).min_length(self.min_length)
+    }
+}
+
+pub struct RegexBytesIterator<'a> {
+    pattern: &'a Pattern,
+    front: Box<dyn Iterator<Item = (usize, Candidate)> + 'a>,
+    back: Option<Walker>,
+    /// Results taken from the front, which is the index of the next one.
+    taken: u128,
+    /// Results left between the two ends, once the back is in use.
+    remaining: Option<BigUint>,
+    /// The count model, if the pattern is bounded, compiled when first
+    /// needed.
+    space: OnceCell<Option<Space>>,
+}
+
+impl RegexBytesIterator<'_> {
+    /// The iterator as an [`ExactSizeIterator`], or `None` if the pattern is
+    /// unbounded with no max length or has more than `usize::MAX` results
+    /// left.
+    pub fn exact(self) -> Option<Exact<Self>> {
+        self.size_hint().1.is_some().then_some(Exact(self))
+    }
+
+    fn space(&self) -> Option<&Space> {
+        self.space
+            .get_or_init(|| self.pattern.space().ok())
+            .as_ref()
+    }
+
+    fn total(&self) -> Option<BigUint> {
+        self.space().map(Space::total)
+    }
+
+    /// The number of results left, if the pattern is bounded.
+    fn left(&self) -> Option<BigUint> {
+        match &self.remaining {
+            Some(remaining) => Some(remaining.clone()),
+            None => Some(self.total()? - self.taken),
+        }
+    }
+
+    /// Claims one of the results left between the ends, if there are any.
+    fn claim(&mut self) -> bool {
+        match &mut self.remaining {
+            Some(remaining) if remaining.is_zero() => false,
+            Some(remaining) => {
+                *remaining -= 1u32;
+                true
+            }
+            None => true,
+        }
+    }
+}
+
+impl Iterator for RegexBytesIterator<'_> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        if !self.claim() {
+            return None;
+        }
+        let (_, result) = self.front.next()?;
+        self.taken += 1;
+        Some(result.to_vec())
+    }
+
+    /// Seeks arithmetically to the result `n` on, rather than generating the
+    /// ones before it, if the pattern is bounded.
+    fn nth(&mut self, n: usize) -> Option<Vec<u8>> {
+        let Some(left) = self.left() else {
+            for _ in 0..n {
+                self.next()?;
+            }
+            return self.next();
+        };
+        if BigUint::from(n) >= left {
+            self.remaining = Some(BigUint::zero());
+            return None;
+        }
+        if let Some(remaining) = &mut self.remaining {
+            *remaining -= n;
+        }
+        self.taken += n as u128;
+        let index = BigUint::from(self.taken);
+        let space = self.space()?;
+        self.front = space.iterate_branches_from(&self.pattern.hir, &index);
+        self.next()
+    }
+
+    /// Exact for bounded patterns with at most `usize::MAX` results left.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.left().map(usize::try_from) {
+            Some(Ok(left)) => (left, Some(left)),
+            Some(Err(_)) => (usize::MAX, None),
+            None => (0, None),
+        }
+    }
+}
+
+impl DoubleEndedIterator for RegexBytesIterator<'_> {
+    /// Iterating from the back needs to know how many results there are.
+    ///
+    /// # Panics
+    ///
+    /// If the pattern is unbounded and there is no max length.
+    fn next_back(&mut self) -> Option<Vec<u8>> {
+        if self.remaining.is_none() {
+            let total = self
+                .total()
+                .expect("iterating from the back needs a bounded pattern or a max length");
+            self.remaining = Some(total - self.taken);
+            self.back = Some(self.pattern.walker().reverse());
+        }
+        if !self.claim() {
+            return None;
+        }
+        let (_, result) = self.back.as_mut()?.next()?;
+        Some(result.to_vec())
+    }
+}
+
+pub struct RegexIterator<'a> {
+    inner: RegexBytesIterator<'a>,
+}
+
+impl Iterator for RegexIterator<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        self.inner
+            .next()
+            .map(|result| String::from_utf8_lossy(&result).into_owned())
+    }
+
+    fn nth(&mut self, n: usize) -> Option<String> {
+        self.inner
+            .nth(n)
+            .map(|result| String::from_utf8_lossy(&result).into_owned())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for RegexIterator<'_> {
+    fn next_back(&mut self) -> Option<String> {
+        self.inner
+            .next_back()
+            .map(|result| String::from_utf8_lossy(&result).into_owned())
+    }
+}
+
+impl RegexIterator<'_> {
+    /// As for [`RegexBytesIterator::exact`].
+    pub fn exact(self) -> Option<Exact<Self>> {
+        self.size_hint().1.is_some().then_some(Exact(self))
+    }
+}
+
+/// A pattern iterator whose results left are known to fit in a `usize`,
+/// made by the iterators' `exact` methods.
+pub struct Exact<I>(I);
+
+impl<I: Iterator> Iterator for Exact<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        self.0.next()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<I::Item> {
+        self.0.nth(n)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<I: Iterator> ExactSizeIterator for Exact<I> {}
+
+impl<I: DoubleEndedIterator> DoubleEndedIterator for Exact<I> {
+    fn next_back(&mut self) -> Option<I::Item> {
+        self.0.next_back()
+    }
+}
+
+#[test]
+fn test_pattern() {
+    let pattern = Pattern::new("a+").unwrap().max_length(Some(3));
+    assert!(pattern.is_unbounded());
+    assert_eq!(pattern.iter().collect::<Vec<_>>(), ["a", "aa", "aaa"]);
+    let hir = regex_syntax::ParserBuilder::new()
+        .utf8(false)
+        .build()
+        .parse("(?-u)[\\xfe-\\xff]")
+        .unwrap();
+    let pattern = Pattern::from_hir(hir);
+    assert_eq!(pattern.bytes().collect::<Vec<_>>(), [[0xfe], [0xff]]);
+    assert_eq!(pattern.iter().collect::<Vec<_>>(), ["\u{fffd}", "\u{fffd}"]);
+    let pattern = Pattern::new("[a-c][0-1]").unwrap();
+    assert_eq!(pattern.nth_string(4).unwrap().as_deref(), Some("b1"));
+    assert_eq!(pattern.nth_string(6).unwrap(), None);
+    assert_eq!(pattern.rank("b1").unwrap(), Some(4));
+    assert_eq!(pattern.rank("d1").unwrap(), None);
+}
+
+#[test]
+fn test_double_ended() {
+    let pattern = Pattern::new("[a-c][0-1]").unwrap();
+    assert_eq!(
+        pattern.iter().rev().collect::<Vec<_>>(),
+        ["c1", "b1", "a1", "c0", "b0", "a0"]
+    );
+    let mut iter = pattern.iter();
+    assert_eq!(iter.next().as_deref(), Some("a0"));
+    assert_eq!(iter.next_back().as_deref(), Some("c1"));
+    assert_eq!(iter.collect::<Vec<_>>(), ["b0", "c0", "a1", "b1"]);
+}
+
+#[test]
+fn test_size_hint() {
+    let pattern = Pattern::new("[a-c][0-1]|x").unwrap().min_length(2);
+    let mut iter = pattern.iter();
+    assert_eq!(iter.size_hint(), (6, Some(6)));
+    iter.next();
+    iter.next_back();
+    let mut iter = iter.exact().unwrap();
+    assert_eq!(iter.len(), 4);
+    assert_eq!(iter.by_ref().count(), 4);
+    assert_eq!(iter.len(), 0);
+    let pattern = Pattern::new("a+").unwrap();
+    assert_eq!(pattern.iter().size_hint(), (0, None));
+    assert!(pattern.iter().exact().is_none());
+    let pattern = pattern.max_length(Some(2));
+    assert_eq!(pattern.iter().exact().unwrap().len(), 2);
+    let pattern = Pattern::new("[a-z]{20}").unwrap();
+    assert_eq!(pattern.bytes().size_hint(), (usize::MAX, None));
+    assert!(pattern.bytes().exact().is_none());
+}
+
+#[test]
+fn test_nth() {
+    let pattern = Pattern::new("[a-z]{12}").unwrap();
+    assert_eq!(
+        pattern.iter().nth(3 * 26usize.pow(11) + 1).as_deref(),
+        Some("baaaaaaaaaad")
+    );
+    let mut iter = pattern.iter().skip(26usize.pow(12) - 1);
+    assert_eq!(iter.next().as_deref(), Some("zzzzzzzzzzzz"));
+    assert_eq!(iter.next(), None);
+    let pattern = Pattern::new("[a-c][0-1]").unwrap();
+    let mut iter = pattern.iter();
+    assert_eq!(iter.next().as_deref(), Some("a0"));
+    assert_eq!(iter.next_back().as_deref(), Some("c1"));
+    assert_eq!(iter.nth(2).as_deref(), Some("a1"));
+    assert_eq!(iter.size_hint(), (1, Some(1)));
+    assert_eq!(iter.nth(1), None);
+    assert_eq!(iter.next(), None);
+    let pattern = Pattern::new("a+").unwrap();
+    assert_eq!(pattern.iter().nth(2).as_deref(), Some("aaa"));
+}
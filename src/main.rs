@@ -1,244 +1,2193 @@
+mod analyze;
+mod checkpoint;
+mod combine;
+mod coverage;
+mod crack;
+mod crunch;
+mod database;
+mod decorate;
+mod exec;
+mod format;
+mod hash;
+mod kbwalk;
+mod markov;
+mod output;
+mod passphrase;
+mod pcfg;
+mod pins;
+mod policy;
+mod prefix;
+mod progress;
+mod pronounceable;
+mod scripts;
+mod serve;
+mod shard;
+mod stats;
+
 use std::{
+    borrow::Cow,
+    cell::Cell,
+    env,
     error::Error,
-    iter::{empty, once},
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, IsTerminal, stdout},
+    iter::{self, empty},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
 };
 
-use clap::Parser as ClapParser;
+use clap::{
+    CommandFactory, FromArgMatches, Parser as ClapParser, Subcommand, error::ErrorKind,
+    parser::ValueSource,
+};
+use num_bigint::BigUint;
 
+use combine::CombineArgs;
+use coverage::Tally;
+use crack::CrackArgs;
+use crunch::CrunchArgs;
+use database::Database;
+use exec::Exec;
+use format::{Column, Delimiter, Encoding, LineFormat, OutputFormat};
+use hash::HashType;
+use kbwalk::KbwalkArgs;
+use markov::MarkovArgs;
+use num_traits::ToPrimitive;
+#[cfg(unix)]
+use output::Feeder;
+use output::{Compression, FanOut, FileWriter, SplitFiles};
+use passphrase::PassphraseArgs;
+use passwd_gen::{
+    Candidate, ClassOrder, Order, Pattern,
+    backref::{self, Backrefs},
+    count,
+    error::GenError,
+    iterate_branches, iterate_branches_rev,
+    mangle::{self, CaseMode, LeetTable, Mirror, PadOverflow, Padding},
+    mask, matcher,
+    parallel::ParallelBranches,
+    rules, sample,
+    space::Space,
+    template::{self, NamedSet},
+};
+use pcfg::PcfgArgs;
+use pins::PinsArgs;
+use prefix::{PerPrefix, Seeking};
+use progress::Progress;
+use pronounceable::PronounceableArgs;
+use regex::Regex;
+use regex_automata::meta::Regex as MetaRegex;
 use regex_syntax::{
-    Parser,
-    hir::{Class::*, Hir, HirKind::*},
+    ParserBuilder,
+    hir::{Capture, Class, ClassUnicode, ClassUnicodeRange, Hir, HirKind, Look},
 };
+use serve::ServeArgs;
+use shard::Shard;
+use stats::Drops;
 
-struct MultiCartesianProduct<I, F>
-where
-    I: Iterator,
-    F: Fn() -> I,
-{
-    factories: Vec<F>,
-    iters: Vec<I>,
-    heads: Vec<I::Item>,
-    done: bool,
-}
-
-impl<I, F> MultiCartesianProduct<I, F>
-where
-    I: Iterator,
-    F: Fn() -> I,
-{
-    fn new(factories: Vec<F>) -> Self {
-        let mut iters: Vec<I> = factories.iter().map(|f| (f)()).collect();
-        let mut heads = Vec::new();
-        let mut done = false;
-        for iter in &mut iters {
-            if let Some(head) = iter.next() {
-                heads.push(head);
-            } else {
-                done = true;
-                break;
+fn escape_nonprintable(pattern: &str) -> String {
+    pattern
+        .chars()
+        .map(|c| match c {
+            '\t' => "\\t".to_string(),
+            '\n' => "\\n".to_string(),
+            '\r' => "\\r".to_string(),
+            c if c.is_control() || (c.is_whitespace() && c != ' ') => {
+                format!("\\u{{{:x}}}", c as u32)
             }
-        }
-        Self {
-            factories,
-            iters,
-            heads,
-            done,
-        }
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+#[test]
+fn test_escape_nonprintable() {
+    assert_eq!(escape_nonprintable("[a-z]\\d{2}"), "[a-z]\\d{2}");
+    assert_eq!(
+        escape_nonprintable("a\tb\n\u{0}\u{a0} "),
+        "a\\tb\\n\\u{0}\\u{a0} "
+    );
+}
+
+/// Regex iterator. A bare pattern is the same as `generate PATTERN`
+#[derive(ClapParser)]
+#[clap(subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    #[clap(flatten)]
+    args: Args,
+}
+
+/// The options that build the pattern, which every subcommand that takes one
+/// honors.
+#[derive(clap::Args)]
+struct PatternArgs {
+    /// Pattern to iterate over, or - to read it from stdin. Several are combined
+    /// according to --mode. (?N:1-31) matches the numbers 1 to 31, in numeric order
+    /// with --order shortlex, and (?N:01-31) the same padded to two digits.
+    /// {date:DDMMYYYY:1980-2010} matches each date of those years, written with
+    /// YYYY, YY, MM, DD, M and D
+    #[clap(required_unless_present_any = ["mask", "mask_file", "patterns_file", "pattern_stdin"])]
+    password_pattern: Vec<String>,
+
+    /// Read the pattern from stdin, which spares it from shell quoting. A final
+    /// newline is left out
+    #[clap(long, conflicts_with_all = ["password_pattern", "mask", "mask_file", "patterns_file"])]
+    pattern_stdin: bool,
+
+    /// Enumerate each pattern of this file in turn, one per line, skipping blank
+    /// lines and # comments. --num then limits the results of each pattern
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["password_pattern", "mask", "mask_file"])]
+    patterns_file: Option<PathBuf>,
+
+    /// How to handle assertions such as ^, $ and \b
+    #[clap(long, visible_alias = "anchors", value_enum, default_value_t)]
+    looks: Looks,
+
+    /// Match letters in either case, as if the pattern began with (?i)
+    #[clap(long)]
+    case_insensitive: bool,
+
+    /// Expand classes such as \w, \d and . to all of Unicode rather than
+    /// ASCII or bytes, as (?u) and (?-u) do within a pattern
+    #[clap(long, value_name = "BOOL", default_value_t = true, action = clap::ArgAction::Set)]
+    unicode: bool,
+
+    /// Let . match a newline too, as if the pattern began with (?s)
+    #[clap(long)]
+    dot_matches_newline: bool,
+
+    /// Expand classes such as \w, \d, \s and . to ASCII characters only
+    #[clap(long)]
+    ascii: bool,
+
+    /// Narrow every class in the pattern to these characters, written as the
+    /// inside of a [...] class, such as a-zA-Z0-9!@#
+    #[clap(long, value_name = "CHARS", value_parser = parse_charset)]
+    charset: Option<ClassUnicode>,
+
+    /// Narrow every class to characters of these Unicode scripts, such as
+    /// Latin,Cyrillic. Digits and punctuation are in the Common script
+    #[clap(long, value_name = "SCRIPT,...", value_delimiter = ',', value_parser = scripts::parse_script)]
+    scripts: Vec<ClassUnicode>,
+
+    /// Narrow every class to characters of these Unicode blocks, such as
+    /// "Basic Latin,Cyrillic". With --scripts, classes keep the characters of either
+    #[clap(long, value_name = "BLOCK,...", value_delimiter = ',', value_parser = scripts::parse_block)]
+    blocks: Vec<ClassUnicode>,
+
+    /// How to combine several patterns
+    #[clap(long, value_enum, default_value_t)]
+    mode: Mode,
+
+    /// Weigh top-level branches, such as password:10,admin:2. Heavier branches are
+    /// enumerated first, and each of their results is drawn that many times as often
+    /// by --sample. A branch is named by any string it matches, and others weigh 1
+    #[clap(
+        long,
+        value_name = "NAME:WEIGHT,...",
+        value_delimiter = ',',
+        value_parser = parse_weight
+    )]
+    weights: Vec<(String, u32)>,
+
+    /// Enumerate a hashcat-style mask such as ?u?l?l?d?d instead of a pattern
+    #[clap(long, conflicts_with = "password_pattern")]
+    mask: Option<String>,
+
+    /// Enumerate each mask of a hashcat .hcmask file in turn
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["password_pattern", "mask"])]
+    mask_file: Option<PathBuf>,
+
+    /// Custom charset for ?1 in masks, such as ?l?d or abc
+    #[clap(short = '1', long, value_name = "CHARSET")]
+    custom_charset1: Option<String>,
+
+    /// Custom charset for ?2 in masks
+    #[clap(short = '2', long, value_name = "CHARSET")]
+    custom_charset2: Option<String>,
+
+    /// Custom charset for ?3 in masks
+    #[clap(short = '3', long, value_name = "CHARSET")]
+    custom_charset3: Option<String>,
+
+    /// Custom charset for ?4 in masks
+    #[clap(short = '4', long, value_name = "CHARSET")]
+    custom_charset4: Option<String>,
+
+    /// Minimum result length. Shorter results are pruned during generation, and
+    /// indices such as those of --skip, --rank and --shard leave them out
+    #[clap(short = 'i', long, default_value_t = 0)]
+    min_length: usize,
+
+    /// Maximum result length
+    #[clap(short = 'x', long)]
+    max_length: Option<usize>,
+
+    /// Only generate results of exactly this length, like an equal min and max length
+    #[clap(long, value_name = "N", conflicts_with_all = ["min_length", "max_length"])]
+    length: Option<usize>,
+
+    /// Order in which members of a character class are enumerated
+    #[clap(long, value_enum, default_value_t)]
+    class_order: ClassOrder,
+
+    /// Print the pattern as received, with non-printable characters escaped
+    #[clap(long)]
+    show_pattern: bool,
+
+    /// Define a named list of strings, inserted wherever the pattern contains {{NAME}}
+    #[clap(long, value_name = "NAME=A,B,...", value_parser = template::parse_set)]
+    set: Vec<NamedSet>,
+
+    /// Like --set, but read the strings from a dictionary file, one per line
+    #[clap(long, value_name = "NAME=PATH", value_parser = template::parse_wordlist)]
+    wordlist: Vec<NamedSet>,
+
+    /// Also emit each result preceded by one of these symbols
+    #[clap(long, value_name = "SYMS")]
+    prepend: Option<String>,
+
+    /// Also emit each result followed by a year in this range, such as 1970-2025
+    #[clap(long, value_name = "FIRST-LAST", value_parser = decorate::parse_years)]
+    append_years: Option<RangeInclusive<u32>>,
+
+    /// Also emit each result followed by one of these symbols, after any year
+    #[clap(long, value_name = "SYMS")]
+    append: Option<String>,
+}
+
+impl PatternArgs {
+    /// The options for just `pattern`, with the rest left at their defaults.
+    fn new(pattern: &str) -> Self {
+        let command = <Self as clap::Args>::augment_args(clap::Command::new("pattern"));
+        let matches = command.get_matches_from(["pattern", "--", pattern]);
+        Self::from_arg_matches(&matches).unwrap()
     }
 }
 
-impl<I, F> Iterator for MultiCartesianProduct<I, F>
-where
-    I: Iterator,
-    I::Item: Clone,
-    F: Fn() -> I,
-{
-    type Item = Vec<I::Item>;
+#[derive(clap::Args)]
+struct Args {
+    #[clap(flatten)]
+    pattern: PatternArgs,
+
+    /// Take results of a union from each pattern in turn, rather than all of
+    /// one before the next. --unique then drops results an earlier pattern matches
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "sequential", "skip", "skip_past", "resume", "checkpoint", "shard", "threads",
+            "sample", "order", "reverse", "weights",
+        ]
+    )]
+    interleave: bool,
+
+    /// Yield all results of each pattern of a union before the next, the default
+    #[clap(long)]
+    sequential: bool,
+
+    /// Maximum number of results to yield
+    #[clap(short = 'n', long)]
+    num: Option<usize>,
+
+    /// Resume output just after this candidate
+    #[clap(long, value_name = "VALUE")]
+    skip_past: Option<String>,
+
+    /// Start output at the candidate with this index, skipping the ones before it
+    #[clap(
+        long,
+        visible_alias = "offset",
+        value_name = "N",
+        conflicts_with = "skip_past"
+    )]
+    skip: Option<BigUint>,
+
+    /// Order in which results are yielded. Sorted orders yield each distinct
+    /// result once, and can't seek into the enumeration
+    #[clap(
+        long,
+        value_enum,
+        default_value_t,
+        conflicts_with_all = [
+            "skip", "skip_past", "resume", "checkpoint", "shard", "threads", "sample",
+            "coverage_report",
+        ]
+    )]
+    order: Order,
+
+    /// Enumerate from the last result back to the first
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "skip", "skip_past", "resume", "checkpoint", "shard", "threads", "sample", "order",
+        ]
+    )]
+    reverse: bool,
+
+    /// Yield results that an ambiguous pattern produces more than once only
+    /// the first time, without keeping a set of the ones seen
+    #[clap(long, conflicts_with_all = ["sample", "reverse"])]
+    unique: bool,
+
+    /// Yield at most this many results for each distinct prefix, stopping once
+    /// every prefix has had them
+    #[clap(long, value_name = "K")]
+    per_prefix: Option<usize>,
+
+    /// Length in characters of the prefix used by --per-prefix
+    #[clap(long, value_name = "L", default_value_t = 1, requires = "per_prefix")]
+    prefix_len: usize,
+
+    /// Show a status line on stderr with the results written per second and,
+    /// when the pattern can be counted, the percentage done and an ETA
+    #[clap(long)]
+    progress: bool,
+
+    /// Generate for this many seconds, 10 if not given, without writing results
+    /// anywhere, then print the results and bytes generated per second
+    #[clap(
+        long,
+        value_name = "SECONDS",
+        num_args = 0..=1,
+        default_missing_value = "10",
+        conflicts_with_all = ["output", "exec", "checkpoint", "resume"]
+    )]
+    benchmark: Option<f64>,
+
+    /// Write at most this many results per second, for consumers that can't
+    /// keep up with a burst
+    #[clap(long, value_name = "N")]
+    rate: Option<f64>,
+
+    /// Stop before the output would grow past this size, such as 50G. Sizes take
+    /// K, M, G or T suffixes, in powers of 1024
+    #[clap(long, value_name = "SIZE", value_parser = output::parse_size)]
+    max_bytes: Option<u64>,
+
+    /// Check that each generated result is matched by the pattern, as compiled
+    /// by the regex crate's engine, and stop at the first that isn't
+    #[clap(long)]
+    verify: bool,
+
+    /// Stop generating after this long, such as 90s, 15m, 2h or 1d, and print the
+    /// index to resume from
+    #[clap(long, value_name = "DURATION", value_parser = parse_duration)]
+    timeout: Option<Duration>,
+
+    /// When the run ends, print to stderr the results and bytes written, the
+    /// time taken and how many results each filter dropped
+    #[clap(long)]
+    stats: bool,
+
+    /// Write results to this file instead of stdout, or to the candidates table
+    /// of an SQLite database given as sqlite:FILE
+    #[clap(short = 'o', long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Run this command for each result instead of printing it, with {} replaced
+    /// by the result, or the result appended if there's no {}. Not run through a shell
+    #[clap(long, value_name = "CMD", conflicts_with = "output")]
+    exec: Option<String>,
+
+    /// Number of --exec commands to run at once
+    #[clap(long, value_name = "N", default_value_t = 1, requires = "exec")]
+    jobs: usize,
+
+    /// Stop once an --exec command exits successfully, and print the result it was given
+    #[clap(long, requires = "exec")]
+    stop_on_success: bool,
+
+    /// Serve results over this Unix socket, giving each client that connects
+    /// the next batch of --batch-size results
+    #[cfg(unix)]
+    #[clap(long, value_name = "SOCKET", conflicts_with_all = ["output", "exec"])]
+    listen: Option<PathBuf>,
+
+    /// Results in each --listen batch
+    #[cfg(unix)]
+    #[clap(long, value_name = "N", default_value_t = 10_000, requires = "listen")]
+    batch_size: usize,
+
+    /// Add to the end of the --output file instead of replacing it
+    #[clap(long, requires = "output")]
+    append_output: bool,
+
+    /// Compress --output as it is written, with gzip or zstd and an optional
+    /// level, such as zstd:19
+    #[clap(long, value_name = "CODEC[:LEVEL]", value_parser = output::parse_compression, requires = "output")]
+    compress: Option<Compression>,
+
+    /// Split --output across FILE.0000, FILE.0001, ... of at most N results each
+    #[clap(long, value_name = "N", requires = "output", conflicts_with_all = ["append_output", "resume"])]
+    split_lines: Option<u64>,
+
+    /// Split --output across files of at most this size, such as 1G
+    #[clap(
+        long,
+        value_name = "SIZE",
+        value_parser = output::parse_size,
+        requires = "output",
+        conflicts_with_all = ["append_output", "resume"]
+    )]
+    split_size: Option<u64>,
+
+    /// Also write results to stdout when writing to a file
+    #[clap(long, requires = "output")]
+    tee: bool,
+
+    /// Only write every Nth result to stdout when teeing
+    #[clap(long, value_name = "N", default_value_t = 1, requires = "tee")]
+    tee_every: usize,
+
+    /// End each result with a NUL byte instead of a newline, for xargs -0
+    #[clap(short = '0', long)]
+    null: bool,
+
+    /// End each result with this instead of a newline. Accepts \n, \t, \0 and \xNN escapes
+    #[clap(long, value_name = "STRING", value_parser = format::parse_delimiter, conflicts_with = "null")]
+    delimiter: Option<Delimiter>,
+
+    /// Write the exact bytes of each result, rather than replacing invalid UTF-8.
+    /// Allows byte patterns such as (?-u)[\x00-\xff]{4}
+    #[clap(long, conflicts_with = "encode")]
+    raw: bool,
+
+    /// Flush output after every N results instead of whenever the buffer fills
+    #[clap(long, value_name = "N")]
+    flush_every: Option<usize>,
+
+    /// Report to stderr what fraction of the distinct results of each length and
+    /// top-level branch was emitted, out of all the pattern generates before filtering
+    #[clap(long)]
+    coverage_report: bool,
+
+    /// Emit every upper/lower case variant of each result (2^letters outputs per result)
+    #[clap(long, visible_alias = "case-permute")]
+    toggle_case: bool,
+
+    /// Only emit case variants with at most this many uppercased letters
+    #[clap(long, value_name = "N", requires = "toggle_case")]
+    max_toggles: Option<usize>,
+
+    /// Which case variants --toggle-case emits
+    #[clap(long, value_enum, default_value_t, requires = "toggle_case")]
+    case: CaseMode,
+
+    /// Emit every leetspeak variant of each result, such as p4ssw0rd for password
+    #[clap(long)]
+    leet: bool,
+
+    /// Replace the built-in leetspeak table with these substitutions. Repeatable
+    #[clap(long, value_name = "C=SUBS", value_parser = mangle::parse_leet_substitution, requires = "leet")]
+    leet_sub: Vec<(char, Vec<char>)>,
+
+    /// Only keep results that also fully match this regex (repeatable; all must match).
+    /// This filters generated results, so it is only efficient when most of them pass
+    #[clap(long, value_name = "REGEX")]
+    require: Vec<String>,
+
+    /// Only keep results containing a match of this regex anywhere (repeatable;
+    /// all must match), such as `\d` for results with a digit in them
+    #[clap(long, value_name = "REGEX")]
+    filter: Vec<String>,
+
+    /// Drop results containing a match of this regex anywhere (repeatable), such
+    /// as `^admin` to skip prefixes already tested
+    #[clap(long, value_name = "REGEX")]
+    exclude: Vec<String>,
+
+    /// Only keep results that zxcvbn scores at least this strong, from 0 (guessable
+    /// in 10^3 tries) to 4 (more than 10^10)
+    #[clap(long, value_name = "N", value_parser = clap::value_parser!(u8).range(0..=4))]
+    min_strength: Option<u8>,
+
+    /// Only keep results containing a character of each of these classes. Named
+    /// so as not to collide with --require
+    #[clap(long, value_enum, value_delimiter = ',', value_name = "CLASS,...")]
+    require_classes: Vec<policy::CharClass>,
+
+    /// Only keep results with characters of at least this many of the classes
+    /// upper, lower, digit and symbol
+    #[clap(long, value_name = "N", default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=4))]
+    min_classes: u8,
+
+    /// Pad each result to this many bytes
+    #[clap(long, value_name = "N")]
+    pad_to: Option<usize>,
+
+    /// ASCII character used for --pad-to
+    #[clap(long, value_name = "C", default_value_t = ' ', requires = "pad_to")]
+    pad_char: char,
+
+    /// Pad on the left instead of the right
+    #[clap(long, requires = "pad_to")]
+    pad_left: bool,
+
+    /// What to do with results longer than the --pad-to width
+    #[clap(long, value_enum, default_value_t, requires = "pad_to")]
+    pad_overflow: PadOverflow,
+
+    /// Add the reverse of each result
+    #[clap(long, value_enum)]
+    mirror: Option<Mirror>,
+
+    /// Write each result inside this template, where {} stands for the result and
+    /// {i} for its index, such as "admin:{}"
+    #[clap(long, value_name = "TEMPLATE", value_parser = format::parse_format)]
+    format: Option<LineFormat>,
+
+    /// Write each result's exact bytes in this encoding, so results that
+    /// aren't valid UTF-8 survive intact
+    #[clap(long, value_enum)]
+    encode: Option<Encoding>,
+
+    /// Write each result as HASH:RESULT, with the hash in hex
+    #[clap(long, value_enum, value_name = "TYPE")]
+    hash: Option<HashType>,
+
+    /// Write only the --hash of each result
+    #[clap(long, requires = "hash")]
+    hash_only: bool,
+
+    /// Layout of each result line
+    #[clap(long, value_enum, default_value_t, conflicts_with = "format")]
+    output_format: OutputFormat,
+
+    /// Fields of each --output-format csv row
+    #[clap(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "index,candidate,length"
+    )]
+    columns: Vec<Column>,
+
+    /// Apply every rule of this hashcat-style rule file to each result, yielding
+    /// one variant per rule that doesn't reject it
+    #[clap(long, value_name = "FILE")]
+    rules: Option<PathBuf>,
+
+    /// Print the exact number of results instead of generating them
+    #[clap(long, conflicts_with = "count_distinct")]
+    count: bool,
+
+    /// Print the number of distinct results instead of generating them
+    #[clap(long)]
+    count_distinct: bool,
+
+    /// Print the index at which this value is first generated, then exit
+    #[clap(long, value_name = "VALUE", conflicts_with_all = ["count", "count_distinct", "unrank"])]
+    rank: Option<String>,
+
+    /// Print the result at this index in the enumeration, then exit
+    #[clap(long, value_name = "INDEX", conflicts_with_all = ["count", "count_distinct"])]
+    unrank: Option<BigUint>,
+
+    /// Only generate slice I of N equal slices of the enumeration, numbered from 1.
+    /// --skip and --resume positions still count from the start of the whole enumeration
+    #[clap(long, value_name = "I/N", value_parser = shard::parse_shard, conflicts_with = "sample")]
+    shard: Option<Shard>,
+
+    /// Generate on this many threads, keeping results in order
+    #[clap(long, value_name = "N", default_value_t = 1, conflicts_with = "sample")]
+    threads: usize,
+
+    /// Periodically save the position reached to this file, for use with --resume
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["reservoir", "sample"])]
+    checkpoint: Option<PathBuf>,
+
+    /// Save a checkpoint after every N results written
+    #[clap(
+        long,
+        value_name = "N",
+        default_value_t = 100_000,
+        requires = "checkpoint"
+    )]
+    checkpoint_every: usize,
+
+    /// Continue a previous run from the position saved in this checkpoint file,
+    /// appending to --output rather than replacing it
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["skip", "skip_past", "sample", "reservoir"])]
+    resume: Option<PathBuf>,
+
+    /// Output N results sampled uniformly from everything generated, in a single pass
+    #[clap(long, value_name = "N")]
+    reservoir: Option<usize>,
+
+    /// Draw N results at random from the pattern instead of enumerating it. Each way
+    /// of generating a result is as likely as the next, so results an ambiguous
+    /// pattern produces more than once are drawn more often than the rest
+    #[clap(long, value_name = "N", conflicts_with_all = ["reservoir", "skip_past", "skip"])]
+    sample: Option<usize>,
+
+    /// Never draw the same result twice with --sample or --random, stopping early if
+    /// the pattern runs out of them
+    #[clap(long, conflicts_with = "weights")]
+    no_replacement: bool,
+
+    /// Seed for the random number generator, such as 42 or bug-1234. The same seed
+    /// samples the same results on every machine and every run
+    #[clap(long, value_name = "VALUE")]
+    seed: Option<String>,
+
+    /// Draw N results, 1 by default, uniformly from the distinct strings the pattern
+    /// matches, using the operating system's secure random number generator
+    #[clap(
+        long,
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "1",
+        conflicts_with_all = [
+            "sample", "seed", "reservoir", "skip_past", "skip", "shard", "threads", "checkpoint",
+            "resume", "weights",
+        ]
+    )]
+    random: Option<usize>,
+}
+
+/// A regex matching just the whole of inputs `hir` matches.
+fn full_match(hir: &Hir) -> Result<MetaRegex, String> {
+    MetaRegex::builder()
+        .configure(MetaRegex::config().utf8_empty(false))
+        .build_from_hir(&Hir::concat(vec![
+            Hir::look(Look::Start),
+            hir.clone(),
+            Hir::look(Look::End),
+        ]))
+        .map_err(|error| error.to_string())
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.done {
-            return None;
+/// Takes an item from each iterator in turn, dropping those that run out.
+fn interleave<I: Iterator>(mut iters: Vec<I>) -> impl Iterator<Item = I::Item> {
+    let mut next = 0;
+    iter::from_fn(move || {
+        while !iters.is_empty() {
+            next %= iters.len();
+            match iters[next].next() {
+                Some(item) => {
+                    next += 1;
+                    return Some(item);
+                }
+                None => {
+                    iters.remove(next);
+                }
+            }
         }
-        if self.factories.is_empty() {
-            self.done = true;
-            return Some(Vec::new());
+        None
+    })
+}
+
+#[test]
+fn test_interleave() {
+    let iters = vec![
+        vec![1, 2, 3].into_iter(),
+        vec![4].into_iter(),
+        vec![5, 6].into_iter(),
+    ];
+    assert_eq!(interleave(iters).collect::<Vec<_>>(), [1, 4, 5, 2, 6, 3]);
+}
+
+/// What to do with assertions such as `^`, `$` and `\b`, which match no
+/// characters of their own to generate.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+enum Looks {
+    /// Generate as if they weren't there, then drop the results they don't hold in
+    #[default]
+    Filter,
+    /// Generate as if they weren't there
+    #[value(alias = "ignore")]
+    Strip,
+    /// Reject patterns that have them
+    Error,
+}
+
+/// A model trained on a wordlist, which enumerates its own results.
+enum Trained {
+    Markov(markov::Model),
+    Pcfg(pcfg::Grammar),
+}
+
+impl Trained {
+    fn pattern(&self) -> String {
+        match self {
+            Trained::Markov(model) => model.pattern(),
+            Trained::Pcfg(grammar) => grammar.pattern(),
         }
-        let result = self.heads.clone();
-        for ((head, iter), factory) in self
-            .heads
-            .iter_mut()
-            .zip(&mut self.iters)
-            .zip(&self.factories)
-        {
-            if let Some(next) = iter.next() {
-                *head = next;
-                return Some(result);
-            } else {
-                *iter = (factory)();
-                *head = iter.next().unwrap();
-            }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = Candidate> + '_> {
+        match self {
+            Trained::Markov(model) => Box::new(model.iter()),
+            Trained::Pcfg(grammar) => Box::new(grammar.iter()),
         }
-        self.done = true;
-        Some(result)
     }
 }
 
-#[test]
-fn test_cartesian() {
-    for item in MultiCartesianProduct::new(vec![
-        || ['a', 'b'].into_iter(),
-        || ['f', 'g'].into_iter(),
-        || ['y', 'z'].into_iter(),
-    ]) {
-        println!("{:?}", item);
+/// How several patterns are combined into one enumeration.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+enum Mode {
+    /// Results matched by any of the patterns
+    #[default]
+    Union,
+    /// Results matched by every pattern, in the order of the pattern with the
+    /// fewest results
+    Intersect,
+    /// Results of the first pattern that none of the others match
+    Subtract,
+}
+
+/// Print the number of results of a pattern
+#[derive(clap::Args)]
+struct CountArgs {
+    /// Count each distinct result once
+    #[clap(long)]
+    distinct: bool,
+
+    #[clap(flatten)]
+    args: PatternArgs,
+}
+
+/// Draw results at random from a pattern, as often as it generates each
+#[derive(clap::Args)]
+struct SampleArgs {
+    /// Number of results to draw
+    #[clap(
+        value_name = "N",
+        conflicts_with_all = [
+            "sample", "random", "reservoir", "skip_past", "skip", "interleave", "order", "reverse",
+            "unique", "shard", "threads", "checkpoint", "resume",
+        ]
+    )]
+    samples: usize,
+
+    #[clap(flatten)]
+    args: Args,
+}
+
+/// Print the index at which a value is first generated by a pattern
+#[derive(clap::Args)]
+struct RankArgs {
+    /// Value to find the index of
+    value: String,
+
+    #[clap(flatten)]
+    args: PatternArgs,
+}
+
+/// Print the result of a pattern at an index
+#[derive(clap::Args)]
+struct UnrankArgs {
+    /// Index of the result
+    index: BigUint,
+
+    #[clap(flatten)]
+    args: PatternArgs,
+}
+
+/// Print the size, entropy, result lengths and characters at each position of a pattern
+#[derive(clap::Args)]
+struct AnalyzeArgs {
+    /// Also estimate how long trying every result would take at this many per
+    /// second, such as 500kH/s or 2G, or at how fast this machine generates the
+    /// pattern if local
+    #[clap(long, value_name = "RATE", value_parser = analyze::parse_rate)]
+    at_rate: Option<analyze::Rate>,
+
+    #[clap(flatten)]
+    args: PatternArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Enumerate the results of a pattern
+    Generate(Box<Args>),
+    Count(Box<CountArgs>),
+    Sample(Box<SampleArgs>),
+    Rank(Box<RankArgs>),
+    Unrank(Box<UnrankArgs>),
+    Analyze(Box<AnalyzeArgs>),
+    Pins(PinsArgs),
+    Crunch(CrunchArgs),
+    Combine(CombineArgs),
+    Kbwalk(KbwalkArgs),
+    Markov(MarkovArgs),
+    Pcfg(PcfgArgs),
+    Pronounceable(PronounceableArgs),
+    Passphrase(PassphraseArgs),
+    Serve(ServeArgs),
+    Crack(Box<CrackArgs>),
+}
+
+/// Parses a duration such as 90s, 15m, 2h or 1d. A bare number is in seconds.
+fn parse_duration(duration: &str) -> Result<Duration, String> {
+    let split = duration
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(duration.len());
+    let (number, unit) = duration.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("{duration:?} doesn't start with a number"))?;
+    let seconds = match unit {
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        "d" => 86400.0,
+        _ => Err(format!("unknown unit {unit:?}, expected s, m, h or d"))?,
+    };
+    Duration::try_from_secs_f64(number * seconds).map_err(|error| error.to_string())
+}
+
+/// Parses one of --weights, such as password:10.
+fn parse_weight(weight: &str) -> Result<(String, u32), String> {
+    let (name, weight) = weight
+        .rsplit_once(':')
+        .ok_or_else(|| format!("{weight:?} isn't of the form NAME:WEIGHT"))?;
+    let weight = weight
+        .parse()
+        .map_err(|_| format!("{weight:?} isn't a whole number"))?;
+    Ok((name.to_string(), weight))
+}
+
+/// Parses the inside of a character class, such as `a-z0-9!@#`.
+fn parse_charset(charset: &str) -> Result<ClassUnicode, String> {
+    let hir = regex_syntax::Parser::new()
+        .parse(&format!("[{charset}]"))
+        .map_err(|error| error.to_string())?;
+    match hir.kind() {
+        HirKind::Class(Class::Unicode(class)) => Ok(class.clone()),
+        HirKind::Literal(literal) => Ok(ClassUnicode::new(
+            String::from_utf8_lossy(&literal.0)
+                .chars()
+                .map(|c| ClassUnicodeRange::new(c, c)),
+        )),
+        _ => Err(format!("{charset:?} isn't a set of characters")),
     }
 }
 
 #[test]
-fn test_cartesian_2() {
-    for item in MultiCartesianProduct::new(vec![|| ['a', 'b', 'c'].into_iter(), || {
-        ['f', 'g', 'h'].into_iter()
-    }]) {
-        println!("{:?}", item);
+fn test_parse_charset() {
+    let charset = parse_charset("a-c0-9!@#").unwrap();
+    assert_eq!(
+        charset
+            .iter()
+            .map(|range| (range.start(), range.end()))
+            .collect::<Vec<_>>(),
+        [('!', '!'), ('#', '#'), ('0', '9'), ('@', '@'), ('a', 'c')]
+    );
+    assert_eq!(parse_charset("x").unwrap().ranges().len(), 1);
+    assert!(parse_charset("z-a").is_err());
+}
+
+#[test]
+fn test_parse_weight() {
+    assert_eq!(parse_weight("a:b:10").unwrap(), ("a:b".to_string(), 10));
+    assert!(parse_weight("password").is_err());
+    assert!(parse_weight("password:-1").is_err());
+}
+
+#[test]
+fn test_parse_duration() {
+    assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+    assert_eq!(parse_duration("1.5m"), Ok(Duration::from_secs(90)));
+    assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+    assert_eq!(parse_duration("1d"), Ok(Duration::from_secs(86400)));
+    assert!(parse_duration("h").is_err());
+    assert!(parse_duration("3w").is_err());
+}
+
+/// A result as text, replacing invalid UTF-8 with U+FFFD. Borrows when the
+/// result is valid, which it always is for Unicode patterns.
+fn text(result: &[u8]) -> Cow<'_, str> {
+    String::from_utf8_lossy(result)
+}
+
+/// Whether zxcvbn scores `candidate` at least `min_strength` out of 4.
+fn strong_enough(candidate: &str, min_strength: Option<u8>) -> bool {
+    min_strength.is_none_or(|min| u8::from(zxcvbn::zxcvbn(candidate, &[]).score()) >= min)
+}
+
+#[test]
+fn test_strong_enough() {
+    assert!(strong_enough("password", None));
+    assert!(!strong_enough("password", Some(1)));
+    assert!(strong_enough("correcthorsebatterystaple", Some(4)));
+    assert!(!strong_enough("", Some(1)));
+}
+
+/// Set by the Ctrl-C handler so generation can stop between candidates.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// The pattern a run enumerates, built from the options and any subcommand
+/// that makes one.
+struct Compiled {
+    pattern: Pattern,
+    /// The patterns given, before --mode combined them.
+    patterns: Vec<Pattern>,
+    /// Matchers for the assertions stripped from the pattern, and from each
+    /// of the patterns given, for --looks filter.
+    looks: Option<MetaRegex>,
+    other_looks: Vec<Option<MetaRegex>>,
+    branch_weights: Vec<u32>,
+    backrefs: Option<Backrefs>,
+    trained: Option<Trained>,
+    /// Whether results are written as exact bytes rather than text.
+    exact: bool,
+    /// Why the pattern only stands in for the results, if it does.
+    generated: Option<&'static str>,
+}
+
+impl Compiled {
+    /// Fails if the results don't come from enumerating the pattern, so
+    /// can't be counted or seeked into.
+    fn seekable(&self) -> Result<(), String> {
+        match self.generated {
+            Some(generated) => Err(format!(
+                "The results can't be counted, seeked into or reordered, since {generated}."
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether `result`, one of the pattern's, is in the combination --mode
+    /// makes of the patterns and holds the assertions --looks filter strips.
+    fn keeps(&self, mode: Mode, max_length: Option<usize>, result: &[u8]) -> bool {
+        combines(mode, &self.patterns, &self.other_looks, max_length, result)
+            && self
+                .looks
+                .as_ref()
+                .is_none_or(|looks| looks.is_match(result))
     }
 }
 
-fn iterate_all(hir: &Hir, max_length: Option<usize>) -> Box<dyn Iterator<Item = Vec<u8>> + '_> {
-    let result: Box<dyn Iterator<Item = Vec<u8>>> = match hir.kind() {
-        Empty | Look(_) => Box::new(empty()),
-        Literal(literal) => Box::new(once(literal.0.clone().into())),
-        Class(class) => match class {
-            Unicode(class_unicode) => Box::new(
-                class_unicode
-                    .iter()
-                    .map(|r| r.start()..=r.end())
-                    .flatten()
-                    .map(|c| c.encode_utf8(&mut [0; 4]).as_bytes().to_vec()),
-            ),
-            Bytes(class_bytes) => Box::new(
-                class_bytes
+/// Whether `result` is in the combination `mode` makes of `patterns`, each
+/// with the matcher for any assertions stripped from it.
+fn combines(
+    mode: Mode,
+    patterns: &[Pattern],
+    looks: &[Option<MetaRegex>],
+    max_length: Option<usize>,
+    result: &[u8],
+) -> bool {
+    let member = |k: usize| {
+        matcher::matches(patterns[k].hir(), result, max_length)
+            && looks[k].as_ref().is_none_or(|looks| looks.is_match(result))
+    };
+    match mode {
+        Mode::Union => true,
+        Mode::Intersect => (0..patterns.len()).all(member),
+        Mode::Subtract => !(0..patterns.len()).any(member),
+    }
+}
+
+/// Builds the pattern. `exact` lets through byte patterns, for results
+/// written as exact bytes rather than text.
+fn compile(
+    args: &mut PatternArgs,
+    command: Option<&Command>,
+    exact: bool,
+) -> Result<Compiled, Box<dyn Error>> {
+    if let Some(length) = args.length {
+        args.min_length = length;
+        args.max_length = Some(length);
+    }
+    let custom = [
+        args.custom_charset1.clone(),
+        args.custom_charset2.clone(),
+        args.custom_charset3.clone(),
+        args.custom_charset4.clone(),
+    ];
+    let masked = args.mask.is_some() || args.mask_file.is_some();
+    let sets: Vec<_> = args.set.iter().chain(&args.wordlist).cloned().collect();
+    let trained = match command {
+        Some(Command::Markov(markov)) => Some(Trained::Markov(markov.model()?)),
+        Some(Command::Pcfg(pcfg)) => Some(Trained::Pcfg(pcfg.grammar()?)),
+        _ => None,
+    };
+    let sources = match (command, &args.mask, &args.mask_file) {
+        (Some(Command::Markov(_) | Command::Pcfg(_)), _, _) => {
+            trained.iter().map(Trained::pattern).collect()
+        }
+        (Some(Command::Pins(pins)), _, _) => vec![pins.pattern()],
+        (Some(Command::Crunch(crunch)), _, _) => vec![crunch.pattern()?],
+        (Some(Command::Combine(combine)), _, _) => vec![combine.pattern(&sets)?],
+        (Some(Command::Kbwalk(kbwalk)), _, _) => vec![kbwalk.pattern()],
+        (Some(Command::Pronounceable(pronounceable)), _, _) => pronounceable.patterns()?,
+        (Some(Command::Passphrase(passphrase)), _, _) => vec![passphrase.pattern()?],
+        (Some(_), _, _) => unreachable!("the other subcommands are handled above"),
+        (None, Some(mask), _) => vec![mask::to_pattern(mask, &custom)?],
+        (None, None, Some(path)) => {
+            vec![mask::hcmask_pattern(&fs::read_to_string(path)?, &custom)?]
+        }
+        (None, None, None) => {
+            let file = match &args.patterns_file {
+                Some(path) => fs::read_to_string(path)?,
+                None => String::new(),
+            };
+            let lines = file
+                .lines()
+                .map(|line| line.trim_end_matches('\r'))
+                .filter(|line| !line.trim().is_empty() && !line.starts_with('#'));
+            let from_stdin = args.pattern_stdin || args.password_pattern.iter().any(|p| p == "-");
+            let stdin = match from_stdin {
+                true => io::read_to_string(io::stdin())?,
+                false => String::new(),
+            };
+            let stdin = stdin.strip_suffix('\n').unwrap_or(&stdin);
+            let stdin = stdin.strip_suffix('\r').unwrap_or(stdin);
+            let patterns: Vec<_> = match &args.patterns_file {
+                Some(_) => lines.collect(),
+                None if args.pattern_stdin => vec![stdin],
+                None => args
+                    .password_pattern
                     .iter()
-                    .map(|r| r.start()..=r.end())
-                    .flatten()
-                    .map(|x| vec![x]),
-            ),
-        },
-        Repetition(repetition) => {
-            let mapper = move |repeats| {
-                MultiCartesianProduct::new(
-                    (0..repeats)
-                        .map(move |_| move || iterate_all(&repetition.sub, max_length))
-                        .collect(),
-                )
-                .map(|x| x.concat())
+                    .map(|pattern| match pattern.as_str() {
+                        "-" => stdin,
+                        pattern => pattern,
+                    })
+                    .collect(),
             };
-            match (repetition.max, max_length) {
-                (Some(max), Some(max_length)) => Box::new(
-                    (repetition.min as usize..=max as usize)
-                        .flat_map(mapper)
-                        .take_while(move |x| x.len() <= max_length),
-                ),
-                (Some(max), None) => {
-                    Box::new((repetition.min as usize..=max as usize).flat_map(mapper))
+            if patterns.is_empty() {
+                Err("The patterns file has no patterns in it.")?
+            }
+            patterns
+                .into_iter()
+                .map(|pattern| {
+                    template::expand_dates(&template::expand_ranges(&template::expand_sets(
+                        pattern, &sets,
+                    )?)?)
+                })
+                .collect::<Result<_, _>>()?
+        }
+    };
+    if args.show_pattern {
+        for source in &sources {
+            eprintln!("pattern: {}", escape_nonprintable(source));
+        }
+    }
+    // ?b matches any byte, which only parses with UTF-8 mode off.
+    let mut backrefs = None;
+    let ascii = args
+        .ascii
+        .then(|| ClassUnicode::new([ClassUnicodeRange::new('\0', '\x7F')]));
+    let allowed = [
+        args.charset.clone(),
+        ascii,
+        scripts::allowed(&args.scripts, &args.blocks),
+    ]
+    .into_iter()
+    .flatten()
+    .reduce(|mut allowed, class| {
+        allowed.intersect(&class);
+        allowed
+    });
+    // Each pattern comes with itself as written when --looks filter has to
+    // check the assertions stripped from it.
+    let (mut patterns, mut raws): (Vec<_>, Vec<_>) = sources
+        .iter()
+        .map(|source| {
+            let rewritten = backref::rewrite(source)?;
+            if rewritten.is_some() && sources.len() > 1 {
+                Err("Backreferences only work in a single pattern.")?
+            }
+            let hir = ParserBuilder::new()
+                .nest_limit(u32::MAX)
+                .utf8(!masked && !exact)
+                .case_insensitive(args.case_insensitive)
+                .unicode(args.unicode)
+                .dot_matches_new_line(args.dot_matches_newline)
+                .build()
+                .parse(rewritten.as_deref().unwrap_or(source))
+                .map_err(GenError::from)?;
+            let hir = match &allowed {
+                Some(allowed) => {
+                    // Backreference placeholders can end up folded into a class.
+                    let mut allowed = allowed.clone();
+                    if rewritten.is_some() {
+                        allowed.union(&backref::placeholders());
+                    }
+                    passwd_gen::restrict_classes(&hir, &allowed)
+                }
+                None => hir,
+            };
+            let hir = decorate::decorate(
+                hir,
+                args.prepend.as_deref(),
+                args.append_years.clone(),
+                args.append.as_deref(),
+            );
+            let (hir, raw) = match (passwd_gen::has_looks(&hir), args.looks) {
+                (false, _) => (hir, None),
+                (true, Looks::Filter) => (passwd_gen::strip_looks(&hir), Some(hir)),
+                (true, Looks::Strip) => (passwd_gen::strip_looks(&hir), None),
+                (true, Looks::Error) => Err(format!(
+                    "{} has an assertion such as ^, $ or \\b, which --looks error rejects.",
+                    escape_nonprintable(source)
+                ))?,
+            };
+            // The pattern stands in for the backreferences wherever it's
+            // matched against rather than enumerated.
+            let (hir, raw) = match rewritten {
+                Some(_) => {
+                    let split = Backrefs::new(&hir)?
+                        .min_length(args.min_length)
+                        .max_length(args.max_length)
+                        .class_order(args.class_order);
+                    let superset = (split.superset(&hir), raw.map(|raw| split.superset(&raw)));
+                    backrefs = Some(split);
+                    superset
+                }
+                None => (hir, raw),
+            };
+            let pattern = Pattern::from_hir(hir)
+                .min_length(args.min_length)
+                .max_length(args.max_length)
+                .class_order(args.class_order);
+            Ok((pattern, raw))
+        })
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?
+        .into_iter()
+        .unzip();
+    if args.mode == Mode::Intersect {
+        // Enumerating the smallest pattern checks the fewest results against
+        // the others.
+        let size = |pattern: &Pattern| pattern.space().ok().map(|space| space.total());
+        let smallest = (0..patterns.len())
+            .min_by_key(|&i| {
+                let size = size(&patterns[i]);
+                (size.is_none(), size)
+            })
+            .unwrap();
+        patterns.swap(0, smallest);
+        raws.swap(0, smallest);
+    }
+    let pattern = match args.mode {
+        Mode::Union if patterns.len() > 1 => {
+            // A group around each pattern keeps them from being flattened
+            // into one another, which could reorder their results.
+            let parts = patterns
+                .iter()
+                .map(|part| {
+                    Hir::capture(Capture {
+                        index: 0,
+                        name: None,
+                        sub: Box::new(part.hir().clone()),
+                    })
+                })
+                .collect();
+            Pattern::from_hir(Hir::alternation(parts))
+                .min_length(args.min_length)
+                .max_length(args.max_length)
+                .class_order(args.class_order)
+        }
+        _ => patterns.remove(0),
+    };
+    let (pattern, branch_weights) = match args.weights.as_slice() {
+        [] => (pattern, Vec::new()),
+        weights => {
+            let (hir, branch_weights) = passwd_gen::weigh_branches(pattern.hir(), weights);
+            let pattern = Pattern::from_hir(hir)
+                .min_length(args.min_length)
+                .max_length(args.max_length)
+                .class_order(args.class_order);
+            (pattern, branch_weights)
+        }
+    };
+    let raw = match args.mode {
+        Mode::Union if patterns.len() > 1 && raws.iter().any(Option::is_some) => {
+            let parts = raws
+                .drain(..)
+                .zip(&patterns)
+                .map(|(raw, part)| raw.unwrap_or_else(|| part.hir().clone()))
+                .collect();
+            Some(Hir::alternation(parts))
+        }
+        Mode::Union => raws.drain(..).next().flatten(),
+        _ => raws.remove(0),
+    };
+    let looks = raw.as_ref().map(full_match).transpose()?;
+    let other_looks = raws
+        .iter()
+        .map(|raw| raw.as_ref().map(full_match).transpose())
+        .collect::<Result<Vec<_>, _>>()?;
+    // Backreferences and the markov model generate their own results, so the
+    // pattern only stands in for them where results are matched against it.
+    let generated = match (&backrefs, &trained) {
+        (Some(_), _) => Some("backreferences are substituted as results are generated"),
+        (_, Some(_)) => Some("the model generates its results in order of probability"),
+        (None, None) => None,
+    };
+    Ok(Compiled {
+        pattern,
+        patterns,
+        looks,
+        other_looks,
+        branch_weights,
+        backrefs,
+        trained,
+        exact,
+        generated,
+    })
+}
+
+/// `analyze`: the size, entropy, lengths and characters at each position
+/// of the pattern, and how long trying every result would take.
+fn analyze_pattern(
+    args: &PatternArgs,
+    compiled: &Compiled,
+    at_rate: Option<analyze::Rate>,
+) -> Result<ExitCode, Box<dyn Error>> {
+    compiled.seekable()?;
+    let pattern = &compiled.pattern;
+    let hir = pattern.hir();
+    if pattern.is_unbounded() && args.max_length.is_none() {
+        Err("analyze on an infinite range requires a max length to be specified.")?
+    }
+    print!(
+        "{}",
+        analyze::analyze(hir, args.min_length, args.max_length)?
+    );
+    // Measuring locally generates for a second, or until the results run
+    // out if that's sooner.
+    let rate = match at_rate {
+        Some(analyze::Rate::PerSecond(rate)) => Some(rate),
+        Some(analyze::Rate::Local) => {
+            let started = Instant::now();
+            let mut results = pattern.iter();
+            let mut generated = 0u64;
+            while results.next().is_some() {
+                generated += 1;
+                if generated.is_multiple_of(1024) && started.elapsed() >= Duration::from_secs(1) {
+                    break;
                 }
-                (None, Some(max_length)) => Box::new(
-                    (repetition.min as usize..)
-                        .flat_map(mapper)
-                        .take_while(move |x| x.len() <= max_length),
-                ),
-                (None, None) => Box::new((repetition.min as usize..).flat_map(mapper)),
             }
+            Some(generated as f64 / started.elapsed().as_secs_f64().max(1e-9))
         }
-        Capture(capture) => iterate_all(&capture.sub, max_length),
-        Concat(hirs) => Box::new(
-            MultiCartesianProduct::new(
-                hirs.iter()
-                    .map(move |hir| move || iterate_all(&hir, max_length))
-                    .collect(),
-            )
-            .map(|x| x.concat()),
-        ),
-        Alternation(hirs) => Box::new(
-            hirs.iter()
-                .map(move |h| iterate_all(h, max_length))
-                .into_iter()
-                .flatten(),
-        ),
+        None => None,
     };
-    if let Some(max_length) = max_length {
-        Box::new(result.filter(move |v| v.len() <= max_length))
-    } else {
-        result
+    if let Some(rate) = rate {
+        let total = Space::new(hir, args.max_length)?.count(args.min_length);
+        print!("{}", analyze::exhaustion(&total, rate));
     }
+    Ok(ExitCode::SUCCESS)
 }
 
-fn is_unbounded(hir: &Hir) -> bool {
-    match hir.kind() {
-        Repetition(repetition) => repetition.max.is_none(),
-        Capture(capture) => is_unbounded(&capture.sub),
-        Concat(hirs) | Alternation(hirs) => hirs.iter().any(|hir| is_unbounded(hir)),
-        _ => false,
+/// `count`, --count and --count-distinct.
+fn count_results(
+    args: &PatternArgs,
+    compiled: &Compiled,
+    distinct: bool,
+) -> Result<ExitCode, Box<dyn Error>> {
+    compiled.seekable()?;
+    let pattern = &compiled.pattern;
+    let hir = pattern.hir();
+    if distinct {
+        if pattern.is_unbounded() && args.max_length.is_none() {
+            Err("--count-distinct on an infinite range requires a max length to be specified.")?
+        }
+        println!(
+            "{}",
+            count::count_distinct(hir, args.min_length, args.max_length)?
+        );
+    } else {
+        println!(
+            "{}",
+            Space::new(hir, args.max_length)?.count(args.min_length)
+        );
     }
+    Ok(ExitCode::SUCCESS)
 }
 
-#[test]
-fn test_unbounded() {
-    let hir = Parser::new().parse("a*b*").unwrap();
-    let patterns: Vec<_> = iterate_all(&hir, Some(5))
-        .map(|s| String::from_utf8_lossy(&s).into_owned())
-        .collect();
-    assert_eq!(
-        patterns,
-        [
-            "", "a", "aa", "aaa", "aaaa", "aaaaa", "b", "ab", "aab", "aaab", "aaaab", "bb", "abb",
-            "aabb", "aaabb", "bbb", "abbb", "aabbb", "bbbb", "abbbb", "bbbbb"
-        ]
-        .into_iter()
-        .map(String::from)
-        .collect::<Vec<_>>()
-    )
+/// `rank` and --rank.
+fn rank_value(compiled: &Compiled, value: &str) -> Result<ExitCode, Box<dyn Error>> {
+    compiled.seekable()?;
+    let rank = compiled
+        .pattern
+        .space()?
+        .rank(value.as_bytes())
+        .ok_or_else(|| format!("{value:?} is not matched by the pattern."))?;
+    println!("{rank}");
+    Ok(ExitCode::SUCCESS)
 }
 
-/// Regex iterator
-#[derive(ClapParser)]
-struct Args {
-    /// Pattern to iterate over
-    password_pattern: String,
+/// `unrank` and --unrank.
+fn unrank_index(compiled: &Compiled, index: &BigUint) -> Result<ExitCode, Box<dyn Error>> {
+    compiled.seekable()?;
+    let result = compiled
+        .pattern
+        .space()?
+        .unrank(index)
+        .ok_or_else(|| format!("The pattern has no result at index {index}."))?;
+    println!("{}", String::from_utf8_lossy(&result));
+    Ok(ExitCode::SUCCESS)
+}
 
-    /// Minimum result length
-    #[clap(short = 'i', long, default_value_t = 0)]
-    min_length: usize,
+/// Parses the command line. The options before a subcommand only apply to
+/// those that set or replace them and generate, so are rejected before the
+/// others rather than left unused.
+fn parse_cli<I: Into<OsString> + Clone>(
+    arguments: impl IntoIterator<Item = I>,
+) -> Result<Cli, clap::Error> {
+    let mut cli = Cli::command();
+    let matches = cli.try_get_matches_from_mut(arguments)?;
+    let parsed = Cli::from_arg_matches(&matches)?;
+    let generates = matches!(
+        parsed.command,
+        None | Some(
+            Command::Pins(_)
+                | Command::Crunch(_)
+                | Command::Combine(_)
+                | Command::Kbwalk(_)
+                | Command::Markov(_)
+                | Command::Pcfg(_)
+                | Command::Pronounceable(_)
+                | Command::Passphrase(_)
+        )
+    );
+    let given = cli
+        .get_arguments()
+        .find(|arg| matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine));
+    if let (false, Some(arg), Some((name, _))) = (generates, given, matches.subcommand()) {
+        let message = format!("the argument '{arg}' has to come after the '{name}' subcommand");
+        Err(cli.error(ErrorKind::ArgumentConflict, message))?
+    }
+    Ok(parsed)
+}
 
-    /// Maximum result length
-    #[clap(short = 'x', long)]
-    max_length: Option<usize>,
+#[test]
+fn test_parse_cli() {
+    let parse = |arguments: &str| parse_cli(arguments.split(' '));
+    assert!(parse("passwd-gen -n 1 generate a").is_err());
+    assert!(parse("passwd-gen -x 3 count a+").is_err());
+    assert!(parse("passwd-gen count --exec echo a").is_err());
+    assert!(parse("passwd-gen rank --reverse ab a[bc]").is_err());
+    assert!(parse("passwd-gen sample 3 --skip 1 a").is_err());
+    let Ok(Cli {
+        command: Some(Command::Count(count)),
+        ..
+    }) = parse("passwd-gen count -x 3 a+")
+    else {
+        panic!("count didn't parse");
+    };
+    assert_eq!(count.args.max_length, Some(3));
+    let Ok(Cli { command, args }) = parse("passwd-gen -n 2 pins") else {
+        panic!("pins didn't parse");
+    };
+    assert!(matches!(command, Some(Command::Pins(_))) && args.num == Some(2));
+}
 
-    /// Maximum number of results to yield
-    #[clap(short = 'n', long)]
-    num: Option<usize>,
+fn run() -> Result<ExitCode, Box<dyn Error>> {
+    let Cli { command, args } = parse_cli(env::args_os()).unwrap_or_else(|error| error.exit());
+    // The subcommands other than these set or replace our own options and
+    // generate.
+    match command {
+        Some(Command::Serve(serve)) => serve::serve(&serve).map(|()| ExitCode::SUCCESS),
+        Some(Command::Crack(mut crack)) => crack::crack(&mut crack).map(|()| ExitCode::SUCCESS),
+        // Nothing but numbers is written, or a result lossily by unrank, so
+        // byte patterns are let through.
+        Some(Command::Analyze(analyze)) => {
+            let AnalyzeArgs { at_rate, mut args } = *analyze;
+            let compiled = compile(&mut args, None, true)?;
+            analyze_pattern(&args, &compiled, at_rate)
+        }
+        Some(Command::Count(count)) => {
+            let CountArgs { distinct, mut args } = *count;
+            let compiled = compile(&mut args, None, true)?;
+            count_results(&args, &compiled, distinct)
+        }
+        Some(Command::Rank(rank)) => {
+            let RankArgs { value, mut args } = *rank;
+            rank_value(&compile(&mut args, None, true)?, &value)
+        }
+        Some(Command::Unrank(unrank)) => {
+            let UnrankArgs { index, mut args } = *unrank;
+            unrank_index(&compile(&mut args, None, true)?, &index)
+        }
+        Some(Command::Generate(args)) => generate(*args, None),
+        Some(Command::Sample(sample)) => {
+            let SampleArgs { samples, mut args } = *sample;
+            args.sample = Some(samples);
+            generate(args, None)
+        }
+        command => generate(args, command),
+    }
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let args = Args::parse();
-    let hir = Parser::new().parse(&args.password_pattern)?;
-    if is_unbounded(&hir) && args.num.is_none() && args.max_length.is_none() {
+fn generate(mut args: Args, mut command: Option<Command>) -> Result<ExitCode, Box<dyn Error>> {
+    // crunch enumerates shortest first, and its -o and -s map onto our own.
+    let mut crunch_start = None;
+    if let Some(Command::Crunch(crunch)) = &mut command {
+        crunch_start = crunch.start.take();
+        if let Some(output) = crunch.output.take() {
+            args.output = Some(output);
+        }
+        if args.order == Order::Pattern {
+            args.order = Order::Shortlex;
+        }
+    }
+    // Passphrases are drawn uniformly from every one the wordlist makes.
+    if let Some(Command::Passphrase(passphrase)) = &command {
+        args.sample = Some(passphrase.count);
+    }
+    // --random is --sample drawing from the OS, and without favouring results
+    // an ambiguous pattern produces more than once.
+    if let Some(n) = args.random {
+        args.sample = Some(n);
+    }
+    if args.no_replacement && args.sample.is_none() {
+        Err("--no-replacement only applies with --sample or --random.")?
+    }
+    // --matching is a second pattern for the syllables to be intersected with.
+    if let Some(Command::Pronounceable(pronounceable)) = &command
+        && pronounceable.matching.is_some()
+    {
+        args.pattern.mode = Mode::Intersect;
+    }
+    let pins = match &command {
+        Some(Command::Pins(pins)) => Some(pins),
+        _ => None,
+    };
+    let exact = args.raw || args.encode.is_some();
+    let compiled = compile(&mut args.pattern, command.as_ref(), exact)?;
+    if args.count || args.count_distinct {
+        return count_results(&args.pattern, &compiled, args.count_distinct);
+    }
+    if let Some(value) = &args.rank {
+        return rank_value(&compiled, value);
+    }
+    if let Some(index) = &args.unrank {
+        return unrank_index(&compiled, index);
+    }
+    if args.skip.is_some()
+        || args.skip_past.is_some()
+        || args.resume.is_some()
+        || args.checkpoint.is_some()
+        || args.shard.is_some()
+        || args.threads > 1
+        || args.sample.is_some()
+        || args.order != Order::Pattern
+        || args.reverse
+        || args.unique
+        || args.coverage_report
+        || !args.pattern.weights.is_empty()
+    {
+        compiled.seekable()?;
+    }
+    let Compiled {
+        pattern,
+        patterns,
+        looks,
+        other_looks,
+        branch_weights,
+        backrefs,
+        trained,
+        exact,
+        generated,
+    } = compiled;
+    let hir = pattern.hir();
+    // A union enumerated one pattern at a time rather than as a whole, so
+    // that each can be limited by --num or they can be interleaved.
+    let by_part = args.pattern.mode == Mode::Union
+        && patterns.len() > 1
+        && (args.interleave || (args.pattern.patterns_file.is_some() && args.num.is_some()));
+    let limit_parts = by_part && args.pattern.patterns_file.is_some();
+    if limit_parts
+        && (args.skip.is_some()
+            || args.skip_past.is_some()
+            || args.resume.is_some()
+            || args.shard.is_some()
+            || args.threads > 1
+            || args.checkpoint.is_some()
+            || args.order != Order::Pattern
+            || args.reverse
+            || args.sample.is_some()
+            || !args.pattern.weights.is_empty())
+    {
+        Err(
+            "--num limits each pattern of --patterns-file, so the patterns can't be seeked into or reordered.",
+        )?
+    }
+    let by_part_emitted = vec![Cell::new(0); patterns.len()];
+    let num = args.num.filter(|_| !limit_parts);
+    let combined = |v: &[u8]| {
+        let max_length = args.pattern.max_length;
+        combines(args.pattern.mode, &patterns, &other_looks, max_length, v)
+    };
+    let policy = policy::Policy {
+        required: args.require_classes.clone(),
+        min_classes: args.min_classes.into(),
+    };
+    if !policy.is_empty() {
+        policy.check(hir)?;
+    }
+    if pattern.is_unbounded()
+        && args.num.is_none()
+        && args.pattern.max_length.is_none()
+        && args.benchmark.is_none()
+    {
         Err(
             "Regex contains infinite range: program will spin forever unless a max length or number of results is specified.",
         )?
     }
-    for (i, item) in iterate_all(&hir, args.max_length)
-        .into_iter()
-        .map(|v| String::from_utf8_lossy(&v).into_owned())
-        .filter(|x| x.len() >= args.min_length)
-        .enumerate()
+    let mut start = match (&args.resume, &args.skip_past) {
+        (Some(path), _) => Some(checkpoint::read(path)?),
+        (None, Some(value)) => {
+            if pattern.is_unbounded() && args.pattern.max_length.is_none() {
+                Err("--skip-past on an infinite range requires a max length to be specified.")?
+            }
+            let rank = pattern
+                .space()?
+                .rank(value.as_bytes())
+                .ok_or_else(|| format!("{value:?} is not matched by the pattern."))?;
+            Some(rank + 1u32)
+        }
+        (None, None) => args.skip.clone(),
+    };
+    let end = match &args.shard {
+        Some(shard) => {
+            if pattern.is_unbounded() && args.pattern.max_length.is_none() {
+                Err("--shard on an infinite range requires a max length to be specified.")?
+            }
+            let (first, end) = shard.range(&pattern.space()?.total());
+            start = Some(start.map_or(first.clone(), |start| start.max(first)));
+            Some(end)
+        }
+        None => None,
+    };
+    let database_path = args
+        .output
+        .as_deref()
+        .and_then(Path::to_str)
+        .and_then(|path| path.strip_prefix("sqlite:"));
+    if database_path.is_some()
+        && (args.compress.is_some() || args.split_lines.is_some() || args.split_size.is_some())
     {
-        println!("{item}");
-        if let Some(num) = args.num {
-            if i >= num {
+        Err("--compress, --split-lines and --split-size don't apply to sqlite: outputs.")?
+    }
+    let mut database = database_path
+        .map(|path| Database::open(Path::new(path), args.resume.is_some() || args.append_output))
+        .transpose()?;
+    let mut out = FanOut::new();
+    match &args.output {
+        Some(_) if database.is_some() => {
+            if args.tee {
+                out.add(BufWriter::new(stdout().lock()), args.tee_every);
+            }
+        }
+        Some(path) if args.split_lines.is_some() || args.split_size.is_some() => {
+            out.add_split(SplitFiles::new(
+                path.clone(),
+                args.compress,
+                args.split_lines,
+                args.split_size,
+            ));
+            if args.tee {
+                out.add(BufWriter::new(stdout().lock()), args.tee_every);
+            }
+        }
+        Some(path) => {
+            let file = if args.resume.is_some() || args.append_output {
+                OpenOptions::new().create(true).append(true).open(path)?
+            } else {
+                File::create(path)?
+            };
+            out.add_file(FileWriter::new(file, args.compress)?);
+            if args.tee {
+                out.add(BufWriter::new(stdout().lock()), args.tee_every);
+            }
+        }
+        None if args.exec.is_some() || args.benchmark.is_some() => {}
+        #[cfg(unix)]
+        None if args.listen.is_some() => {}
+        None => out.add(BufWriter::new(stdout().lock()), 1),
+    }
+    #[cfg(unix)]
+    if let Some(path) = &args.listen {
+        out.add_feeder(Feeder::bind(path.clone(), args.batch_size)?);
+    }
+    let mut exec = match &args.exec {
+        Some(command) => Some(Exec::new(
+            exec::parse_command(command)?,
+            args.jobs,
+            args.stop_on_success,
+        )),
+        None => None,
+    };
+    let mut found = None;
+    if args.null {
+        out.delimiter(b"\0".to_vec());
+    }
+    if let Some(Delimiter(delimiter)) = args.delimiter.clone() {
+        out.delimiter(delimiter);
+    }
+    if let Some(lines) = args.flush_every {
+        out.flush_every(lines);
+    }
+    if args.output_format == OutputFormat::Csv && args.resume.is_none() && !args.append_output {
+        out.write_line(&format::csv_header(&args.columns))?;
+    }
+    if args.coverage_report && pattern.is_unbounded() && args.pattern.max_length.is_none() {
+        Err("--coverage-report on an infinite range requires a max length to be specified.")?
+    }
+    let requires = args
+        .require
+        .iter()
+        .map(|pattern| Regex::new(&format!("^(?:{pattern})$")))
+        .collect::<Result<Vec<_>, _>>()?;
+    let filters = args
+        .filter
+        .iter()
+        .map(|pattern| Regex::new(pattern))
+        .collect::<Result<Vec<_>, _>>()?;
+    let excludes = args
+        .exclude
+        .iter()
+        .map(|pattern| Regex::new(pattern))
+        .collect::<Result<Vec<_>, _>>()?;
+    if !args.pad_char.is_ascii() {
+        Err("--pad-char must be a single ASCII character.")?
+    }
+    let padding = args.pad_to.map(|width| Padding {
+        width,
+        byte: args.pad_char as u8,
+        left: args.pad_left,
+        overflow: args.pad_overflow,
+    });
+    let rule_set = match &args.rules {
+        Some(path) => rules::parse_rules(&fs::read_to_string(path)?)?,
+        None => Vec::new(),
+    };
+    let leet_table = match args.leet_sub.as_slice() {
+        [] => LeetTable::default(),
+        substitutions => LeetTable::new(substitutions),
+    };
+    let mut hit = Tally::default();
+    let per_prefix = args.per_prefix.map(|limit| {
+        PerPrefix::new(
+            hir,
+            limit,
+            args.prefix_len,
+            args.pattern.min_length,
+            args.pattern.max_length,
+        )
+    });
+    let base = start.clone().unwrap_or_default();
+    let pulled = Cell::new(0u64);
+    let mut emitted = 0;
+    if args.reservoir.is_some() && pattern.is_unbounded() && args.pattern.max_length.is_none() {
+        Err("--reservoir on an infinite range requires a max length to be specified.")?
+    }
+    let source: Box<dyn Iterator<Item = (usize, Candidate)>> = match args.sample {
+        Some(n) if args.no_replacement => {
+            let space =
+                Space::new(hir, args.pattern.max_length)?.min_length(args.pattern.min_length);
+            let results: Vec<_> = match args.random {
+                Some(_) => space.shuffled(&mut sample::os_rng()).take(n).collect(),
+                None => space
+                    .shuffled(&mut sample::seeded_rng(args.seed.as_deref()))
+                    .take(n)
+                    .collect(),
+            };
+            Box::new(results.into_iter().map(|v| (0, Candidate::from_vec(v))))
+        }
+        Some(n) if args.random.is_some() => {
+            let space =
+                Space::new(hir, args.pattern.max_length)?.min_length(args.pattern.min_length);
+            let mut rng = sample::os_rng();
+            Box::new((0..n).map_while(move |_| {
+                space
+                    .sample_distinct(&mut rng)
+                    .map(|v| (0, Candidate::from_vec(v)))
+            }))
+        }
+        Some(n) => {
+            let space = Space::new(hir, args.pattern.max_length)?;
+            let mut rng = sample::seeded_rng(args.seed.as_deref());
+            Box::new((0..n).map_while(move |_| {
+                space
+                    .sample_weighted(args.pattern.min_length, &branch_weights, &mut rng)
+                    .map(|(branch, v)| (branch, Candidate::from_vec(v)))
+            }))
+        }
+        None if args.order == Order::Shortlex => {
+            let sorted = pattern.shortlex()?;
+            match crunch_start {
+                Some(start) => Box::new(
+                    sorted
+                        .skip_while(move |x| {
+                            (x.len(), x.as_slice()) < (start.len(), start.as_bytes())
+                        })
+                        .map(|x| (0, x)),
+                ),
+                None => Box::new(sorted.map(|x| (0, x))),
+            }
+        }
+        None if args.order == Order::Lex => Box::new(pattern.lex()?.map(|x| (0, x))),
+        None if args.reverse => iterate_branches_rev(
+            hir,
+            args.pattern.min_length,
+            args.pattern.max_length,
+            args.pattern.class_order,
+        )?,
+        None if let Some(backrefs) = &backrefs => Box::new(backrefs.iter().map(|v| (0, v))),
+        None if let Some(trained) = &trained => Box::new(
+            trained
+                .iter()
+                .filter(|v| {
+                    v.len() >= args.pattern.min_length
+                        && args.pattern.max_length.is_none_or(|max| v.len() <= max)
+                })
+                .map(|v| (0, v)),
+        ),
+        None if by_part => {
+            if args.unique && pattern.is_unbounded() && args.pattern.max_length.is_none() {
+                Err("--unique on an infinite range requires a max length to be specified.")?
+            }
+            let max_length = args.pattern.max_length;
+            let mut parts = Vec::new();
+            for (k, (part, emitted)) in patterns.iter().zip(&by_part_emitted).enumerate() {
+                let earlier = &patterns[..k];
+                let space = match args.unique {
+                    true => Some(part.space()?),
+                    false => None,
+                };
+                parts.push(
+                    iterate_branches(
+                        part.hir(),
+                        args.pattern.min_length,
+                        max_length,
+                        args.pattern.class_order,
+                    )
+                    .enumerate()
+                    .filter(move |(offset, (_, v))| {
+                        space.as_ref().is_none_or(|space| {
+                            space.rank(v) == Some(BigUint::from(*offset))
+                                && !earlier
+                                    .iter()
+                                    .any(|other| matcher::matches(other.hir(), v, max_length))
+                        })
+                    })
+                    .take_while(|_| !limit_parts || args.num.is_none_or(|num| emitted.get() < num))
+                    .map(move |(_, (_, v))| (k, v)),
+                );
+            }
+            match args.interleave {
+                true => Box::new(interleave(parts)),
+                false => Box::new(parts.into_iter().flatten()),
+            }
+        }
+        None if args.threads > 1 => {
+            if pattern.is_unbounded() && args.pattern.max_length.is_none() {
+                Err("--threads on an infinite range requires a max length to be specified.")?
+            }
+            Box::new(ParallelBranches::new(
+                pattern.space()?,
+                hir,
+                start.clone().unwrap_or_default(),
+                end.clone(),
+                args.threads,
+            )?)
+        }
+        // Results sharing a prefix come in runs only where a branch or repeat
+        // count starts with it, so these are seeked past once it's full.
+        None if let Some(per_prefix) = &per_prefix
+            && let Ok(space) = pattern.space() =>
+        {
+            Box::new(Seeking::new(space, hir, base.clone(), per_prefix, &pulled))
+        }
+        None => match &start {
+            Some(start) => match pattern.space() {
+                Ok(space) => space.iterate_branches_from(hir, start),
+                // Without a max length there are no counts to seek with.
+                Err(GenError::Unbounded) => Box::new(
+                    iterate_branches(
+                        hir,
+                        args.pattern.min_length,
+                        args.pattern.max_length,
+                        args.pattern.class_order,
+                    )
+                    .skip(usize::try_from(start)?),
+                ),
+                Err(error) => Err(error)?,
+            },
+            None => iterate_branches(
+                hir,
+                args.pattern.min_length,
+                args.pattern.max_length,
+                args.pattern.class_order,
+            ),
+        },
+    };
+    let mut source: Box<dyn Iterator<Item = (usize, Candidate)>> = match (&start, &end) {
+        (Some(start), Some(end)) if start < end => {
+            Box::new(source.take(usize::try_from(end - start).unwrap_or(usize::MAX)))
+        }
+        (_, Some(_)) => Box::new(empty()),
+        _ => source,
+    };
+    if let Some(per_prefix) = &per_prefix {
+        source = Box::new(iter::from_fn(move || match per_prefix.finished() {
+            true => None,
+            false => source.next(),
+        }));
+    }
+    // A result is kept only at the index it first appears at. That needs a
+    // rank per result, so patterns whose results are already distinct skip it.
+    let first_only = match args.unique && args.order == Order::Pattern && !by_part {
+        true => {
+            if pattern.is_unbounded() && args.pattern.max_length.is_none() {
+                Err("--unique on an infinite range requires a max length to be specified.")?
+            }
+            let space = pattern.space()?;
+            let distinct =
+                count::count_distinct(hir, args.pattern.min_length, args.pattern.max_length)
+                    .is_ok_and(|distinct| distinct == space.total());
+            (!distinct).then_some(space)
+        }
+        false => None,
+    };
+    // Each result carries how far past `base` it was generated, its index.
+    let drops = Drops::default();
+    let began = Instant::now();
+    let candidates = source
+        .map(|(branch, v)| {
+            pulled.set(pulled.get() + 1);
+            (branch, v, pulled.get() - 1)
+        })
+        .filter(|(_, v, offset)| {
+            let first = first_only
+                .as_ref()
+                .is_none_or(|space| space.rank(v) == Some(&base + *offset));
+            drops.keep("--unique", first)
+        })
+        .filter(|(_, v, _)| drops.keep("--mode", combined(v)))
+        .filter(|(_, v, _)| {
+            let keep = looks.as_ref().is_none_or(|looks| looks.is_match(&v[..]));
+            drops.keep("--looks", keep)
+        })
+        .filter(|(_, v, _)| {
+            let keep = requires.iter().all(|require| require.is_match(&text(v)));
+            drops.keep("--require", keep)
+        })
+        .filter(|(_, v, _)| {
+            let keep = filters.iter().all(|filter| filter.is_match(&text(v)));
+            drops.keep("--filter", keep)
+        })
+        .filter(|(_, v, _)| {
+            let keep = !excludes.iter().any(|exclude| exclude.is_match(&text(v)));
+            drops.keep("--exclude", keep)
+        })
+        .filter(|(_, v, _)| {
+            drops.keep("--min-strength", strong_enough(&text(v), args.min_strength))
+        })
+        .filter(|(_, v, _)| {
+            let keep = policy.is_empty() || policy.keep(&text(v));
+            drops.keep("--require-classes/--min-classes", keep)
+        })
+        .filter(|(_, v, _)| drops.keep("pins", pins.is_none_or(|pins| pins.keep(&text(v)))))
+        .filter(|(_, v, _)| {
+            let keep = per_prefix
+                .as_ref()
+                .is_none_or(|per_prefix| per_prefix.keep(v));
+            drops.keep("--per-prefix", keep)
+        });
+    let candidates: Box<dyn Iterator<Item = (usize, Candidate, u64)>> = match args.reservoir {
+        Some(n) => {
+            let mut rng = sample::seeded_rng(args.seed.as_deref());
+            Box::new(sample::reservoir(candidates, n, &mut rng).into_iter())
+        }
+        None => Box::new(candidates),
+    };
+    let mut progress = args.progress.then(|| {
+        let total = match args.sample {
+            Some(n) => Some(BigUint::from(n)),
+            None if generated.is_some() => None,
+            None if args.order == Order::Pattern => pattern.space().ok().map(|space| {
+                let end = end.clone().unwrap_or_else(|| space.total());
+                match end > base {
+                    true => end - &base,
+                    false => BigUint::default(),
+                }
+            }),
+            None => None,
+        };
+        Progress::new(total.and_then(|total| total.to_f64()), args.num)
+    });
+    let verifier = match args.verify {
+        true => Some(full_match(hir)?),
+        false => None,
+    };
+    if args.rate.is_some_and(|rate| rate.is_nan() || rate <= 0.0) {
+        Err("--rate must be a positive number of results per second.")?
+    }
+    let mut unsaved = 0;
+    let mut pending = 0u64;
+    let started = Instant::now();
+    ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::Relaxed))?;
+    let mut line = Vec::new();
+    let deadline = args
+        .benchmark
+        .map(Duration::from_secs_f64)
+        .into_iter()
+        .chain(args.timeout)
+        .min()
+        .map(|duration| began + duration);
+    let mut timed_out = false;
+    'generate: for (branch, result, offset) in candidates {
+        if num.is_some_and(|num| emitted >= num) || INTERRUPTED.load(Ordering::Relaxed) {
+            pending = 1;
+            break;
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            pending = 1;
+            timed_out = true;
+            break;
+        }
+        if let Some(verifier) = &verifier {
+            let length_ok = result.len() >= args.pattern.min_length
+                && args
+                    .pattern
+                    .max_length
+                    .is_none_or(|max| result.len() <= max);
+            if !length_ok || !verifier.is_match(&result[..]) {
+                out.flush()?;
+                Err(format!(
+                    "verify: result {} at index {} isn't matched by the pattern{}",
+                    escape_nonprintable(&text(&result)),
+                    &base + offset,
+                    match length_ok {
+                        true => "",
+                        false => " within the length limits",
+                    }
+                ))?
+            }
+        }
+        let candidate = text(&result);
+        if args.coverage_report {
+            hit.record(branch, &result);
+        }
+        // Plain results are written straight from the generator's buffer;
+        // only mangling allocates.
+        let plain = !args.toggle_case && !args.leet && args.mirror.is_none() && rule_set.is_empty();
+        let mut variants = Vec::new();
+        if !plain {
+            variants = if args.toggle_case {
+                mangle::case_permutations(&candidate, args.case, args.max_toggles)?
+            } else {
+                vec![candidate.to_string()]
+            };
+            if args.leet {
+                variants = variants
+                    .iter()
+                    .map(|item| mangle::leet_variants(item, &leet_table))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .concat();
+            }
+            if let Some(mode) = args.mirror {
+                variants = variants
+                    .into_iter()
+                    .flat_map(|item| mangle::mirror(item, mode))
+                    .collect();
+            }
+            if !rule_set.is_empty() {
+                variants = variants
+                    .iter()
+                    .flat_map(|item| rule_set.iter().filter_map(|rule| rule.apply(item)))
+                    .collect();
+            }
+        }
+        // Raw and encoded results keep their exact bytes; mangling works on text.
+        let items = plain
+            .then(|| match exact {
+                true => &result[..],
+                false => candidate.as_bytes(),
+            })
+            .map(Cow::Borrowed)
+            .into_iter()
+            .chain(
+                variants
+                    .into_iter()
+                    .map(|item| Cow::Owned(item.into_bytes())),
+            );
+        for item in items {
+            if num.is_some_and(|num| emitted >= num) {
+                break 'generate;
+            }
+            let emitted_by_part = by_part_emitted.get(branch).filter(|_| limit_parts);
+            if let Some(emitted) = emitted_by_part
+                && args.num.is_some_and(|num| emitted.get() >= num)
+            {
                 break;
             }
+            if let Some(emitted) = emitted_by_part {
+                emitted.set(emitted.get() + 1);
+            }
+            let mut item = match &padding {
+                Some(padding) => Cow::Owned(mangle::pad(item.into_owned(), padding)?),
+                None => item,
+            };
+            let digest = args.hash.map(|hash_type| hash::digest(&item, hash_type));
+            if let Some(encoding) = args.encode {
+                item = Cow::Owned(format::encode(&item, encoding));
+            }
+            if let Some(digest) = digest {
+                let mut hashed = format::encode(&digest, Encoding::Hex);
+                if !args.hash_only {
+                    hashed.push(b':');
+                    hashed.extend_from_slice(&item);
+                }
+                item = Cow::Owned(hashed);
+            }
+            if let Some(rate) = args.rate {
+                let due = started + Duration::from_secs_f64(emitted as f64 / rate);
+                let now = Instant::now();
+                if due > now {
+                    out.flush()?;
+                    thread::sleep(due - now);
+                }
+            }
+            if let Some(database) = &mut database {
+                database.insert(&(&base + offset), &item)?;
+            }
+            if let Some(exec) = &mut exec {
+                found = exec.run(&text(&item))?;
+                emitted += 1;
+                unsaved += 1;
+                if found.is_some() {
+                    break 'generate;
+                }
+                continue;
+            }
+            let rendered: &[u8] = match (args.output_format, &args.format) {
+                (OutputFormat::Jsonl, _) => {
+                    format::jsonl(&item, &base + offset, &mut line);
+                    &line
+                }
+                (OutputFormat::Csv, _) => {
+                    format::csv(&args.columns, &item, &base + offset, branch, &mut line);
+                    &line
+                }
+                (OutputFormat::Plain, Some(format)) => {
+                    format.render(&item, || &base + offset, &mut line);
+                    &line
+                }
+                (OutputFormat::Plain, None) => &item,
+            };
+            if args
+                .max_bytes
+                .is_some_and(|max_bytes| !out.fits(rendered, max_bytes))
+            {
+                pending = 1;
+                break 'generate;
+            }
+            out.write_line(rendered)?;
+            emitted += 1;
+            unsaved += 1;
         }
+        if let Some(path) = &args.checkpoint
+            && unsaved >= args.checkpoint_every
+        {
+            out.flush()?;
+            if let Some(database) = &mut database {
+                database.commit()?;
+            }
+            checkpoint::write(path, &(&base + pulled.get()))?;
+            unsaved = 0;
+        }
+        if let Some(progress) = &mut progress {
+            progress.tick(pulled.get(), emitted);
+        }
+    }
+    if let Some(progress) = &mut progress {
+        progress.finish(pulled.get(), emitted);
+    }
+    if let Some(exec) = &mut exec
+        && found.is_none()
+    {
+        found = exec.finish()?;
+    }
+    if let Some(found) = found {
+        println!("{found}");
+    }
+    out.finish()?;
+    if let Some(database) = &mut database {
+        database.commit()?;
+    }
+    if let Some(path) = &args.output
+        && io::stderr().is_terminal()
+    {
+        let seconds = started.elapsed().as_secs_f64();
+        eprintln!(
+            "wrote {emitted} results ({:.1} MiB) to {} in {seconds:.1}s, {:.1} MiB/s",
+            out.bytes() as f64 / (1 << 20) as f64,
+            path.display(),
+            out.bytes() as f64 / (1 << 20) as f64 / seconds,
+        );
     }
+    if args.benchmark.is_some() {
+        let seconds = began.elapsed().as_secs_f64();
+        println!(
+            "{emitted} results in {seconds:.1}s: {:.0} results/s, {:.1} MiB/s",
+            emitted as f64 / seconds,
+            out.bytes() as f64 / (1 << 20) as f64 / seconds,
+        );
+    }
+    if args.stats {
+        eprint!(
+            "{}",
+            stats::summary(emitted, out.bytes(), began.elapsed(), &drops)
+        );
+    }
+    let next = &base + (pulled.get() - pending);
+    if let Some(path) = &args.checkpoint {
+        checkpoint::write(path, &next)?;
+    }
+    let seekable = args.order == Order::Pattern
+        && !args.reverse
+        && !by_part
+        && args.sample.is_none()
+        && args.reservoir.is_none();
+    if INTERRUPTED.load(Ordering::Relaxed) {
+        eprintln!(
+            "interrupted after {emitted} results in {:.1?}",
+            started.elapsed()
+        );
+        if seekable {
+            eprintln!("resume with --skip {next}");
+        }
+        // 128 + SIGINT, as a shell reports a process Ctrl-C stopped.
+        return Ok(ExitCode::from(130));
+    }
+    if timed_out && args.timeout.is_some() {
+        eprintln!(
+            "timed out after {emitted} results in {:.1?}",
+            started.elapsed()
+        );
+        if seekable {
+            eprintln!("resume with --skip {next}");
+        }
+    }
+    if args.coverage_report {
+        eprint!("{}", coverage::report(&hit, &pattern.space()?));
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
 
-    Ok(())
+fn main() -> ExitCode {
+    match run() {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -0,0 +1,288 @@
+use clap::ValueEnum;
+
+/// Candidates with more case variants than this are rejected by
+/// [`case_permutations`], since the number of variants doubles with every letter.
+pub const MAX_CASE_VARIANTS: usize = 1 << 16;
+
+/// Which case variants `--toggle-case` emits.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum CaseMode {
+    /// Every upper/lower case variant
+    #[default]
+    All,
+    /// Only the lowercase, capitalized and uppercase forms
+    Simple,
+}
+
+fn binomial(n: usize, k: usize) -> usize {
+    (0..k).fold(1usize, |acc, i| acc.saturating_mul(n - i) / (i + 1))
+}
+
+/// The upper/lower case variants of the alphabetic characters in `candidate`,
+/// starting with the all-lowercase form. With `max_toggles`, only variants with
+/// at most that many uppercased letters are kept; otherwise [`CaseMode::All`]
+/// yields `2^letters` results.
+pub fn case_permutations(
+    candidate: &str,
+    mode: CaseMode,
+    max_toggles: Option<usize>,
+) -> Result<Vec<String>, String> {
+    let chars: Vec<(String, String)> = candidate
+        .chars()
+        .map(|c| (c.to_lowercase().collect(), c.to_uppercase().collect()))
+        .collect();
+    let letters = chars.iter().filter(|(lower, upper)| lower != upper).count();
+    let max_toggles = max_toggles.unwrap_or(letters).min(letters);
+    let variant = |mask: u64| -> String {
+        let mut bit = 0;
+        chars
+            .iter()
+            .map(|(lower, upper)| {
+                if lower == upper {
+                    return lower.as_str();
+                }
+                let toggled = mask & (1 << bit) != 0;
+                bit += 1;
+                if toggled { upper } else { lower }
+            })
+            .collect()
+    };
+    if mode == CaseMode::Simple {
+        let mut masks = vec![0, 1, u64::MAX];
+        masks.retain(|&mask| (mask.count_ones() as usize).min(letters) <= max_toggles);
+        let mut variants: Vec<String> = masks.into_iter().map(variant).collect();
+        variants.dedup();
+        return Ok(variants);
+    }
+    let total = (0..=max_toggles).fold(0usize, |acc, k| acc.saturating_add(binomial(letters, k)));
+    if letters >= u64::BITS as usize || total > MAX_CASE_VARIANTS {
+        return Err(format!(
+            "{candidate:?} has more than the {MAX_CASE_VARIANTS} case variants supported by --toggle-case."
+        ));
+    }
+    let mut variants = Vec::with_capacity(total);
+    let mut mask = 0u64;
+    while mask < 1 << letters {
+        if mask.count_ones() as usize > max_toggles {
+            // Every mask up to the next multiple of the lowest set bit keeps
+            // the same higher bits, so has too many set bits as well.
+            mask += mask & mask.wrapping_neg();
+            continue;
+        }
+        variants.push(variant(mask));
+        mask += 1;
+    }
+    Ok(variants)
+}
+
+#[test]
+fn test_case_permutations() {
+    let all = |candidate| case_permutations(candidate, CaseMode::All, None).unwrap();
+    assert_eq!(all("a1b"), ["a1b", "A1b", "a1B", "A1B"]);
+    assert_eq!(all("abc").len(), 8);
+    assert_eq!(all("123"), ["123"]);
+    assert!(case_permutations(&"a".repeat(17), CaseMode::All, None).is_err());
+    assert_eq!(
+        case_permutations("abc", CaseMode::All, Some(1)).unwrap(),
+        ["abc", "Abc", "aBc", "abC"]
+    );
+    assert_eq!(
+        case_permutations(&"a".repeat(40), CaseMode::All, Some(2))
+            .unwrap()
+            .len(),
+        1 + 40 + 780
+    );
+    assert_eq!(
+        case_permutations("PaSS1", CaseMode::Simple, None).unwrap(),
+        ["pass1", "Pass1", "PASS1"]
+    );
+    assert_eq!(
+        case_permutations("x", CaseMode::Simple, None).unwrap(),
+        ["x", "X"]
+    );
+    assert_eq!(
+        case_permutations("pass", CaseMode::Simple, Some(1)).unwrap(),
+        ["pass", "Pass"]
+    );
+}
+
+/// What to do with results longer than the `--pad-to` width.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum PadOverflow {
+    /// Fail with an error
+    #[default]
+    Error,
+    /// Cut the result down to the width
+    Truncate,
+}
+
+pub struct Padding {
+    pub width: usize,
+    pub byte: u8,
+    pub left: bool,
+    pub overflow: PadOverflow,
+}
+
+/// Pads `result` with `padding.byte` up to exactly `padding.width` bytes.
+pub fn pad(mut result: Vec<u8>, padding: &Padding) -> Result<Vec<u8>, String> {
+    if result.len() > padding.width {
+        match padding.overflow {
+            PadOverflow::Error => Err(format!(
+                "{:?} is longer than the --pad-to width of {}.",
+                String::from_utf8_lossy(&result),
+                padding.width
+            ))?,
+            PadOverflow::Truncate => result.truncate(padding.width),
+        }
+    }
+    let fill = padding.width - result.len();
+    if padding.left {
+        result.splice(0..0, std::iter::repeat_n(padding.byte, fill));
+    } else {
+        result.resize(padding.width, padding.byte);
+    }
+    Ok(result)
+}
+
+#[test]
+fn test_pad() {
+    let mut padding = Padding {
+        width: 4,
+        byte: b'.',
+        left: false,
+        overflow: PadOverflow::Error,
+    };
+    assert_eq!(pad(b"ab".to_vec(), &padding).unwrap(), b"ab..");
+    assert!(pad(b"abcde".to_vec(), &padding).is_err());
+    padding.left = true;
+    padding.overflow = PadOverflow::Truncate;
+    assert_eq!(pad(b"ab".to_vec(), &padding).unwrap(), b"..ab");
+    assert_eq!(pad(b"abcde".to_vec(), &padding).unwrap(), b"abcd");
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Mirror {
+    /// Append the reversed result, making a palindrome (ab -> abba)
+    Append,
+    /// Emit the result followed by its reverse (ab -> ab, ba)
+    Variant,
+}
+
+pub fn mirror(result: String, mode: Mirror) -> Vec<String> {
+    let reversed: String = result.chars().rev().collect();
+    match mode {
+        Mirror::Append => vec![result + &reversed],
+        Mirror::Variant if reversed == result => vec![result],
+        Mirror::Variant => vec![result, reversed],
+    }
+}
+
+#[test]
+fn test_mirror() {
+    assert_eq!(mirror("ab".into(), Mirror::Append), ["abba"]);
+    assert_eq!(mirror("ab".into(), Mirror::Variant), ["ab", "ba"]);
+    assert_eq!(mirror("aba".into(), Mirror::Variant), ["aba"]);
+    assert_eq!(mirror("é1".into(), Mirror::Append), ["é11é"]);
+}
+
+/// Candidates with more leetspeak variants than this are rejected by
+/// [`leet_variants`].
+pub const MAX_LEET_VARIANTS: usize = 1 << 16;
+
+/// The characters each letter may be replaced with by [`leet_variants`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LeetTable(Vec<(char, Vec<char>)>);
+
+impl Default for LeetTable {
+    fn default() -> Self {
+        Self(
+            [
+                ('a', "4@"),
+                ('b', "8"),
+                ('e', "3"),
+                ('g', "9"),
+                ('i', "1!"),
+                ('l', "1"),
+                ('o', "0"),
+                ('s', "5$"),
+                ('t', "7"),
+                ('z', "2"),
+            ]
+            .into_iter()
+            .map(|(c, subs)| (c, subs.chars().collect()))
+            .collect(),
+        )
+    }
+}
+
+impl LeetTable {
+    /// A table of `C=SUBS` substitutions, such as `a=4@`.
+    pub fn new(substitutions: &[(char, Vec<char>)]) -> Self {
+        Self(substitutions.to_vec())
+    }
+
+    fn substitutes(&self, c: char) -> &[char] {
+        let lower = c.to_lowercase().next().unwrap_or(c);
+        self.0
+            .iter()
+            .find(|(from, _)| *from == lower)
+            .map_or(&[], |(_, subs)| subs)
+    }
+}
+
+/// Parses a `C=SUBS` substitution for clap.
+pub fn parse_leet_substitution(s: &str) -> Result<(char, Vec<char>), String> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(from), Some('=')) if chars.clone().next().is_some() => Ok((from, chars.collect())),
+        _ => Err(format!("expected C=SUBSTITUTES, got {s:?}")),
+    }
+}
+
+/// Every variant of `candidate` with any of its letters swapped for their
+/// substitutes in `table`, starting with `candidate` itself.
+pub fn leet_variants(candidate: &str, table: &LeetTable) -> Result<Vec<String>, String> {
+    let options: Vec<Vec<char>> = candidate
+        .chars()
+        .map(|c| {
+            std::iter::once(c)
+                .chain(table.substitutes(c).iter().copied())
+                .collect()
+        })
+        .collect();
+    let total = options
+        .iter()
+        .try_fold(1usize, |total, options| total.checked_mul(options.len()))
+        .filter(|&total| total <= MAX_LEET_VARIANTS)
+        .ok_or_else(|| {
+            format!("{candidate:?} has more than the {MAX_LEET_VARIANTS} leetspeak variants supported by --leet.")
+        })?;
+    Ok((0..total)
+        .map(|mut index| {
+            options
+                .iter()
+                .map(|options| {
+                    let c = options[index % options.len()];
+                    index /= options.len();
+                    c
+                })
+                .collect()
+        })
+        .collect())
+}
+
+#[test]
+fn test_leet_variants() {
+    let variants = leet_variants("Sea", &LeetTable::default()).unwrap();
+    assert_eq!(variants.len(), 18);
+    assert_eq!(variants[..4], ["Sea", "5ea", "$ea", "S3a"]);
+    assert_eq!(variants[17], "$3@");
+    let table = LeetTable::new(&[parse_leet_substitution("o=0()").unwrap()]);
+    assert_eq!(
+        leet_variants("no", &table).unwrap(),
+        ["no", "n0", "n(", "n)"]
+    );
+    assert!(leet_variants(&"a".repeat(20), &LeetTable::default()).is_err());
+    assert!(parse_leet_substitution("o0").is_err());
+    assert!(parse_leet_substitution("o=").is_err());
+}
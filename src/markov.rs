@@ -0,0 +1,240 @@
+//! The `markov` subcommand: an order-N character model trained on a
+//! wordlist, enumerated from the most probable result down.
+
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use clap::Args;
+use passwd_gen::{Candidate, template};
+
+/// Enumerate strings from the most to the least probable under a character
+/// Markov model trained on a wordlist
+#[derive(Args)]
+pub struct MarkovArgs {
+    /// Wordlist to train on, one word per line
+    pub corpus: PathBuf,
+
+    /// Number of preceding characters each character depends on
+    #[clap(long, value_name = "N", default_value_t = 2)]
+    pub order: usize,
+
+    /// Fewest characters in a result
+    #[clap(long, value_name = "N", default_value_t = 1)]
+    pub min_chars: usize,
+
+    /// Most characters in a result
+    #[clap(long, value_name = "N", default_value_t = 8)]
+    pub max_chars: usize,
+
+    /// Stop at results less probable than this
+    #[clap(long, value_name = "P")]
+    pub threshold: Option<f64>,
+}
+
+/// The characters before a position, `None` before the start of the word.
+type Context = Vec<Option<char>>;
+
+pub struct Model {
+    /// Log probability of each character after a context, most probable
+    /// first. `None` ends the word.
+    next: HashMap<Context, Vec<(Option<char>, f64)>>,
+    order: usize,
+    alphabet: Vec<char>,
+    min_chars: usize,
+    max_chars: usize,
+    threshold: f64,
+}
+
+/// Reads a wordlist to train on, one word per line. Fails if it's empty.
+pub fn read_corpus(path: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let corpus =
+        fs::read_to_string(path).map_err(|error| format!("{}: {error}", path.display()))?;
+    let words: Vec<_> = corpus
+        .lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect();
+    if words.is_empty() {
+        Err(format!("{} has no words in it.", path.display()))?
+    }
+    Ok(words)
+}
+
+impl MarkovArgs {
+    pub fn model(&self) -> Result<Model, Box<dyn Error>> {
+        let words = read_corpus(&self.corpus)?;
+        if self.min_chars > self.max_chars {
+            Err("--min-chars can't be more than --max-chars.")?
+        }
+        Ok(Model::train(&words, self.order)
+            .chars(self.min_chars, self.max_chars)
+            .threshold(self.threshold))
+    }
+}
+
+impl Model {
+    pub fn train(words: &[impl AsRef<str>], order: usize) -> Self {
+        let mut counts: HashMap<Context, HashMap<Option<char>, u64>> = HashMap::new();
+        let mut alphabet = Vec::new();
+        for word in words {
+            let mut context = vec![None; order];
+            for c in word.as_ref().chars().map(Some).chain([None]) {
+                *counts
+                    .entry(context.clone())
+                    .or_default()
+                    .entry(c)
+                    .or_default() += 1;
+                if order > 0 {
+                    context.remove(0);
+                    context.push(c);
+                }
+                alphabet.extend(c);
+            }
+        }
+        alphabet.sort_unstable();
+        alphabet.dedup();
+        let next = counts
+            .into_iter()
+            .map(|(context, counts)| {
+                let total = counts.values().sum::<u64>() as f64;
+                let mut next: Vec<_> = counts
+                    .into_iter()
+                    .map(|(c, count)| (c, (count as f64 / total).ln()))
+                    .collect();
+                next.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+                (context, next)
+            })
+            .collect();
+        Self {
+            next,
+            order,
+            alphabet,
+            min_chars: 1,
+            max_chars: 8,
+            threshold: f64::NEG_INFINITY,
+        }
+    }
+
+    pub fn chars(mut self, min_chars: usize, max_chars: usize) -> Self {
+        self.min_chars = min_chars;
+        self.max_chars = max_chars;
+        self
+    }
+
+    /// Leave out results less probable than `threshold`.
+    pub fn threshold(mut self, threshold: Option<f64>) -> Self {
+        self.threshold = threshold.map_or(f64::NEG_INFINITY, f64::ln);
+        self
+    }
+
+    /// A pattern matching every result and more, for the options that
+    /// match against the pattern.
+    pub fn pattern(&self) -> String {
+        let chars: Vec<_> = self.alphabet.iter().map(char::to_string).collect();
+        format!(
+            "{}{{{},{}}}",
+            template::alternation(&chars),
+            self.min_chars,
+            self.max_chars
+        )
+    }
+
+    /// Iterates over results from the most probable, breaking ties by
+    /// which was reached first.
+    pub fn iter(&self) -> impl Iterator<Item = Candidate> + '_ {
+        // Each word is paired with whether it has ended, rather than being a
+        // prefix to extend.
+        let mut heap = BinaryHeap::from([Ranked {
+            log_probability: 0.0,
+            sequence: 0,
+            item: (String::new(), false),
+        }]);
+        let mut sequence = 0;
+        std::iter::from_fn(move || {
+            while let Some(entry) = heap.pop() {
+                let (word, done) = entry.item;
+                if done {
+                    return Some(Candidate::from_slice(word.as_bytes()));
+                }
+                // Every continuation is at most as probable as its prefix,
+                // so nothing popped later can beat what's popped now.
+                let chars = word.chars().count();
+                let mut context: Context = word.chars().map(Some).collect();
+                context.splice(..0, vec![None; self.order]);
+                let context = context.split_off(context.len() - self.order);
+                for &(c, log_probability) in self.next.get(&context).into_iter().flatten() {
+                    let log_probability = entry.log_probability + log_probability;
+                    let fits = match c {
+                        None => chars >= self.min_chars,
+                        Some(_) => chars < self.max_chars,
+                    };
+                    if !fits || log_probability < self.threshold {
+                        continue;
+                    }
+                    sequence += 1;
+                    heap.push(Ranked {
+                        log_probability,
+                        sequence,
+                        item: (word.chars().chain(c).collect(), c.is_none()),
+                    });
+                }
+            }
+            None
+        })
+    }
+}
+
+/// An item on a search frontier, popped most probable first and then in the
+/// order it was pushed.
+pub struct Ranked<T> {
+    pub log_probability: f64,
+    pub sequence: u64,
+    pub item: T,
+}
+
+impl<T> Ord for Ranked<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.log_probability
+            .total_cmp(&other.log_probability)
+            .then(other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl<T> PartialOrd for Ranked<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> PartialEq for Ranked<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Ranked<T> {}
+
+#[test]
+fn test_markov() {
+    let model = Model::train(&["aab", "aab", "ab", "b"], 1);
+    let results: Vec<_> = model
+        .iter()
+        .map(|result| String::from_utf8(result.to_vec()).unwrap())
+        .take(4)
+        .collect();
+    // a starts 3 of 4 words and is followed by b 3 times in 5, so ab is 0.45
+    // likely, b 0.25 and aab 0.18.
+    assert_eq!(results, ["ab", "b", "aab", "aaab"]);
+    let model = Model::train(&["aab", "aab", "ab", "b"], 1)
+        .chars(2, 3)
+        .threshold(Some(0.1));
+    let results: Vec<_> = model.iter().map(|result| result.to_vec()).collect();
+    assert_eq!(results, [b"ab".to_vec(), b"aab".to_vec()]);
+    assert_eq!(model.pattern(), "(?:a|b){2,3}");
+}
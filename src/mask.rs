@@ -0,0 +1,179 @@
+//! Hashcat-style masks such as `?u?l?l?l?d?d`, translated into patterns.
+
+use std::collections::BTreeSet;
+
+use crate::{error::GenError, template};
+
+const SYMBOLS: &str = " !\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+/// The members of a built-in charset other than `?b`.
+fn builtin(name: char) -> Option<String> {
+    let range = |start: char, end: char| (start..=end).collect::<String>();
+    Some(match name {
+        'l' => range('a', 'z'),
+        'u' => range('A', 'Z'),
+        'd' => range('0', '9'),
+        'h' => range('0', '9') + &range('a', 'f'),
+        'H' => range('0', '9') + &range('A', 'F'),
+        's' => SYMBOLS.to_string(),
+        'a' => range('a', 'z') + &range('A', 'Z') + &range('0', '9') + SYMBOLS,
+        _ => return None,
+    })
+}
+
+fn invalid(message: String) -> GenError {
+    GenError::Mask(message)
+}
+
+/// A character class matching every member of a charset definition, which
+/// may mix literal characters with built-in charsets like `?l`.
+fn class(definition: &str) -> Result<String, GenError> {
+    let mut members = BTreeSet::new();
+    let mut chars = definition.chars();
+    while let Some(c) = chars.next() {
+        if c != '?' {
+            members.insert(c);
+            continue;
+        }
+        match chars.next() {
+            Some('?') => {
+                members.insert('?');
+            }
+            Some('b') => return Ok("(?-u:[\\x00-\\xFF])".to_string()),
+            Some(name) => members.extend(
+                builtin(name)
+                    .ok_or_else(|| invalid(format!("unknown charset ?{name} in {definition:?}")))?
+                    .chars(),
+            ),
+            None => Err(invalid(format!("{definition:?} ends with a lone '?'")))?,
+        }
+    }
+    if members.is_empty() {
+        Err(invalid("charsets can't be empty".to_string()))?
+    }
+    Ok(template::class(&members.iter().collect::<String>()))
+}
+
+/// Translates a mask into a pattern. Each `?x` placeholder stands for one
+/// character of a built-in charset, `?1` to `?4` for one of the `custom`
+/// charsets, `??` for a literal `?`, and anything else for itself. `?b`
+/// matches any byte, so the pattern must be parsed with UTF-8 mode off.
+pub fn to_pattern(mask: &str, custom: &[Option<String>; 4]) -> Result<String, GenError> {
+    let mut pattern = String::new();
+    let mut chars = mask.chars();
+    while let Some(c) = chars.next() {
+        if c != '?' {
+            pattern += &regex_syntax::escape(c.encode_utf8(&mut [0; 4]));
+            continue;
+        }
+        match chars.next() {
+            Some('?') => pattern += "\\?",
+            Some(digit @ '1'..='4') => {
+                let index = digit as usize - '1' as usize;
+                let definition = custom[index].as_ref().ok_or_else(|| {
+                    invalid(format!("mask uses ?{digit}, but charset {digit} isn't set"))
+                })?;
+                pattern += &class(definition)?;
+            }
+            Some(name) if name == 'b' || builtin(name).is_some() => {
+                pattern += &class(&format!("?{name}"))?
+            }
+            Some(name) => Err(invalid(format!("unknown charset ?{name}")))?,
+            None => Err(invalid("mask ends with a lone '?'".to_string()))?,
+        }
+    }
+    Ok(pattern)
+}
+
+/// Splits a line of a mask file on unescaped commas, unescaping `\,`.
+fn fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&',') => {
+                fields.last_mut().unwrap().push(',');
+                chars.next();
+            }
+            ',' => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+/// Translates the contents of a `.hcmask` file into one pattern, an
+/// alternation with a branch per mask in file order. Each line is a mask,
+/// optionally preceded by up to four comma-separated custom charsets that
+/// override those in `custom`. Blank lines and lines starting with `#` are
+/// skipped, and `\,` is a literal comma.
+pub fn hcmask_pattern(contents: &str, custom: &[Option<String>; 4]) -> Result<String, GenError> {
+    let mut branches = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let at_line = |error| match error {
+            GenError::Mask(message) => GenError::Mask(format!("line {}: {message}", number + 1)),
+            error => error,
+        };
+        let mut fields = fields(
+            line.strip_prefix('\\')
+                .filter(|rest| rest.starts_with('#'))
+                .unwrap_or(line),
+        );
+        let mask = fields.pop().unwrap();
+        if fields.len() > 4 {
+            Err(at_line(invalid(
+                "more than four custom charsets".to_string(),
+            )))?
+        }
+        let mut charsets = custom.clone();
+        for (charset, field) in charsets.iter_mut().zip(fields) {
+            *charset = Some(field);
+        }
+        branches.push(format!(
+            "(?:{})",
+            to_pattern(&mask, &charsets).map_err(at_line)?
+        ));
+    }
+    if branches.is_empty() {
+        Err(invalid("mask file has no masks".to_string()))?
+    }
+    Ok(branches.join("|"))
+}
+
+#[test]
+fn test_to_pattern() {
+    let custom = [Some("ab?d".to_string()), None, None, None];
+    assert_eq!(
+        to_pattern("?d-?1??", &custom).unwrap(),
+        "[0123456789]\\-[0123456789ab]\\?"
+    );
+    assert_eq!(to_pattern("x?b", &custom).unwrap(), "x(?-u:[\\x00-\\xFF])");
+    assert!(matches!(to_pattern("?2", &custom), Err(GenError::Mask(_))));
+    assert!(matches!(to_pattern("?z", &custom), Err(GenError::Mask(_))));
+    assert!(matches!(to_pattern("ab?", &custom), Err(GenError::Mask(_))));
+    let hir = regex_syntax::Parser::new()
+        .parse(&to_pattern("?s", &custom).unwrap())
+        .unwrap();
+    assert_eq!(
+        crate::iterate_all(&hir, None, Default::default()).count(),
+        33
+    );
+}
+
+#[test]
+fn test_hcmask_pattern() {
+    let custom = [None, None, None, Some("xy".to_string())];
+    let contents = "# comment\n?d,ab\\,,?1?2?4\n\n\\#?d\n";
+    assert_eq!(
+        hcmask_pattern(contents, &custom).unwrap(),
+        "(?:[0123456789][,ab][xy])|(?:\\#[0123456789])"
+    );
+    assert!(matches!(
+        hcmask_pattern("?l\n?3", &custom),
+        Err(GenError::Mask(message)) if message.starts_with("line 2:")
+    ));
+    assert!(hcmask_pattern("# nothing\n", &custom).is_err());
+}
@@ -0,0 +1,170 @@
+use std::collections::BTreeSet;
+
+use regex_syntax::hir::{self, Class::*, Hir, HirKind::*};
+
+fn decode_char(input: &[u8]) -> Option<char> {
+    (1..=input.len().min(4))
+        .find_map(|n| std::str::from_utf8(&input[..n]).ok())
+        .and_then(|s| s.chars().next())
+}
+
+/// A node part way through [`ends`].
+enum Matching<'a> {
+    /// Matching the rest of a concatenation's parts in turn.
+    Concat(std::slice::Iter<'a, Hir>),
+    /// Matching the rest of an alternation's branches from `starts`.
+    Alternation {
+        rest: std::slice::Iter<'a, Hir>,
+        starts: BTreeSet<usize>,
+        ends: BTreeSet<usize>,
+    },
+    /// Matching one more copy of a repetition's sub-expression.
+    Repetition {
+        repetition: &'a hir::Repetition,
+        count: u32,
+        seen: BTreeSet<usize>,
+        ends: BTreeSet<usize>,
+    },
+}
+
+/// Every position at which a match of `hir` starting at any of `starts`
+/// can end. Runs without recursing, however deeply the pattern nests.
+fn ends(hir: &Hir, input: &[u8], starts: BTreeSet<usize>) -> BTreeSet<usize> {
+    let mut stack = Vec::new();
+    let mut next = Some((hir, starts));
+    let mut positions = BTreeSet::new();
+    loop {
+        if let Some((hir, starts)) = next.take() {
+            positions = match hir.kind() {
+                Empty => starts,
+                // The generator yields nothing for look-arounds, so neither do we.
+                Look(_) => BTreeSet::new(),
+                Literal(literal) => starts
+                    .into_iter()
+                    .filter(|&start| input[start..].starts_with(&literal.0))
+                    .map(|start| start + literal.0.len())
+                    .collect(),
+                Class(Unicode(class)) => starts
+                    .into_iter()
+                    .filter_map(|start| {
+                        let c = decode_char(&input[start..])?;
+                        class
+                            .ranges()
+                            .iter()
+                            .any(|r| r.start() <= c && c <= r.end())
+                            .then(|| start + c.len_utf8())
+                    })
+                    .collect(),
+                Class(Bytes(class)) => starts
+                    .into_iter()
+                    .filter(|&start| {
+                        input.get(start).is_some_and(|&b| {
+                            class
+                                .ranges()
+                                .iter()
+                                .any(|r| r.start() <= b && b <= r.end())
+                        })
+                    })
+                    .map(|start| start + 1)
+                    .collect(),
+                Capture(capture) => {
+                    next = Some((&capture.sub, starts));
+                    continue;
+                }
+                Concat(hirs) => {
+                    stack.push(Matching::Concat(hirs.iter()));
+                    starts
+                }
+                Alternation(hirs) => {
+                    stack.push(Matching::Alternation {
+                        rest: hirs.iter(),
+                        starts,
+                        ends: BTreeSet::new(),
+                    });
+                    BTreeSet::new()
+                }
+                Repetition(repetition) => {
+                    stack.push(Matching::Repetition {
+                        repetition,
+                        count: 0,
+                        seen: BTreeSet::new(),
+                        ends: BTreeSet::new(),
+                    });
+                    starts
+                }
+            };
+            continue;
+        }
+        // Hand the positions just found to the node waiting on them.
+        let Some(matching) = stack.last_mut() else {
+            return positions;
+        };
+        match matching {
+            Matching::Concat(rest) => match rest.next() {
+                Some(hir) => next = Some((hir, std::mem::take(&mut positions))),
+                None => {
+                    stack.pop();
+                }
+            },
+            Matching::Alternation { rest, starts, ends } => {
+                ends.append(&mut positions);
+                match rest.next() {
+                    Some(hir) => next = Some((hir, starts.clone())),
+                    None => {
+                        positions = std::mem::take(ends);
+                        stack.pop();
+                    }
+                }
+            }
+            Matching::Repetition {
+                repetition,
+                count,
+                seen,
+                ends,
+            } => {
+                let mut current = std::mem::take(&mut positions);
+                if *count >= repetition.min {
+                    ends.extend(current.iter().copied());
+                    current.retain(|&position| seen.insert(position));
+                }
+                if current.is_empty() || repetition.max == Some(*count) {
+                    positions = std::mem::take(ends);
+                    stack.pop();
+                } else {
+                    *count += 1;
+                    next = Some((&repetition.sub, current));
+                }
+            }
+        }
+    }
+}
+
+/// Whether `candidate` is one of the results the generator emits for `hir`.
+pub fn matches(hir: &Hir, candidate: &[u8], max_length: Option<usize>) -> bool {
+    max_length.is_none_or(|max_length| candidate.len() <= max_length)
+        && ends(hir, candidate, BTreeSet::from([0])).contains(&candidate.len())
+}
+
+#[test]
+fn test_matches() {
+    let hir = regex_syntax::Parser::new()
+        .parse("(ab|c)*[0-9]{2}é")
+        .unwrap();
+    assert!(matches(&hir, "ababc42é".as_bytes(), None));
+    assert!(matches(&hir, "00é".as_bytes(), None));
+    assert!(!matches(&hir, "ab4é".as_bytes(), None));
+    assert!(!matches(&hir, "ababc42é".as_bytes(), Some(8)));
+    let hir = regex_syntax::Parser::new().parse("(a?)*b{2,3}").unwrap();
+    assert!(matches(&hir, b"aabbb", None));
+    assert!(!matches(&hir, b"abbbb", None));
+    let hir = regex_syntax::Parser::new().parse("^ab").unwrap();
+    assert!(!matches(&hir, b"ab", None));
+    let hir = regex_syntax::Parser::new().parse("a(?:)|").unwrap();
+    assert!(matches(&hir, b"a", None));
+    assert!(matches(&hir, b"", None));
+    let depth = 5000;
+    let pattern = format!("{}a{}", "(?:".repeat(depth), "|b)".repeat(depth));
+    let pattern = crate::Pattern::new(&pattern).unwrap();
+    assert!(matches(pattern.hir(), b"b", None));
+    assert!(!matches(pattern.hir(), b"ab", None));
+}
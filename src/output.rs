@@ -0,0 +1,569 @@
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+};
+
+/// Buffer size for output files, large enough that writes are rarely the
+/// bottleneck.
+pub const OUTPUT_BUFFER: usize = 1 << 20;
+
+/// Parses a size such as `500M` or `1G`, with binary K, M, G and T suffixes.
+pub fn parse_size(size: &str) -> Result<u64, String> {
+    let digits = size.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let shift = match &size[digits.len()..] {
+        "" | "B" => 0,
+        "K" | "KB" | "KiB" => 10,
+        "M" | "MB" | "MiB" => 20,
+        "G" | "GB" | "GiB" => 30,
+        "T" | "TB" | "TiB" => 40,
+        suffix => Err(format!("unknown size suffix {suffix:?}"))?,
+    };
+    let n: u64 = digits
+        .parse()
+        .map_err(|error| format!("{size:?} isn't a size: {error}"))?;
+    n.checked_mul(1 << shift)
+        .filter(|&n| n > 0)
+        .ok_or_else(|| format!("{size:?} must be between 1 byte and 2^64"))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Codec {
+    Gzip,
+    Zstd,
+}
+
+/// How output files are compressed, parsed from `gzip` or `zstd` with an
+/// optional `:LEVEL`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Compression {
+    codec: Codec,
+    level: Option<i32>,
+}
+
+pub fn parse_compression(compression: &str) -> Result<Compression, String> {
+    let (codec, level) = match compression.split_once(':') {
+        Some((codec, level)) => (codec, Some(level)),
+        None => (compression, None),
+    };
+    let (codec, levels) = match codec {
+        "gzip" | "gz" => (Codec::Gzip, 0..=9),
+        "zstd" | "zst" => (Codec::Zstd, zstd::compression_level_range()),
+        codec => Err(format!(
+            "unknown compression {codec:?}; expected gzip or zstd"
+        ))?,
+    };
+    let level = level
+        .map(|level| {
+            level
+                .parse()
+                .ok()
+                .filter(|level| levels.contains(level))
+                .ok_or_else(|| {
+                    format!(
+                        "{codec:?} levels go from {} to {}, not {level:?}",
+                        levels.start(),
+                        levels.end()
+                    )
+                })
+        })
+        .transpose()?;
+    Ok(Compression { codec, level })
+}
+
+impl Compression {
+    /// Extension added to the names of split files.
+    fn extension(self) -> &'static str {
+        match self.codec {
+            Codec::Gzip => ".gz",
+            Codec::Zstd => ".zst",
+        }
+    }
+}
+
+/// A buffered output file, compressed or not. A compressed file is only
+/// complete once [`FileWriter::finish`] has ended its stream.
+pub enum FileWriter {
+    Plain(BufWriter<File>),
+    Gzip(BufWriter<flate2::write::GzEncoder<File>>),
+    Zstd(BufWriter<zstd::Encoder<'static, File>>),
+}
+
+impl FileWriter {
+    /// Wraps `file`, compressed if `compression` is given.
+    pub fn new(file: File, compression: Option<Compression>) -> io::Result<Self> {
+        let Some(compression) = compression else {
+            return Ok(FileWriter::Plain(BufWriter::with_capacity(
+                OUTPUT_BUFFER,
+                file,
+            )));
+        };
+        Ok(match compression.codec {
+            Codec::Gzip => FileWriter::Gzip(BufWriter::with_capacity(
+                OUTPUT_BUFFER,
+                flate2::write::GzEncoder::new(
+                    file,
+                    compression
+                        .level
+                        .map_or_else(flate2::Compression::default, |level| {
+                            flate2::Compression::new(level as u32)
+                        }),
+                ),
+            )),
+            Codec::Zstd => FileWriter::Zstd(BufWriter::with_capacity(
+                OUTPUT_BUFFER,
+                zstd::Encoder::new(
+                    file,
+                    compression.level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL),
+                )?,
+            )),
+        })
+    }
+
+    /// Writes out what's buffered and ends the compressed stream, if there
+    /// is one. Nothing more can be written after.
+    pub fn finish(&mut self) -> io::Result<()> {
+        match self {
+            FileWriter::Plain(writer) => writer.flush(),
+            FileWriter::Gzip(writer) => {
+                writer.flush()?;
+                writer.get_mut().try_finish()
+            }
+            FileWriter::Zstd(writer) => {
+                writer.flush()?;
+                writer.get_mut().do_finish()
+            }
+        }
+    }
+}
+
+impl Write for FileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            FileWriter::Plain(writer) => writer.write(buf),
+            FileWriter::Gzip(writer) => writer.write(buf),
+            FileWriter::Zstd(writer) => writer.write(buf),
+        }
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        match self {
+            FileWriter::Plain(writer) => writer.write_all(buf),
+            FileWriter::Gzip(writer) => writer.write_all(buf),
+            FileWriter::Zstd(writer) => writer.write_all(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            FileWriter::Plain(writer) => writer.flush(),
+            FileWriter::Gzip(writer) => writer.flush(),
+            FileWriter::Zstd(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Writes lines to `PATH.0000`, `PATH.0001` and so on, starting the next file
+/// once the current one holds `max_lines` lines or another line would take it
+/// past `max_bytes`. Sizes are counted before compression.
+pub struct SplitFiles {
+    path: PathBuf,
+    compression: Option<Compression>,
+    max_lines: Option<u64>,
+    max_bytes: Option<u64>,
+    next: usize,
+    current: Option<FileWriter>,
+    lines: u64,
+    bytes: u64,
+}
+
+impl SplitFiles {
+    pub fn new(
+        path: PathBuf,
+        compression: Option<Compression>,
+        max_lines: Option<u64>,
+        max_bytes: Option<u64>,
+    ) -> Self {
+        Self {
+            path,
+            compression,
+            max_lines,
+            max_bytes,
+            next: 0,
+            current: None,
+            lines: 0,
+            bytes: 0,
+        }
+    }
+
+    fn write_line(&mut self, line: &[u8], delimiter: &[u8]) -> io::Result<()> {
+        let len = (line.len() + delimiter.len()) as u64;
+        let full = self.max_lines.is_some_and(|max| self.lines >= max)
+            || self
+                .max_bytes
+                .is_some_and(|max| self.bytes > 0 && self.bytes + len > max);
+        if full && let Some(mut current) = self.current.take() {
+            current.finish()?;
+        }
+        let current = match &mut self.current {
+            Some(current) => current,
+            None => {
+                let mut path = OsString::from(&self.path);
+                path.push(format!(".{:04}", self.next));
+                path.push(self.compression.map_or("", Compression::extension));
+                self.next += 1;
+                self.lines = 0;
+                self.bytes = 0;
+                self.current
+                    .insert(FileWriter::new(File::create(path)?, self.compression)?)
+            }
+        };
+        current.write_all(line)?;
+        current.write_all(delimiter)?;
+        self.lines += 1;
+        self.bytes += len;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.current {
+            Some(current) => current.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Ends the last file.
+    fn finish(&mut self) -> io::Result<()> {
+        match self.current.take() {
+            Some(mut current) => current.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Hands out lines in batches of `batch_size` over a Unix socket, one batch
+/// per connection, so several local consumers share one enumeration without
+/// overlap. Each batch waits for a client to connect.
+#[cfg(unix)]
+pub struct Feeder {
+    listener: std::os::unix::net::UnixListener,
+    path: PathBuf,
+    batch_size: usize,
+    batch: Vec<u8>,
+    lines: usize,
+}
+
+#[cfg(unix)]
+impl Feeder {
+    pub fn bind(path: PathBuf, batch_size: usize) -> io::Result<Self> {
+        Ok(Self {
+            listener: std::os::unix::net::UnixListener::bind(&path)?,
+            path,
+            batch_size: batch_size.max(1),
+            batch: Vec::new(),
+            lines: 0,
+        })
+    }
+
+    fn write_line(&mut self, line: &[u8], delimiter: &[u8]) -> io::Result<()> {
+        self.batch.extend_from_slice(line);
+        self.batch.extend_from_slice(delimiter);
+        self.lines += 1;
+        if self.lines >= self.batch_size {
+            self.send()?;
+        }
+        Ok(())
+    }
+
+    /// Sends the lines so far to the next client to connect.
+    fn send(&mut self) -> io::Result<()> {
+        let (mut client, _) = self.listener.accept()?;
+        // A client that hangs up early loses its batch rather than stopping
+        // the enumeration.
+        if let Err(error) = client.write_all(&self.batch) {
+            eprintln!(
+                "warning: a batch of {} results was lost: {error}",
+                self.lines
+            );
+        }
+        self.batch.clear();
+        self.lines = 0;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if self.lines > 0 {
+            self.send()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Feeder {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+enum Writer {
+    Stream(Box<dyn Write>),
+    File(FileWriter),
+    Split(SplitFiles),
+    #[cfg(unix)]
+    Feed(Feeder),
+}
+
+struct Sink {
+    writer: Writer,
+    every: usize,
+}
+
+/// Writes each result line to several sinks, each with its own buffering
+/// and sampling interval.
+pub struct FanOut {
+    sinks: Vec<Sink>,
+    count: usize,
+    bytes: u64,
+    flush_every: Option<usize>,
+    delimiter: Vec<u8>,
+}
+
+impl FanOut {
+    pub fn new() -> Self {
+        Self {
+            sinks: Vec::new(),
+            count: 0,
+            bytes: 0,
+            flush_every: None,
+            delimiter: b"\n".to_vec(),
+        }
+    }
+
+    /// Flushes every sink after each `lines` lines, so consumers see
+    /// results promptly rather than a buffer at a time.
+    pub fn flush_every(&mut self, lines: usize) {
+        self.flush_every = Some(lines.max(1));
+    }
+
+    /// Ends each line with `delimiter` instead of a newline.
+    pub fn delimiter(&mut self, delimiter: Vec<u8>) {
+        self.delimiter = delimiter;
+    }
+
+    /// Adds a sink that receives every `every`th line.
+    pub fn add(&mut self, writer: impl Write + 'static, every: usize) {
+        self.sinks.push(Sink {
+            writer: Writer::Stream(Box::new(writer)),
+            every: every.max(1),
+        });
+    }
+
+    /// Adds a sink that receives every line, written to a file that
+    /// [`FanOut::finish`] ends.
+    pub fn add_file(&mut self, file: FileWriter) {
+        self.sinks.push(Sink {
+            writer: Writer::File(file),
+            every: 1,
+        });
+    }
+
+    /// Adds a sink that receives every line, in batches over a socket.
+    #[cfg(unix)]
+    pub fn add_feeder(&mut self, feeder: Feeder) {
+        self.sinks.push(Sink {
+            writer: Writer::Feed(feeder),
+            every: 1,
+        });
+    }
+
+    /// Adds a sink that receives every line, split across files.
+    pub fn add_split(&mut self, split: SplitFiles) {
+        self.sinks.push(Sink {
+            writer: Writer::Split(split),
+            every: 1,
+        });
+    }
+
+    pub fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        for sink in &mut self.sinks {
+            if !self.count.is_multiple_of(sink.every) {
+                continue;
+            }
+            match &mut sink.writer {
+                Writer::Stream(writer) => {
+                    writer.write_all(line)?;
+                    writer.write_all(&self.delimiter)?;
+                }
+                Writer::File(file) => {
+                    file.write_all(line)?;
+                    file.write_all(&self.delimiter)?;
+                }
+                Writer::Split(split) => split.write_line(line, &self.delimiter)?,
+                #[cfg(unix)]
+                Writer::Feed(feeder) => feeder.write_line(line, &self.delimiter)?,
+            }
+        }
+        self.count += 1;
+        self.bytes += (line.len() + self.delimiter.len()) as u64;
+        if self
+            .flush_every
+            .is_some_and(|lines| self.count.is_multiple_of(lines))
+        {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Whether writing `line` would keep the total size within `max_bytes`.
+    pub fn fits(&self, line: &[u8], max_bytes: u64) -> bool {
+        self.bytes + (line.len() + self.delimiter.len()) as u64 <= max_bytes
+    }
+
+    /// Total size of the lines written so far, including delimiters.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        for sink in &mut self.sinks {
+            match &mut sink.writer {
+                Writer::Stream(writer) => writer.flush()?,
+                Writer::File(file) => file.flush()?,
+                Writer::Split(split) => split.flush()?,
+                // Batches only go out once full, or at the end.
+                #[cfg(unix)]
+                Writer::Feed(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Flushes every sink, ends compressed files, and sends the last partial
+    /// batch of each feeder. Files aren't complete until this returns.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.flush()?;
+        for sink in &mut self.sinks {
+            match &mut sink.writer {
+                Writer::Stream(_) => {}
+                Writer::File(file) => file.finish()?,
+                Writer::Split(split) => split.finish()?,
+                #[cfg(unix)]
+                Writer::Feed(feeder) => feeder.finish()?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_parse_size() {
+    assert_eq!(parse_size("512"), Ok(512));
+    assert_eq!(parse_size("1G"), Ok(1 << 30));
+    assert_eq!(parse_size("3MiB"), Ok(3 << 20));
+    assert!(parse_size("0").is_err());
+    assert!(parse_size("1X").is_err());
+    assert!(parse_size("G").is_err());
+}
+
+#[test]
+fn test_split_files() {
+    let dir = std::env::temp_dir().join(format!("passwd-gen-split-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut split = SplitFiles::new(dir.join("out"), None, Some(3), Some(8));
+    for line in ["a", "bb", "c", "d", "eeeeeeeeee", "f"] {
+        split.write_line(line.as_bytes(), b"\n").unwrap();
+    }
+    split.flush().unwrap();
+    let read = |n: &str| std::fs::read_to_string(dir.join(format!("out.{n}"))).unwrap();
+    assert_eq!(read("0000"), "a\nbb\nc\n");
+    assert_eq!(read("0001"), "d\n");
+    assert_eq!(read("0002"), "eeeeeeeeee\n");
+    assert_eq!(read("0003"), "f\n");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_compressed_files() {
+    use std::io::Read;
+    let dir = std::env::temp_dir().join(format!("passwd-gen-compress-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let gzip = parse_compression("gzip").ok();
+    let mut out = FanOut::new();
+    let file = File::create(dir.join("out.gz")).unwrap();
+    out.add_file(FileWriter::new(file, gzip).unwrap());
+    out.add_split(SplitFiles::new(
+        dir.join("split"),
+        parse_compression("zstd").ok(),
+        Some(2),
+        None,
+    ));
+    for line in ["a", "b", "c"] {
+        out.write_line(line.as_bytes()).unwrap();
+    }
+    // The streams must be complete before anything is dropped.
+    out.finish().unwrap();
+    let mut gunzipped = String::new();
+    flate2::read::GzDecoder::new(File::open(dir.join("out.gz")).unwrap())
+        .read_to_string(&mut gunzipped)
+        .unwrap();
+    assert_eq!(gunzipped, "a\nb\nc\n");
+    let unzstd = |n: &str| zstd::decode_all(File::open(dir.join(n)).unwrap()).unwrap();
+    assert_eq!(unzstd("split.0000.zst"), b"a\nb\n");
+    assert_eq!(unzstd("split.0001.zst"), b"c\n");
+    drop(out);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_parse_compression() {
+    assert_eq!(
+        parse_compression("gzip:9"),
+        Ok(Compression {
+            codec: Codec::Gzip,
+            level: Some(9)
+        })
+    );
+    assert_eq!(
+        parse_compression("zstd"),
+        Ok(Compression {
+            codec: Codec::Zstd,
+            level: None
+        })
+    );
+    assert!(parse_compression("gzip:10").is_err());
+    assert!(parse_compression("zstd:x").is_err());
+    assert!(parse_compression("bzip2").is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_feeder() {
+    use std::{io::Read, os::unix::net::UnixStream};
+    let path = std::env::temp_dir().join(format!("passwd-gen-{}.sock", std::process::id()));
+    let mut out = FanOut::new();
+    out.add_feeder(Feeder::bind(path.clone(), 2).unwrap());
+    let client = std::thread::spawn({
+        let path = path.clone();
+        move || {
+            (0..2)
+                .map(|_| {
+                    let mut batch = String::new();
+                    UnixStream::connect(&path)
+                        .unwrap()
+                        .read_to_string(&mut batch)
+                        .unwrap();
+                    batch
+                })
+                .collect::<Vec<_>>()
+        }
+    });
+    for line in ["a", "b", "c"] {
+        out.write_line(line.as_bytes()).unwrap();
+    }
+    out.finish().unwrap();
+    assert_eq!(client.join().unwrap(), ["a\nb\n", "c\n"]);
+    drop(out);
+    assert!(!path.exists());
+}
@@ -0,0 +1,104 @@
+//! Enumeration split into chunks that are generated on several threads and
+//! yielded in the original order.
+
+use std::vec;
+
+use num_bigint::BigUint;
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder, prelude::*};
+use regex_syntax::hir::Hir;
+
+use crate::{Candidate, space::Space};
+
+/// Like [`crate::iterate_branches`], but each batch of chunks is generated in
+/// parallel by seeking to the start of every chunk with [`Space::iterate_branches_from`].
+pub struct ParallelBranches<'a> {
+    space: Space,
+    hir: &'a Hir,
+    pool: ThreadPool,
+    chunk_size: usize,
+    next: BigUint,
+    end: BigUint,
+    batch: vec::IntoIter<(usize, Candidate)>,
+}
+
+impl<'a> ParallelBranches<'a> {
+    /// Generates results `start..end` of `hir` on `threads` threads. `space`
+    /// must have been built from `hir`; `end` defaults to its total.
+    pub fn new(
+        space: Space,
+        hir: &'a Hir,
+        start: BigUint,
+        end: Option<BigUint>,
+        threads: usize,
+    ) -> Result<Self, ThreadPoolBuildError> {
+        let end = end.unwrap_or_else(|| space.total());
+        Ok(Self {
+            space,
+            hir,
+            pool: ThreadPoolBuilder::new().num_threads(threads).build()?,
+            chunk_size: 1 << 16,
+            next: start,
+            end,
+            batch: Vec::new().into_iter(),
+        })
+    }
+
+    /// Number of results each thread generates at a time.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    fn next_batch(&mut self) -> Vec<(usize, Candidate)> {
+        let chunks = self.pool.current_num_threads() * 4;
+        let mut starts = Vec::new();
+        while starts.len() < chunks && self.next < self.end {
+            let length = (&self.end - &self.next).min(self.chunk_size.into());
+            starts.push((self.next.clone(), usize::try_from(&length).unwrap()));
+            self.next += length;
+        }
+        let (space, hir) = (&self.space, self.hir);
+        let chunks: Vec<Vec<_>> = self.pool.install(|| {
+            starts
+                .par_iter()
+                .map(|(start, length)| {
+                    space
+                        .iterate_branches_from(hir, start)
+                        .take(*length)
+                        .collect()
+                })
+                .collect()
+        });
+        chunks.into_iter().flatten().collect()
+    }
+}
+
+impl Iterator for ParallelBranches<'_> {
+    type Item = (usize, Candidate);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(result) = self.batch.next() {
+                return Some(result);
+            }
+            if self.next >= self.end {
+                return None;
+            }
+            self.batch = self.next_batch().into_iter();
+        }
+    }
+}
+
+#[test]
+fn test_parallel_branches() {
+    let hir = regex_syntax::Parser::new()
+        .parse("[a-c]{0,3}(x|yz)?|[0-9]{2}")
+        .unwrap();
+    let expected: Vec<_> = crate::iterate_branches(&hir, 0, None, Default::default()).collect();
+    let space = Space::new(&hir, None).unwrap();
+    let parallel: Vec<_> = ParallelBranches::new(space, &hir, 5u32.into(), None, 3)
+        .unwrap()
+        .chunk_size(7)
+        .collect();
+    assert_eq!(parallel, expected[5..]);
+}
@@ -0,0 +1,148 @@
+use std::{error::Error, fs, path::PathBuf};
+
+use clap::Args;
+use passwd_gen::template;
+
+/// Words used without --wordlist: 256 short, common and distinct ones, so
+/// each adds 8 bits. EFF's long list adds about 12.9.
+const WORDS: &[&str] = &[
+    "acid", "acorn", "actor", "adobe", "agent", "alarm", "album", "alien", "alley", "amber",
+    "angel", "ankle", "apple", "apron", "arena", "armor", "arrow", "atlas", "attic", "audio",
+    "award", "bacon", "badge", "bagel", "baker", "bamboo", "banjo", "barn", "basil", "batch",
+    "beach", "beard", "bench", "berry", "bible", "bison", "blade", "blank", "blaze", "blimp",
+    "bloom", "board", "boat", "bonus", "book", "boost", "boots", "brain", "brass", "bread",
+    "brick", "bride", "broom", "brush", "bucket", "buddy", "bugle", "bunny", "cabin", "cable",
+    "cactus", "camel", "candy", "canoe", "canvas", "cargo", "carpet", "carrot", "cedar", "chair",
+    "chalk", "charm", "cheek", "chess", "chief", "chimp", "cider", "cigar", "cinema", "circle",
+    "civic", "clamp", "cliff", "clock", "cloud", "clown", "coast", "cobra", "cocoa", "comet",
+    "coral", "couch", "cougar", "crane", "crater", "crayon", "creek", "crisp", "crown", "crumb",
+    "cube", "cupid", "curry", "cycle", "daisy", "dance", "delta", "denim", "depot", "diary",
+    "diner", "disco", "ditch", "dizzy", "dock", "dodge", "dolphin", "donut", "dragon", "drama",
+    "dream", "drift", "drum", "duck", "dune", "eagle", "earth", "easel", "echo", "elbow", "elder",
+    "elk", "ember", "emoji", "empty", "engine", "envoy", "epic", "equal", "error", "ethic",
+    "event", "fable", "fairy", "falcon", "fancy", "farm", "feast", "fence", "ferry", "fiber",
+    "field", "filter", "flame", "flask", "fleet", "flute", "foam", "focus", "forest", "fossil",
+    "frame", "frost", "fruit", "fudge", "galaxy", "gamma", "garden", "gecko", "ghost", "giant",
+    "ginger", "glass", "globe", "glove", "goose", "grape", "gravy", "guitar", "habit", "hammer",
+    "harbor", "harp", "hazel", "helmet", "hippo", "honey", "hotel", "humor", "igloo", "index",
+    "ink", "iris", "island", "ivory", "jacket", "jelly", "jewel", "jockey", "judge", "juice",
+    "jungle", "karma", "kayak", "kettle", "kiosk", "kite", "koala", "label", "ladder", "lagoon",
+    "lamp", "laser", "lemon", "lever", "lilac", "linen", "llama", "lobby", "lotus", "lunar",
+    "magnet", "mango", "maple", "marble", "meadow", "melon", "metal", "mint", "mirror", "moose",
+    "mosaic", "motor", "mural", "napkin", "nectar", "noodle", "north", "nugget", "oasis", "ocean",
+    "olive", "omega", "onion", "opera", "orbit", "otter", "oyster", "paddle", "panda", "parrot",
+    "pasta", "peach", "pencil", "pepper", "piano",
+];
+
+/// Generate random passphrases of words from a wordlist, drawn with the
+/// operating system's secure random number generator unless --seed is given
+#[derive(Args)]
+pub struct PassphraseArgs {
+    /// Number of passphrases to generate
+    #[clap(default_value_t = 1)]
+    pub count: usize,
+
+    /// Words in each passphrase
+    #[clap(long, value_name = "N", default_value_t = 6)]
+    pub words: u32,
+
+    /// File of words to draw from, one per line, such as one of EFF's dice
+    /// lists. A dice number before each word is skipped
+    #[clap(long, value_name = "FILE")]
+    pub wordlist: Option<PathBuf>,
+
+    /// Put this between words
+    #[clap(long, default_value = "-")]
+    pub separator: String,
+
+    /// Start each word with an uppercase letter
+    #[clap(long)]
+    pub capitalize: bool,
+
+    /// Put a random digit after one of the words
+    #[clap(long)]
+    pub digit: bool,
+}
+
+fn capitalized(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+impl PassphraseArgs {
+    fn wordlist(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut words: Vec<String> = match &self.wordlist {
+            Some(path) => fs::read_to_string(path)
+                .map_err(|error| format!("{}: {error}", path.display()))?
+                .lines()
+                .filter_map(|line| line.split_whitespace().last())
+                .map(String::from)
+                .collect(),
+            None => WORDS.iter().map(|word| word.to_string()).collect(),
+        };
+        if self.capitalize {
+            words = words.iter().map(|word| capitalized(word)).collect();
+        }
+        // A repeated word would be drawn more often than the rest.
+        words.sort_unstable();
+        words.dedup();
+        if words.is_empty() {
+            Err("The wordlist has no words in it.")?
+        }
+        Ok(words)
+    }
+
+    /// A pattern matching every passphrase once, for sampling from
+    /// uniformly.
+    pub fn pattern(&self) -> Result<String, Box<dyn Error>> {
+        if self.words == 0 {
+            Err("--words must be at least 1.")?
+        }
+        let word = template::alternation(&self.wordlist()?);
+        let separator = regex_syntax::escape(&self.separator);
+        let rest = self.words - 1;
+        Ok(match self.digit {
+            false => format!("{word}(?:{separator}{word}){{{rest}}}"),
+            // One alternative for each word the digit can follow.
+            true => {
+                let alternatives: Vec<_> = (0..self.words)
+                    .map(|before| {
+                        let after = rest - before;
+                        format!(
+                            "(?:{word}{separator}){{{before}}}{word}[0-9](?:{separator}{word}){{{after}}}"
+                        )
+                    })
+                    .collect();
+                format!("(?:{})", alternatives.join("|"))
+            }
+        })
+    }
+}
+
+#[test]
+fn test_passphrase_pattern() {
+    assert_eq!(WORDS.len(), 256);
+    let mut sorted = WORDS.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    assert_eq!(sorted.len(), 256);
+    let path = std::env::temp_dir().join(format!("passwd-gen-passphrase-{}", std::process::id()));
+    fs::write(&path, "11111\tabacus\n11112\tabdomen\n11113\tabacus\n").unwrap();
+    let args = PassphraseArgs {
+        count: 1,
+        words: 2,
+        wordlist: Some(path.clone()),
+        separator: ".".to_string(),
+        capitalize: true,
+        digit: true,
+    };
+    let pattern = args.pattern().unwrap();
+    fs::remove_file(&path).unwrap();
+    let hir = regex_syntax::Parser::new().parse(&pattern).unwrap();
+    let results: Vec<_> = passwd_gen::iterate_all(&hir, None, Default::default()).collect();
+    assert_eq!(results.len(), 2 * 2 * 2 * 10);
+    assert_eq!(&results[0][..], b"Abacus0.Abacus");
+}
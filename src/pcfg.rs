@@ -0,0 +1,258 @@
+//! The `pcfg` subcommand: a probabilistic grammar of password structures
+//! such as L6D2, learned from a wordlist along with the letters, digits and
+//! symbols that fill them, and enumerated from the most probable result down.
+
+use std::{
+    collections::{BinaryHeap, HashMap},
+    error::Error,
+    fmt::Write,
+    path::PathBuf,
+};
+
+use clap::Args;
+use passwd_gen::{Candidate, template};
+
+use crate::markov::{Ranked, read_corpus};
+
+/// Enumerate strings from the most to the least probable under a grammar of
+/// password structures, such as six letters then two digits, trained on a
+/// wordlist
+#[derive(Args)]
+pub struct PcfgArgs {
+    /// Wordlist to train on, one word per line
+    pub corpus: PathBuf,
+
+    /// Stop at results less probable than this
+    #[clap(long, value_name = "P")]
+    pub threshold: Option<f64>,
+
+    /// Print each structure learned and its probability to stderr
+    #[clap(long)]
+    pub show_structures: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Kind {
+    Letter,
+    Digit,
+    Symbol,
+}
+
+impl Kind {
+    fn of(c: char) -> Self {
+        match c {
+            _ if c.is_alphabetic() => Kind::Letter,
+            _ if c.is_ascii_digit() => Kind::Digit,
+            _ => Kind::Symbol,
+        }
+    }
+
+    fn code(self) -> char {
+        match self {
+            Kind::Letter => 'L',
+            Kind::Digit => 'D',
+            Kind::Symbol => 'S',
+        }
+    }
+}
+
+/// A run of characters of one kind, as its kind and length in characters.
+type Segment = (Kind, usize);
+
+/// Splits `word` into its runs of letters, digits and symbols.
+fn segments(word: &str) -> Vec<(Segment, &str)> {
+    let mut segments: Vec<(Segment, &str)> = Vec::new();
+    let mut start = 0;
+    for (i, c) in word.char_indices() {
+        let kind = Kind::of(c);
+        match segments.last_mut() {
+            Some(((last, length), text)) if *last == kind => {
+                *length += 1;
+                *text = &word[start..i + c.len_utf8()];
+            }
+            _ => {
+                start = i;
+                segments.push(((kind, 1), &word[i..i + c.len_utf8()]));
+            }
+        }
+    }
+    segments
+}
+
+/// Entries of `counts` with their log probabilities, most probable first and
+/// then in order.
+fn ranked<T: Ord>(counts: HashMap<T, u64>) -> Vec<(T, f64)> {
+    let total = counts.values().sum::<u64>() as f64;
+    let mut ranked: Vec<_> = counts
+        .into_iter()
+        .map(|(item, count)| (item, (count as f64 / total).ln()))
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    ranked
+}
+
+pub struct Grammar {
+    /// Each structure with its log probability, most probable first.
+    structures: Vec<(Vec<Segment>, f64)>,
+    /// The strings seen in each kind and length of segment with their log
+    /// probabilities, most probable first.
+    terminals: HashMap<Segment, Vec<(String, f64)>>,
+    alphabet: Vec<char>,
+    threshold: f64,
+}
+
+impl PcfgArgs {
+    pub fn grammar(&self) -> Result<Grammar, Box<dyn Error>> {
+        let grammar = Grammar::train(&read_corpus(&self.corpus)?).threshold(self.threshold);
+        if self.show_structures {
+            eprint!("{}", grammar.structures());
+        }
+        Ok(grammar)
+    }
+}
+
+impl Grammar {
+    pub fn train(words: &[impl AsRef<str>]) -> Self {
+        let mut structures: HashMap<Vec<Segment>, u64> = HashMap::new();
+        let mut terminals: HashMap<Segment, HashMap<String, u64>> = HashMap::new();
+        let mut alphabet = Vec::new();
+        for word in words {
+            let word = word.as_ref();
+            let mut structure = Vec::new();
+            for (segment, text) in segments(word) {
+                *terminals
+                    .entry(segment)
+                    .or_default()
+                    .entry(text.to_string())
+                    .or_default() += 1;
+                structure.push(segment);
+            }
+            *structures.entry(structure).or_default() += 1;
+            alphabet.extend(word.chars());
+        }
+        alphabet.sort_unstable();
+        alphabet.dedup();
+        Self {
+            structures: ranked(structures),
+            terminals: terminals
+                .into_iter()
+                .map(|(segment, counts)| (segment, ranked(counts)))
+                .collect(),
+            alphabet,
+            threshold: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Leave out results less probable than `threshold`.
+    pub fn threshold(mut self, threshold: Option<f64>) -> Self {
+        self.threshold = threshold.map_or(f64::NEG_INFINITY, f64::ln);
+        self
+    }
+
+    /// Each structure such as L6D2 and its probability, one per line.
+    pub fn structures(&self) -> String {
+        let mut listing = String::new();
+        for (structure, log_probability) in &self.structures {
+            for (kind, length) in structure {
+                write!(listing, "{}{length}", kind.code()).unwrap();
+            }
+            writeln!(listing, " {:.6}", log_probability.exp()).unwrap();
+        }
+        listing
+    }
+
+    /// A pattern matching every result and more, for the options that
+    /// match against the pattern.
+    pub fn pattern(&self) -> String {
+        let lengths = self
+            .structures
+            .iter()
+            .map(|(structure, _)| structure.iter().map(|(_, length)| length).sum::<usize>());
+        let (min, max) = (lengths.clone().min(), lengths.max());
+        let chars: Vec<_> = self.alphabet.iter().map(char::to_string).collect();
+        format!(
+            "{}{{{},{}}}",
+            template::alternation(&chars),
+            min.unwrap_or(0),
+            max.unwrap_or(0)
+        )
+    }
+
+    /// Iterates over results from the most probable, breaking ties by
+    /// which was reached first.
+    pub fn iter(&self) -> impl Iterator<Item = Candidate> + '_ {
+        let terminals = |structure: &[Segment]| -> Vec<&Vec<(String, f64)>> {
+            structure
+                .iter()
+                .map(|segment| &self.terminals[segment])
+                .collect()
+        };
+        // Each item is a structure, which terminal of each of its segments is
+        // used, and the first segment its successors may advance. Advancing
+        // only from there on reaches each combination exactly once.
+        let mut heap = BinaryHeap::new();
+        let mut sequence = 0;
+        for (i, (structure, log_probability)) in self.structures.iter().enumerate() {
+            let log_probability = terminals(structure)
+                .iter()
+                .map(|terminals| terminals[0].1)
+                .sum::<f64>()
+                + log_probability;
+            if log_probability >= self.threshold {
+                sequence += 1;
+                heap.push(Ranked {
+                    log_probability,
+                    sequence,
+                    item: (i, vec![0; structure.len()], 0),
+                });
+            }
+        }
+        std::iter::from_fn(move || {
+            let entry = heap.pop()?;
+            let (i, chosen, pivot) = entry.item;
+            let terminals = terminals(&self.structures[i].0);
+            let mut word = String::new();
+            for (terminals, &j) in terminals.iter().zip(&chosen) {
+                word += &terminals[j].0;
+            }
+            for segment in pivot..chosen.len() {
+                let Some(next) = terminals[segment].get(chosen[segment] + 1) else {
+                    continue;
+                };
+                let log_probability =
+                    entry.log_probability - terminals[segment][chosen[segment]].1 + next.1;
+                if log_probability < self.threshold {
+                    continue;
+                }
+                let mut chosen = chosen.clone();
+                chosen[segment] += 1;
+                sequence += 1;
+                heap.push(Ranked {
+                    log_probability,
+                    sequence,
+                    item: (i, chosen, segment),
+                });
+            }
+            Some(Candidate::from_slice(word.as_bytes()))
+        })
+    }
+}
+
+#[test]
+fn test_pcfg() {
+    let grammar = Grammar::train(&["abc12", "abc12", "xyz12", "abc7", "abc7", "hi!"]);
+    assert_eq!(
+        grammar.structures(),
+        "L3D2 0.500000\nL3D1 0.333333\nL2S1 0.166667\n"
+    );
+    let results: Vec<_> = grammar
+        .iter()
+        .map(|result| String::from_utf8(result.to_vec()).unwrap())
+        .collect();
+    // Three letters are abc 4 times in 5, so abc12 is 0.5 × 0.8 likely, abc7
+    // 0.33 × 0.8 and xyz12 0.5 × 0.2.
+    assert_eq!(results, ["abc12", "abc7", "hi!", "xyz12", "xyz7"]);
+    let grammar = grammar.threshold(Some(0.2));
+    assert_eq!(grammar.iter().count(), 2);
+    assert_eq!(grammar.pattern(), "(?:!|1|2|7|a|b|c|h|i|x|y|z){3,5}");
+}
@@ -0,0 +1,77 @@
+use clap::Args;
+
+/// Frequently chosen PINs, dropped by `--no-common`.
+const COMMON_PINS: &[&str] = &[
+    "0000", "1111", "1212", "1234", "1004", "2000", "2222", "3333", "4321", "4444", "5555", "6666",
+    "6969", "7777", "8888", "9999", "1122", "1313", "1010", "2001", "0123", "1357", "2468", "2580",
+    "0852", "1478", "9876", "5683", "0007", "1998", "1999", "000000", "111111", "112233", "121212",
+    "123123", "123321", "123456", "159753", "654321", "666666", "696969", "888888", "11111111",
+    "12341234", "12345678", "87654321",
+];
+
+/// Enumerate numeric PINs
+#[derive(Args)]
+pub struct PinsArgs {
+    /// Number of digits in each PIN
+    #[clap(default_value_t = 4)]
+    pub digits: usize,
+
+    /// Drop PINs whose digits ascend or descend by one (e.g. 1234, 9876)
+    #[clap(long)]
+    pub no_sequential: bool,
+
+    /// Drop PINs made of a single repeated digit (e.g. 1111)
+    #[clap(long)]
+    pub no_repeated: bool,
+
+    /// Drop PINs found in a built-in list of commonly used PINs
+    #[clap(long)]
+    pub no_common: bool,
+}
+
+impl PinsArgs {
+    pub fn pattern(&self) -> String {
+        format!("[0-9]{{{}}}", self.digits)
+    }
+
+    pub fn keep(&self, pin: &str) -> bool {
+        !(self.no_sequential && is_sequential(pin)
+            || self.no_repeated && is_repeated(pin)
+            || self.no_common && COMMON_PINS.contains(&pin))
+    }
+}
+
+fn is_sequential(pin: &str) -> bool {
+    let digits = pin.as_bytes();
+    digits.len() > 1
+        && [1, -1].into_iter().any(|step| {
+            digits
+                .windows(2)
+                .all(|pair| pair[1] as i8 - pair[0] as i8 == step)
+        })
+}
+
+fn is_repeated(pin: &str) -> bool {
+    pin.bytes().all(|digit| Some(digit) == pin.bytes().next())
+}
+
+#[test]
+fn test_pin_filters() {
+    assert!(is_sequential("1234"));
+    assert!(is_sequential("9876"));
+    assert!(!is_sequential("1235"));
+    assert!(is_repeated("7777"));
+    assert!(!is_repeated("7778"));
+    let pins = PinsArgs {
+        digits: 4,
+        no_sequential: true,
+        no_repeated: true,
+        no_common: true,
+    };
+    assert!(!pins.keep("3456"));
+    assert!(!pins.keep("0000"));
+    assert!(!pins.keep("6969"));
+    assert!(pins.keep("8351"));
+    let common: std::collections::HashSet<_> = COMMON_PINS.iter().collect();
+    assert_eq!(common.len(), COMMON_PINS.len());
+}
@@ -0,0 +1,141 @@
+//! Password-policy composition rules, such as requiring an uppercase letter
+//! and a digit.
+
+use clap::ValueEnum;
+use regex_syntax::hir::{Class, Hir, HirKind};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum CharClass {
+    Upper,
+    Lower,
+    Digit,
+    /// Anything printable that isn't a letter, digit or space
+    Symbol,
+}
+
+const CLASSES: [CharClass; 4] = [
+    CharClass::Upper,
+    CharClass::Lower,
+    CharClass::Digit,
+    CharClass::Symbol,
+];
+
+impl CharClass {
+    fn contains(self, c: char) -> bool {
+        match self {
+            CharClass::Upper => c.is_uppercase(),
+            CharClass::Lower => c.is_lowercase(),
+            CharClass::Digit => c.is_ascii_digit(),
+            CharClass::Symbol => !c.is_alphanumeric() && !c.is_whitespace() && !c.is_control(),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            CharClass::Upper => "an uppercase letter",
+            CharClass::Lower => "a lowercase letter",
+            CharClass::Digit => "a digit",
+            CharClass::Symbol => "a symbol",
+        }
+    }
+}
+
+/// Which of [`CLASSES`] some result of `hir` can contain a character of.
+fn possible(hir: &Hir, found: &mut [bool; 4]) {
+    let mut mark = |c: char| {
+        for (found, class) in found.iter_mut().zip(CLASSES) {
+            *found |= class.contains(c);
+        }
+    };
+    let mut pending = vec![hir];
+    while let Some(hir) = pending.pop() {
+        match hir.kind() {
+            HirKind::Literal(literal) => String::from_utf8_lossy(&literal.0)
+                .chars()
+                .for_each(&mut mark),
+            HirKind::Class(Class::Unicode(class)) => {
+                for range in class.ranges() {
+                    (range.start()..=range.end()).for_each(&mut mark);
+                }
+            }
+            HirKind::Class(Class::Bytes(class)) => {
+                for range in class.ranges() {
+                    (range.start()..=range.end().min(0x7F)).for_each(|b| mark(b as char));
+                }
+            }
+            HirKind::Repetition(repetition) if repetition.max != Some(0) => {
+                pending.push(&repetition.sub)
+            }
+            HirKind::Capture(capture) => pending.push(&capture.sub),
+            HirKind::Concat(hirs) | HirKind::Alternation(hirs) => pending.extend(hirs),
+            _ => {}
+        }
+    }
+}
+
+/// Results must contain each of `required`, and characters of at least
+/// `min_classes` of the four classes.
+pub struct Policy {
+    pub required: Vec<CharClass>,
+    pub min_classes: usize,
+}
+
+impl Policy {
+    pub fn is_empty(&self) -> bool {
+        self.required.is_empty() && self.min_classes == 0
+    }
+
+    pub fn keep(&self, candidate: &str) -> bool {
+        let mut present = [false; 4];
+        for c in candidate.chars() {
+            for (present, class) in present.iter_mut().zip(CLASSES) {
+                *present |= class.contains(c);
+            }
+        }
+        let has = |class| present[CLASSES.iter().position(|&c| c == class).unwrap()];
+        self.required.iter().all(|&class| has(class))
+            && present.iter().filter(|&&present| present).count() >= self.min_classes
+    }
+
+    /// Fails if no result of `hir` could satisfy the policy, rather than
+    /// filtering out everything it generates.
+    pub fn check(&self, hir: &Hir) -> Result<(), String> {
+        let mut found = [false; 4];
+        possible(hir, &mut found);
+        let has = |class| found[CLASSES.iter().position(|&c| c == class).unwrap()];
+        if let Some(class) = self.required.iter().find(|&&class| !has(class)) {
+            Err(format!("The pattern never produces {}.", class.name()))?
+        }
+        let classes = found.iter().filter(|&&found| found).count();
+        if classes < self.min_classes {
+            Err(format!(
+                "The pattern only produces characters of {classes} classes, fewer than --min-classes {}.",
+                self.min_classes
+            ))?
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_policy() {
+    let policy = Policy {
+        required: vec![CharClass::Digit],
+        min_classes: 3,
+    };
+    assert!(policy.keep("Pass1"));
+    assert!(policy.keep("pas$1"));
+    assert!(!policy.keep("Pass!"));
+    assert!(!policy.keep("pass1"));
+    let hir = |pattern| {
+        regex_syntax::ParserBuilder::new()
+            .utf8(false)
+            .build()
+            .parse(pattern)
+            .unwrap()
+    };
+    assert!(policy.check(&hir("[A-Za-z]+[0-9]")).is_ok());
+    assert!(policy.check(&hir("[A-Za-z]+")).is_err());
+    assert!(policy.check(&hir("[a-z]+[0-9]{0}|[0-9]")).is_err());
+    assert!(policy.check(&hir("(?-u:[\\x00-\\xFF])+")).is_ok());
+}
@@ -0,0 +1,168 @@
+//! `--per-prefix`, at most K results for each distinct prefix.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+};
+
+use num_bigint::BigUint;
+use passwd_gen::{Candidate, sorted::Prefixes, space::Space};
+use regex_syntax::hir::Hir;
+
+/// Each prefix's results so far. Shared between the filter, which counts
+/// them, and [`Seeking`], which stops generating a prefix once it's full.
+pub struct PerPrefix {
+    limit: usize,
+    chars: usize,
+    counts: RefCell<HashMap<String, usize>>,
+    /// Used to tell once every prefix has as many results as it can get,
+    /// when the pattern can be put in a DFA and is bounded.
+    prefixes: Option<(Prefixes, BigUint)>,
+    done: Cell<usize>,
+}
+
+impl PerPrefix {
+    pub fn new(
+        hir: &Hir,
+        limit: usize,
+        chars: usize,
+        min_length: usize,
+        max_length: Option<usize>,
+    ) -> Self {
+        let prefixes = Prefixes::new(hir, chars, min_length, max_length)
+            .ok()
+            .map(|prefixes| {
+                let count = prefixes.count();
+                (prefixes, count)
+            });
+        Self {
+            limit,
+            chars,
+            counts: RefCell::default(),
+            prefixes,
+            done: Cell::new(0),
+        }
+    }
+
+    fn prefix(&self, result: &[u8]) -> String {
+        String::from_utf8_lossy(result)
+            .chars()
+            .take(self.chars)
+            .collect()
+    }
+
+    /// Counts `result` against its prefix, returning whether the prefix was
+    /// still under the limit.
+    pub fn keep(&self, result: &[u8]) -> bool {
+        let prefix = self.prefix(result);
+        let whole = self.whole_prefix(result);
+        let mut counts = self.counts.borrow_mut();
+        let count = counts.entry(prefix).or_default();
+        *count += 1;
+        if let Some((prefixes, _)) = &self.prefixes {
+            // A result shorter than the prefix is the only one with its prefix.
+            let available = match whole {
+                Some(length) => prefixes.completions(&result[..length]),
+                None => 1u8.into(),
+            };
+            if BigUint::from(*count) == available.min(self.limit.into()) {
+                self.done.set(self.done.get() + 1);
+            }
+        }
+        *count <= self.limit
+    }
+
+    /// The length in bytes of the prefix of `result`, if the result has the
+    /// prefix's full number of characters.
+    fn whole_prefix(&self, result: &[u8]) -> Option<usize> {
+        let prefix = self.prefix(result);
+        (prefix.chars().count() == self.chars && result.starts_with(prefix.as_bytes()))
+            .then_some(prefix.len())
+    }
+
+    /// If `result`'s prefix has had its results, the prefix's length in
+    /// bytes, to skip past the results that share it.
+    pub fn full(&self, result: &[u8]) -> Option<usize> {
+        let counts = self.counts.borrow();
+        let count = counts
+            .get(&self.prefix(result))
+            .copied()
+            .unwrap_or_default();
+        self.whole_prefix(result).filter(|_| count >= self.limit)
+    }
+
+    /// Whether every prefix has as many results as it can get.
+    pub fn finished(&self) -> bool {
+        self.prefixes
+            .as_ref()
+            .is_some_and(|(_, count)| BigUint::from(self.done.get()) == *count)
+    }
+}
+
+/// Generates in pattern order from `index`, seeking past the rest of a run
+/// of results whose prefix is already full instead of generating them.
+pub struct Seeking<'a> {
+    space: Space,
+    hir: &'a Hir,
+    index: BigUint,
+    base: BigUint,
+    results: Box<dyn Iterator<Item = (usize, Candidate)> + 'a>,
+    per_prefix: &'a PerPrefix,
+    /// How far past `base` the next result is, which seeking moves on.
+    pulled: &'a Cell<u64>,
+}
+
+impl<'a> Seeking<'a> {
+    pub fn new(
+        space: Space,
+        hir: &'a Hir,
+        base: BigUint,
+        per_prefix: &'a PerPrefix,
+        pulled: &'a Cell<u64>,
+    ) -> Self {
+        Self {
+            results: space.iterate_branches_from(hir, &base),
+            space,
+            hir,
+            index: base.clone(),
+            base,
+            per_prefix,
+            pulled,
+        }
+    }
+}
+
+impl Iterator for Seeking<'_> {
+    type Item = (usize, Candidate);
+
+    fn next(&mut self) -> Option<(usize, Candidate)> {
+        loop {
+            let (branch, result) = self.results.next()?;
+            let Some(length) = self.per_prefix.full(&result) else {
+                let offset = &self.index - &self.base;
+                self.pulled.set(u64::try_from(offset).unwrap_or(u64::MAX));
+                self.index += 1u8;
+                return Some((branch, result));
+            };
+            let next = self.space.skip_prefix(&self.index, length);
+            if next > &self.index + 1u8 {
+                self.results = self.space.iterate_branches_from(self.hir, &next);
+                self.index = next;
+            } else {
+                self.index += 1u8;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_per_prefix() {
+    let hir = regex_syntax::Parser::new().parse("a[0-9]|b|c[xy]").unwrap();
+    let per_prefix = PerPrefix::new(&hir, 2, 1, 0, None);
+    assert!(per_prefix.keep(b"a0") && per_prefix.full(b"a1").is_none());
+    assert!(per_prefix.keep(b"a1") && per_prefix.full(b"a2") == Some(1));
+    assert!(!per_prefix.keep(b"a2"));
+    assert!(per_prefix.keep(b"b") && per_prefix.keep(b"cx"));
+    assert!(!per_prefix.finished());
+    assert!(per_prefix.keep(b"cy") && per_prefix.finished());
+}
@@ -0,0 +1,114 @@
+//! `--progress`, a status line on stderr redrawn as results are generated.
+
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+/// Time between redraws of the status line.
+const INTERVAL: Duration = Duration::from_millis(200);
+/// Width of the bar, in characters.
+const WIDTH: usize = 30;
+
+fn clock(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..3600 => format!("{:02}:{:02}", seconds / 60, seconds % 60),
+        _ => format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        ),
+    }
+}
+
+/// The status line after `emitted` results in `elapsed`, with a bar and ETA
+/// when the fraction of the enumeration that has been done is known.
+fn render(emitted: usize, fraction: Option<f64>, elapsed: Duration) -> String {
+    let rate = emitted as f64 / elapsed.as_secs_f64().max(1e-9);
+    let status = format!("{emitted} results, {rate:.0}/s, {} elapsed", clock(elapsed));
+    match fraction {
+        Some(fraction) => {
+            let fraction = fraction.clamp(0.0, 1.0);
+            let filled = (fraction * WIDTH as f64) as usize;
+            let eta = match fraction > 0.0 {
+                true => clock(elapsed.mul_f64((1.0 - fraction) / fraction)),
+                false => "--:--".to_string(),
+            };
+            format!(
+                "[{}{}] {:5.1}% {status}, ETA {eta}",
+                "#".repeat(filled),
+                " ".repeat(WIDTH - filled),
+                fraction * 100.0,
+            )
+        }
+        None => status,
+    }
+}
+
+pub struct Progress {
+    /// Number of results in the enumeration, if it's countable.
+    total: Option<f64>,
+    /// Limit on the number of results written.
+    num: Option<usize>,
+    started: Instant,
+    drawn: Instant,
+    /// Length of the last line drawn, so a shorter one can cover it.
+    width: usize,
+}
+
+impl Progress {
+    pub fn new(total: Option<f64>, num: Option<usize>) -> Self {
+        let now = Instant::now();
+        Self {
+            total,
+            num,
+            started: now,
+            drawn: now,
+            width: 0,
+        }
+    }
+
+    fn draw(&mut self, generated: u64, emitted: usize) {
+        let by_total = self.total.map(|total| generated as f64 / total);
+        let by_num = self.num.map(|num| emitted as f64 / num as f64);
+        let fraction = match (by_total, by_num) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        let line = render(emitted, fraction, self.started.elapsed());
+        let pad = self.width.saturating_sub(line.len());
+        eprint!("\r{line}{}", " ".repeat(pad));
+        let _ = io::stderr().flush();
+        self.width = line.len();
+    }
+
+    /// Redraws the line if it's been long enough since the last time, after
+    /// `generated` results have been pulled and `emitted` of them written.
+    pub fn tick(&mut self, generated: u64, emitted: usize) {
+        if self.drawn.elapsed() >= INTERVAL {
+            self.drawn = Instant::now();
+            self.draw(generated, emitted);
+        }
+    }
+
+    /// Draws the final line and ends it.
+    pub fn finish(&mut self, generated: u64, emitted: usize) {
+        self.draw(generated, emitted);
+        eprintln!();
+    }
+}
+
+#[test]
+fn test_render() {
+    assert_eq!(
+        render(500, None, Duration::from_secs(2)),
+        "500 results, 250/s, 00:02 elapsed"
+    );
+    assert_eq!(
+        render(100, Some(0.25), Duration::from_secs(3700)),
+        "[#######                       ]  25.0% 100 results, 0/s, 1:01:40 elapsed, ETA 3:05:00"
+    );
+    assert!(render(0, Some(0.0), Duration::ZERO).ends_with("ETA --:--"));
+}
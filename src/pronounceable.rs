@@ -0,0 +1,106 @@
+use clap::Args;
+use passwd_gen::template::class;
+
+/// Enumerate pronounceable strings made of consonant-vowel syllables, such
+/// as dorevi. Use --sample to pick some at random
+#[derive(Args)]
+pub struct PronounceableArgs {
+    /// Fewest syllables in a result
+    #[clap(long, value_name = "N", default_value_t = 2)]
+    pub min_syllables: u32,
+
+    /// Most syllables in a result
+    #[clap(long, value_name = "N", default_value_t = 3)]
+    pub max_syllables: u32,
+
+    /// Let syllables end in a consonant too, as in dorvan
+    #[clap(long)]
+    pub closed: bool,
+
+    /// Consonants that start and close syllables
+    #[clap(long, value_name = "LETTERS", default_value = "bdfghjklmnprstvz")]
+    pub consonants: String,
+
+    /// Vowels at the heart of each syllable
+    #[clap(long, value_name = "LETTERS", default_value = "aeiou")]
+    pub vowels: String,
+
+    /// Start with an uppercase letter
+    #[clap(long)]
+    pub capitalize: bool,
+
+    /// Pattern for what comes after the syllables, such as [0-9]{2}
+    #[clap(long, value_name = "PATTERN")]
+    pub suffix: Option<String>,
+
+    /// Only keep results that PATTERN matches the whole of, such as .*[0-9].*
+    /// for a policy requiring a digit
+    #[clap(long, value_name = "PATTERN")]
+    pub matching: Option<String>,
+}
+
+impl PronounceableArgs {
+    /// The syllable pattern, then the --matching pattern if there is one, to
+    /// be intersected with it.
+    pub fn patterns(&self) -> Result<Vec<String>, String> {
+        if self.min_syllables == 0 || self.min_syllables > self.max_syllables {
+            Err("--min-syllables must be at least 1 and at most --max-syllables.")?
+        }
+        if self.consonants.is_empty() || self.vowels.is_empty() {
+            Err("--consonants and --vowels can't be empty.")?
+        }
+        let consonant = class(&self.consonants);
+        let close = match self.closed {
+            true => format!("{consonant}?"),
+            false => String::new(),
+        };
+        let first = match self.capitalize {
+            true => class(&self.consonants.to_uppercase()),
+            false => consonant.clone(),
+        };
+        let vowel = class(&self.vowels);
+        let mut syllables = format!(
+            "{first}{vowel}{close}(?:{consonant}{vowel}{close}){{{},{}}}",
+            self.min_syllables - 1,
+            self.max_syllables - 1
+        );
+        if let Some(suffix) = &self.suffix {
+            syllables += &format!("(?:{suffix})");
+        }
+        Ok([Some(syllables), self.matching.clone()]
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+}
+
+#[test]
+fn test_pronounceable_patterns() {
+    let args = PronounceableArgs {
+        min_syllables: 1,
+        max_syllables: 2,
+        closed: true,
+        consonants: "bd".to_string(),
+        vowels: "a".to_string(),
+        capitalize: true,
+        suffix: Some("[0-9]".to_string()),
+        matching: Some(".*1".to_string()),
+    };
+    assert_eq!(
+        args.patterns().unwrap(),
+        ["[BD][a][bd]?(?:[bd][a][bd]?){0,1}(?:[0-9])", ".*1"]
+    );
+    let hir = regex_syntax::Parser::new()
+        .parse(&args.patterns().unwrap()[0])
+        .unwrap();
+    let results: Vec<_> = passwd_gen::iterate_all(&hir, None, Default::default()).collect();
+    assert!(results.iter().any(|result| &result[..] == b"Dabda7"));
+    assert!(
+        PronounceableArgs {
+            min_syllables: 0,
+            ..args
+        }
+        .patterns()
+        .is_err()
+    );
+}
@@ -0,0 +1,258 @@
+//! The common subset of hashcat and John the Ripper mangling rules.
+
+/// One command of a rule. Positions and counts are given as a single
+/// character, `0`-`9` then `A`-`Z` for 10-35.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Command {
+    Noop,
+    Lower,
+    Upper,
+    Capitalize,
+    InvertCapitalize,
+    ToggleAll,
+    Toggle(usize),
+    Reverse,
+    Duplicate,
+    DuplicateTimes(usize),
+    Reflect,
+    RotateLeft,
+    RotateRight,
+    Append(char),
+    Prepend(char),
+    DeleteFirst,
+    DeleteLast,
+    Delete(usize),
+    Extract(usize, usize),
+    Omit(usize, usize),
+    Insert(usize, char),
+    Overwrite(usize, char),
+    Truncate(usize),
+    Replace(char, char),
+    Purge(char),
+    DuplicateFirst(usize),
+    DuplicateLast(usize),
+    DuplicateAll,
+    SwapFront,
+    SwapBack,
+    Swap(usize, usize),
+    Title,
+    /// Rejects words that aren't shorter than this.
+    RejectUnlessShorter(usize),
+    /// Rejects words that aren't longer than this.
+    RejectUnlessLonger(usize),
+    RejectContains(char),
+    RejectUnlessContains(char),
+}
+
+/// A sequence of commands applied to a word in order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rule(Vec<Command>);
+
+fn position(c: char) -> Option<usize> {
+    match c {
+        '0'..='9' => Some(c as usize - '0' as usize),
+        'A'..='Z' => Some(c as usize - 'A' as usize + 10),
+        _ => None,
+    }
+}
+
+/// Parses one rule, such as `c $1 $2`. Spaces between commands are ignored.
+pub fn parse_rule(line: &str) -> Result<Rule, String> {
+    let mut chars = line.chars();
+    let mut commands = Vec::new();
+    while let Some(name) = chars.next() {
+        let mut char_arg = || {
+            chars
+                .next()
+                .ok_or_else(|| format!("rule {line:?} ends in the middle of {name:?}"))
+        };
+        let mut args = [None; 2];
+        let arity = match name {
+            'T' | 'p' | 'D' | '\'' | 'z' | 'Z' | '<' | '>' | 'i' | 'o' => 1,
+            'x' | 'O' | '*' => 2,
+            _ => 0,
+        };
+        for arg in args.iter_mut().take(arity) {
+            let c = char_arg()?;
+            *arg = Some(position(c).ok_or_else(|| format!("{c:?} isn't a position in {line:?}"))?);
+        }
+        let n = args[0].unwrap_or(0);
+        let m = args[1].unwrap_or(0);
+        commands.push(match name {
+            ' ' => continue,
+            ':' => Command::Noop,
+            'l' => Command::Lower,
+            'u' => Command::Upper,
+            'c' => Command::Capitalize,
+            'C' => Command::InvertCapitalize,
+            't' => Command::ToggleAll,
+            'T' => Command::Toggle(n),
+            'r' => Command::Reverse,
+            'd' => Command::Duplicate,
+            'p' => Command::DuplicateTimes(n),
+            'f' => Command::Reflect,
+            '{' => Command::RotateLeft,
+            '}' => Command::RotateRight,
+            '$' => Command::Append(char_arg()?),
+            '^' => Command::Prepend(char_arg()?),
+            '[' => Command::DeleteFirst,
+            ']' => Command::DeleteLast,
+            'D' => Command::Delete(n),
+            'x' => Command::Extract(n, m),
+            'O' => Command::Omit(n, m),
+            'i' => Command::Insert(n, char_arg()?),
+            'o' => Command::Overwrite(n, char_arg()?),
+            '\'' => Command::Truncate(n),
+            's' => Command::Replace(char_arg()?, char_arg()?),
+            '@' => Command::Purge(char_arg()?),
+            'z' => Command::DuplicateFirst(n),
+            'Z' => Command::DuplicateLast(n),
+            'q' => Command::DuplicateAll,
+            'k' => Command::SwapFront,
+            'K' => Command::SwapBack,
+            '*' => Command::Swap(n, m),
+            'E' => Command::Title,
+            '<' => Command::RejectUnlessShorter(n),
+            '>' => Command::RejectUnlessLonger(n),
+            '!' => Command::RejectContains(char_arg()?),
+            '/' => Command::RejectUnlessContains(char_arg()?),
+            name => Err(format!("unknown rule command {name:?} in {line:?}"))?,
+        });
+    }
+    Ok(Rule(commands))
+}
+
+/// Parses a rule file: one rule per line, skipping blank lines and lines
+/// starting with `#`.
+pub fn parse_rules(contents: &str) -> Result<Vec<Rule>, String> {
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| {
+            parse_rule(line).map_err(|error| format!("line {}: {error}", number + 1))
+        })
+        .collect()
+}
+
+fn toggle(c: char) -> char {
+    if c.is_lowercase() {
+        c.to_uppercase().next().unwrap_or(c)
+    } else {
+        c.to_lowercase().next().unwrap_or(c)
+    }
+}
+
+fn lower(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn upper(c: char) -> char {
+    c.to_uppercase().next().unwrap_or(c)
+}
+
+impl Rule {
+    /// Applies the rule to `word`, or returns `None` if one of its commands
+    /// rejects it. Commands given out-of-range positions leave the word as it
+    /// is, as hashcat does.
+    pub fn apply(&self, word: &str) -> Option<String> {
+        let mut w: Vec<char> = word.chars().collect();
+        for &command in &self.0 {
+            let len = w.len();
+            match command {
+                Command::Noop => {}
+                Command::Lower => w.iter_mut().for_each(|c| *c = lower(*c)),
+                Command::Upper => w.iter_mut().for_each(|c| *c = upper(*c)),
+                Command::Capitalize | Command::InvertCapitalize => {
+                    let capitalize = command == Command::Capitalize;
+                    for (i, c) in w.iter_mut().enumerate() {
+                        *c = if (i == 0) == capitalize {
+                            upper(*c)
+                        } else {
+                            lower(*c)
+                        };
+                    }
+                }
+                Command::ToggleAll => w.iter_mut().for_each(|c| *c = toggle(*c)),
+                Command::Toggle(n) => {
+                    if let Some(c) = w.get_mut(n) {
+                        *c = toggle(*c);
+                    }
+                }
+                Command::Reverse => w.reverse(),
+                Command::Duplicate => w.extend_from_within(..),
+                Command::DuplicateTimes(n) => w = w.repeat(n + 1),
+                Command::Reflect => w.extend(w.clone().into_iter().rev()),
+                Command::RotateLeft if len > 0 => w.rotate_left(1),
+                Command::RotateRight if len > 0 => w.rotate_right(1),
+                Command::Append(c) => w.push(c),
+                Command::Prepend(c) => w.insert(0, c),
+                Command::DeleteFirst if len > 0 => {
+                    w.remove(0);
+                }
+                Command::DeleteLast => {
+                    w.pop();
+                }
+                Command::Delete(n) if n < len => {
+                    w.remove(n);
+                }
+                Command::Extract(n, m) if n + m <= len => w = w[n..n + m].to_vec(),
+                Command::Omit(n, m) if n + m <= len => {
+                    w.drain(n..n + m);
+                }
+                Command::Insert(n, c) if n <= len => w.insert(n, c),
+                Command::Overwrite(n, c) if n < len => w[n] = c,
+                Command::Truncate(n) => w.truncate(n),
+                Command::Replace(from, to) => {
+                    w.iter_mut().filter(|c| **c == from).for_each(|c| *c = to)
+                }
+                Command::Purge(c) => w.retain(|&x| x != c),
+                Command::DuplicateFirst(n) if len > 0 => {
+                    w.splice(0..0, std::iter::repeat_n(w[0], n));
+                }
+                Command::DuplicateLast(n) if len > 0 => {
+                    w.extend(std::iter::repeat_n(w[len - 1], n));
+                }
+                Command::DuplicateAll => w = w.iter().flat_map(|&c| [c, c]).collect(),
+                Command::SwapFront if len > 1 => w.swap(0, 1),
+                Command::SwapBack if len > 1 => w.swap(len - 1, len - 2),
+                Command::Swap(n, m) if n < len && m < len => w.swap(n, m),
+                Command::Title => {
+                    let mut start = true;
+                    for c in &mut w {
+                        *c = if start { upper(*c) } else { lower(*c) };
+                        start = *c == ' ';
+                    }
+                }
+                Command::RejectUnlessShorter(n) if len >= n => return None,
+                Command::RejectUnlessLonger(n) if len <= n => return None,
+                Command::RejectContains(c) if w.contains(&c) => return None,
+                Command::RejectUnlessContains(c) if !w.contains(&c) => return None,
+                _ => {}
+            }
+        }
+        Some(w.into_iter().collect())
+    }
+}
+
+#[test]
+fn test_rules() {
+    let apply = |rule: &str, word: &str| parse_rule(rule).unwrap().apply(word);
+    assert_eq!(apply(":", "pass").as_deref(), Some("pass"));
+    assert_eq!(apply("c $1 $!", "pASS").as_deref(), Some("Pass1!"));
+    assert_eq!(apply("u r", "abc").as_deref(), Some("CBA"));
+    assert_eq!(apply("sa@ so0", "password").as_deref(), Some("p@ssw0rd"));
+    assert_eq!(apply("d ]", "ab").as_deref(), Some("aba"));
+    assert_eq!(apply("^x T1 D9", "ab").as_deref(), Some("xAb"));
+    assert_eq!(apply("x13 i0-", "abcdef").as_deref(), Some("-bcd"));
+    assert_eq!(apply("E", "hello wORLD").as_deref(), Some("Hello World"));
+    assert_eq!(apply("f {", "ab").as_deref(), Some("bbaa"));
+    assert_eq!(apply(">5", "abc"), None);
+    assert_eq!(apply("/a", "xyz"), None);
+    assert!(parse_rule("$").is_err());
+    assert!(parse_rule("w").is_err());
+    assert!(parse_rule("Tz").is_err());
+    let rules = parse_rules("# comment\n:\n\nu\n").unwrap();
+    assert_eq!(rules.len(), 2);
+    assert!(parse_rules(":\n?").unwrap_err().starts_with("line 2:"));
+}
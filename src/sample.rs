@@ -0,0 +1,139 @@
+use num_bigint::BigUint;
+use rand::{
+    Rng, RngExt, SeedableRng,
+    rand_core::UnwrapErr,
+    rngs::{ChaCha20Rng, SysRng},
+};
+use sha2::{Digest, Sha256};
+
+/// A generator seeded from `seed` if given, or else from the OS. ChaCha20
+/// keyed by the SHA-256 of the seed is fixed by their specifications, so a
+/// seed draws the same results on every machine, unlike `StdRng`, whose
+/// algorithm may change between versions of `rand`.
+pub fn seeded_rng(seed: Option<&str>) -> ChaCha20Rng {
+    match seed {
+        Some(seed) => ChaCha20Rng::from_seed(Sha256::digest(seed).into()),
+        None => rand::make_rng(),
+    }
+}
+
+/// The operating system's secure random number generator, read afresh for
+/// every draw. Panics if it fails, which it shouldn't once the OS has seeded it.
+pub fn os_rng() -> UnwrapErr<SysRng> {
+    UnwrapErr(SysRng)
+}
+
+/// Draws `n` items uniformly from `iter` in a single pass (Algorithm R),
+/// returned in the order they were produced. Every item the iterator yields
+/// has the same chance of being kept; nothing it does not yield is considered.
+pub fn reservoir<T>(iter: impl Iterator<Item = T>, n: usize, rng: &mut impl Rng) -> Vec<T> {
+    let mut kept: Vec<(usize, T)> = Vec::with_capacity(n);
+    for (i, item) in iter.enumerate() {
+        if i < n {
+            kept.push((i, item));
+        } else {
+            let j = rng.random_range(0..=i);
+            if j < n {
+                kept[j] = (i, item);
+            }
+        }
+    }
+    kept.sort_by_key(|&(i, _)| i);
+    kept.into_iter().map(|(_, item)| item).collect()
+}
+
+/// A random bijection on `0..total`: a Feistel network over enough bits,
+/// walking the cycle from any output past `total` until it lands inside.
+/// It maps each index in constant memory however many there are.
+pub struct Permutation {
+    total: BigUint,
+    half_bits: u64,
+    keys: [[u8; 32]; 6],
+}
+
+impl Permutation {
+    pub fn new(total: BigUint, rng: &mut impl Rng) -> Self {
+        let half_bits = total.bits().div_ceil(2).max(1);
+        let mut keys = [[0; 32]; 6];
+        for key in &mut keys {
+            rng.fill_bytes(key);
+        }
+        Self {
+            total,
+            half_bits,
+            keys,
+        }
+    }
+
+    /// The round function: `half_bits` bits of SHA-256 hashes of the key and
+    /// the right half.
+    fn round(&self, key: &[u8; 32], right: &BigUint) -> BigUint {
+        let blocks = self.half_bits.div_ceil(256);
+        let mut bytes = Vec::new();
+        for block in 0..blocks {
+            let mut hasher = Sha256::new();
+            hasher.update(key);
+            hasher.update(block.to_le_bytes());
+            hasher.update(right.to_bytes_le());
+            bytes.extend(hasher.finalize());
+        }
+        BigUint::from_bytes_le(&bytes) & self.mask()
+    }
+
+    fn mask(&self) -> BigUint {
+        (BigUint::from(1u8) << self.half_bits) - 1u8
+    }
+
+    /// Where `index`, which must be below the total, is sent.
+    pub fn get(&self, index: &BigUint) -> BigUint {
+        let mut x = index.clone();
+        loop {
+            let mut left = &x >> self.half_bits;
+            let mut right = &x & self.mask();
+            for key in &self.keys {
+                let next = left ^ self.round(key, &right);
+                left = right;
+                right = next;
+            }
+            x = (left << self.half_bits) | right;
+            if x < self.total {
+                return x;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_permutation() {
+    let mut rng = seeded_rng(Some("7"));
+    for total in [0u32, 1, 2, 1000] {
+        let permutation = Permutation::new(BigUint::from(total), &mut rng);
+        let mut seen: Vec<_> = (0..total)
+            .map(|i| permutation.get(&BigUint::from(i)))
+            .collect();
+        let shuffled = seen.clone();
+        seen.sort();
+        assert_eq!(seen, (0..total).map(BigUint::from).collect::<Vec<_>>());
+        if total == 1000 {
+            assert_ne!(shuffled, seen);
+        }
+    }
+}
+
+#[test]
+fn test_reservoir() {
+    let mut rng = seeded_rng(Some("7"));
+    let sample = reservoir(0..1000, 10, &mut rng);
+    assert_eq!(sample.len(), 10);
+    assert!(sample.is_sorted());
+    assert_eq!(sample, reservoir(0..1000, 10, &mut seeded_rng(Some("7"))));
+    assert_eq!(reservoir(0..3, 10, &mut rng), [0, 1, 2]);
+
+    let mut hits = [0usize; 10];
+    for _ in 0..2000 {
+        for i in reservoir(0..10, 3, &mut rng) {
+            hits[i] += 1;
+        }
+    }
+    assert!(hits.iter().all(|&hit| (500..700).contains(&hit)));
+}
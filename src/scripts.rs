@@ -0,0 +1,105 @@
+//! `--scripts` and `--blocks`, which limit classes to parts of Unicode.
+
+use regex_syntax::hir::{Class, ClassUnicode, ClassUnicodeRange, HirKind};
+
+/// Blocks that come up in passwords, from the Unicode Character Database's
+/// Blocks.txt. `regex-syntax` knows scripts but not blocks.
+const BLOCKS: &[(&str, char, char)] = &[
+    ("Basic Latin", '\u{0000}', '\u{007F}'),
+    ("Latin-1 Supplement", '\u{0080}', '\u{00FF}'),
+    ("Latin Extended-A", '\u{0100}', '\u{017F}'),
+    ("Latin Extended-B", '\u{0180}', '\u{024F}'),
+    ("IPA Extensions", '\u{0250}', '\u{02AF}'),
+    ("Greek and Coptic", '\u{0370}', '\u{03FF}'),
+    ("Cyrillic", '\u{0400}', '\u{04FF}'),
+    ("Armenian", '\u{0530}', '\u{058F}'),
+    ("Hebrew", '\u{0590}', '\u{05FF}'),
+    ("Arabic", '\u{0600}', '\u{06FF}'),
+    ("Devanagari", '\u{0900}', '\u{097F}'),
+    ("Thai", '\u{0E00}', '\u{0E7F}'),
+    ("Georgian", '\u{10A0}', '\u{10FF}'),
+    ("Hangul Jamo", '\u{1100}', '\u{11FF}'),
+    ("Latin Extended Additional", '\u{1E00}', '\u{1EFF}'),
+    ("Greek Extended", '\u{1F00}', '\u{1FFF}'),
+    ("General Punctuation", '\u{2000}', '\u{206F}'),
+    ("Currency Symbols", '\u{20A0}', '\u{20CF}'),
+    ("Letterlike Symbols", '\u{2100}', '\u{214F}'),
+    ("Arrows", '\u{2190}', '\u{21FF}'),
+    ("Mathematical Operators", '\u{2200}', '\u{22FF}'),
+    ("Box Drawing", '\u{2500}', '\u{257F}'),
+    ("CJK Symbols and Punctuation", '\u{3000}', '\u{303F}'),
+    ("Hiragana", '\u{3040}', '\u{309F}'),
+    ("Katakana", '\u{30A0}', '\u{30FF}'),
+    ("CJK Unified Ideographs", '\u{4E00}', '\u{9FFF}'),
+    ("Hangul Syllables", '\u{AC00}', '\u{D7AF}'),
+    ("Halfwidth and Fullwidth Forms", '\u{FF00}', '\u{FFEF}'),
+    ("Emoticons", '\u{1F600}', '\u{1F64F}'),
+];
+
+/// Block names compare ignoring case, spaces, hyphens and underscores, as
+/// Unicode's loose matching does.
+fn loose(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '_'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Parses the name of a script such as `Latin` or `Cyrl` for clap.
+pub fn parse_script(name: &str) -> Result<ClassUnicode, String> {
+    let hir = regex_syntax::Parser::new()
+        .parse(&format!(r"\p{{Script={name}}}"))
+        .map_err(|_| format!("unknown script {name:?}"))?;
+    match hir.kind() {
+        HirKind::Class(Class::Unicode(class)) => Ok(class.clone()),
+        _ => Err(format!("unknown script {name:?}")),
+    }
+}
+
+/// Parses the name of a block such as `Basic Latin` or `cyrillic` for clap.
+pub fn parse_block(name: &str) -> Result<ClassUnicode, String> {
+    let (_, start, end) = BLOCKS
+        .iter()
+        .find(|(block, _, _)| loose(block) == loose(name))
+        .ok_or_else(|| {
+            let names: Vec<_> = BLOCKS.iter().map(|(block, _, _)| *block).collect();
+            format!(
+                "unknown block {name:?}, expected one of: {}",
+                names.join(", ")
+            )
+        })?;
+    Ok(ClassUnicode::new([ClassUnicodeRange::new(*start, *end)]))
+}
+
+/// The characters of any of `scripts` or `blocks`, or `None` if neither
+/// limits anything.
+pub fn allowed(scripts: &[ClassUnicode], blocks: &[ClassUnicode]) -> Option<ClassUnicode> {
+    scripts
+        .iter()
+        .chain(blocks)
+        .cloned()
+        .reduce(|mut all, class| {
+            all.union(&class);
+            all
+        })
+}
+
+#[test]
+fn test_scripts() {
+    let latin = parse_script("Latin").unwrap();
+    let contains = |class: &ClassUnicode, c: char| {
+        class
+            .ranges()
+            .iter()
+            .any(|range| (range.start()..=range.end()).contains(&c))
+    };
+    assert!(contains(&latin, 'é') && !contains(&latin, 'ж') && !contains(&latin, '1'));
+    assert!(parse_script("Klingon").is_err());
+    let cyrillic = parse_block("cyrillic").unwrap();
+    let ascii = parse_block("basic_latin").unwrap();
+    assert!(contains(&cyrillic, 'ж') && contains(&ascii, '1'));
+    assert!(parse_block("Latin").is_err());
+    let both = allowed(&[latin], &[cyrillic]).unwrap();
+    assert!(contains(&both, 'é') && contains(&both, 'ж'));
+    assert!(allowed(&[], &[]).is_none());
+}
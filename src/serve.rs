@@ -0,0 +1,258 @@
+//! The `serve` subcommand: a minimal HTTP server whose `GET /generate` streams
+//! a slice of a pattern's results, so workers can pull parts of a keyspace.
+
+use std::{
+    error::Error,
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+};
+
+use clap::Args;
+use num_bigint::BigUint;
+use passwd_gen::{Candidate, error::GenError};
+
+use crate::{Compiled, PatternArgs, compile, format};
+
+/// Serve `GET /generate?pattern=...&skip=...&limit=...` over HTTP, streaming
+/// results as chunked plain text, or JSON Lines with format=jsonl. min_length
+/// and max_length are accepted too
+#[derive(Args)]
+pub struct ServeArgs {
+    /// Address to listen on
+    #[clap(long, value_name = "ADDR", default_value = "127.0.0.1:8080")]
+    pub listen: String,
+}
+
+/// Bytes of results sent in each chunk of a response.
+const CHUNK: usize = 1 << 16;
+
+fn percent_decode(s: &str) -> Result<String, String> {
+    let mut bytes = Vec::new();
+    let mut rest = s.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        rest = tail;
+        bytes.push(match b {
+            b'+' => b' ',
+            b'%' => {
+                let byte = rest
+                    .get(..2)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| format!("bad percent escape in {s:?}"))?;
+                rest = &rest[2..];
+                byte
+            }
+            b => b,
+        });
+    }
+    String::from_utf8(bytes).map_err(|_| format!("{s:?} doesn't decode to UTF-8"))
+}
+
+struct Query {
+    args: PatternArgs,
+    compiled: Compiled,
+    skip: BigUint,
+    limit: Option<u64>,
+    jsonl: bool,
+}
+
+fn parse_query(query: &str) -> Result<Query, String> {
+    let mut pattern = None;
+    let (mut skip, mut limit, mut jsonl) = (BigUint::default(), None, false);
+    let (mut min_length, mut max_length) = (0, None);
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = percent_decode(value)?;
+        let number = |value: &str| {
+            value
+                .parse::<u64>()
+                .map_err(|error| format!("{key}={value:?}: {error}"))
+        };
+        match key {
+            "pattern" => pattern = Some(value),
+            "skip" => {
+                skip = value
+                    .parse()
+                    .map_err(|error| format!("skip={value:?}: {error}"))?
+            }
+            "limit" => limit = Some(number(&value)?),
+            "min_length" => min_length = number(&value)? as usize,
+            "max_length" => max_length = Some(number(&value)? as usize),
+            "format" => {
+                jsonl = match value.as_str() {
+                    "plain" => false,
+                    "jsonl" => true,
+                    _ => Err(format!("format={value:?} must be plain or jsonl"))?,
+                }
+            }
+            key => Err(format!("unknown parameter {key:?}"))?,
+        }
+    }
+    let pattern = pattern.ok_or("missing the pattern parameter")?;
+    if pattern == "-" {
+        Err("pattern=- can't read the pattern from stdin")?
+    }
+    let mut args = PatternArgs::new(&pattern);
+    (args.min_length, args.max_length) = (min_length, max_length);
+    let compiled = compile(&mut args, None, false).map_err(|error| error.to_string())?;
+    Ok(Query {
+        args,
+        compiled,
+        skip,
+        limit,
+        jsonl,
+    })
+}
+
+fn respond(out: &mut impl Write, status: &str, body: &str) -> io::Result<()> {
+    write!(
+        out,
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+fn write_chunk(out: &mut impl Write, chunk: &mut Vec<u8>) -> io::Result<()> {
+    if !chunk.is_empty() {
+        write!(out, "{:x}\r\n", chunk.len())?;
+        out.write_all(chunk)?;
+        out.write_all(b"\r\n")?;
+        chunk.clear();
+    }
+    Ok(())
+}
+
+/// Answers the request for `target`, such as `/generate?pattern=a`.
+fn handle(target: &str, out: &mut impl Write) -> io::Result<()> {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    if path != "/generate" {
+        return respond(out, "404 Not Found", "only /generate is served\n");
+    }
+    let query = match parse_query(query) {
+        Ok(query) => query,
+        Err(error) => return respond(out, "400 Bad Request", &format!("{error}\n")),
+    };
+    let pattern = &query.compiled.pattern;
+    let results: Box<dyn Iterator<Item = Candidate>> = match (&query.compiled, pattern.space()) {
+        (Compiled { backrefs: None, .. }, Ok(space)) => {
+            space.iterate_from(pattern.hir(), &query.skip)
+        }
+        // Without a max length there are no counts to seek with, and
+        // backreferences are substituted as results are generated.
+        (Compiled { backrefs, .. }, Ok(_) | Err(GenError::Unbounded)) => {
+            let Ok(skip) = usize::try_from(&query.skip) else {
+                return respond(out, "400 Bad Request", "skip is too large\n");
+            };
+            match backrefs {
+                Some(backrefs) => Box::new(backrefs.iter().skip(skip)),
+                None => Box::new(pattern.bytes().skip(skip).map(Candidate::from_vec)),
+            }
+        }
+        (_, Err(error)) => return respond(out, "400 Bad Request", &format!("{error}\n")),
+    };
+    let (mode, max_length) = (query.args.mode, query.args.max_length);
+    let results = (0u64..)
+        .zip(results)
+        .filter(|(_, result)| query.compiled.keeps(mode, max_length, result));
+    let content_type = match query.jsonl {
+        true => "application/jsonl",
+        false => "text/plain",
+    };
+    write!(
+        out,
+        "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n"
+    )?;
+    let (mut chunk, mut line) = (Vec::new(), Vec::new());
+    let limit = query.limit.unwrap_or(u64::MAX);
+    for (offset, result) in results.take(usize::try_from(limit).unwrap_or(usize::MAX)) {
+        if query.jsonl {
+            format::jsonl(&result, &query.skip + offset, &mut line);
+            chunk.extend_from_slice(&line);
+        } else {
+            chunk.extend_from_slice(&result);
+        }
+        chunk.push(b'\n');
+        if chunk.len() >= CHUNK {
+            write_chunk(out, &mut chunk)?;
+        }
+    }
+    write_chunk(out, &mut chunk)?;
+    out.write_all(b"0\r\n\r\n")
+}
+
+fn connection(stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // The headers don't change the response, but are read so the client
+    // doesn't see the connection reset.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let mut out = io::BufWriter::new(stream);
+    match request.split_whitespace().collect::<Vec<_>>()[..] {
+        ["GET", target, _] => handle(target, &mut out)?,
+        [_, _, _] => respond(&mut out, "405 Method Not Allowed", "only GET is served\n")?,
+        _ => respond(&mut out, "400 Bad Request", "malformed request\n")?,
+    }
+    out.flush()
+}
+
+pub fn serve(args: &ServeArgs) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind(&args.listen)?;
+    eprintln!("listening on http://{}", listener.local_addr()?);
+    for stream in listener.incoming() {
+        let stream = stream?;
+        thread::spawn(move || {
+            // A client that disconnects early ends its own stream only.
+            let _ = connection(stream);
+        });
+    }
+    Ok(())
+}
+
+#[test]
+fn test_handle() {
+    let get = |target: &str| {
+        let mut out = Vec::new();
+        handle(target, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    let body = |response: String| response.split_once("\r\n\r\n").unwrap().1.to_string();
+    assert_eq!(
+        body(get("/generate?pattern=%5Ba-e%5D&skip=1&limit=3")),
+        "6\r\nb\nc\nd\n\r\n0\r\n\r\n"
+    );
+    assert_eq!(
+        body(get(
+            "/generate?pattern=a%2B&max_length=2&skip=1&format=jsonl"
+        )),
+        "2a\r\n{\"index\": 1, \"candidate\": \"aa\", \"len\": 2}\n\r\n0\r\n\r\n"
+    );
+    assert_eq!(
+        body(get("/generate?pattern=a*&skip=2&limit=1")),
+        "3\r\naa\n\r\n0\r\n\r\n"
+    );
+    // Patterns are compiled as on the command line.
+    assert_eq!(
+        body(get("/generate?pattern=%5Ea%5Bbc%5D%24")),
+        "6\r\nab\nac\n\r\n0\r\n\r\n"
+    );
+    assert_eq!(
+        body(get("/generate?pattern=x(%3FN%3A8-10)&skip=1")),
+        "7\r\nx9\nx10\n\r\n0\r\n\r\n"
+    );
+    assert_eq!(
+        body(get("/generate?pattern=(a%7Cb)%5C1&skip=1&format=jsonl")),
+        "2a\r\n{\"index\": 1, \"candidate\": \"bb\", \"len\": 2}\n\r\n0\r\n\r\n"
+    );
+    assert_eq!(
+        body(get("/generate?pattern=(%3F%3Aa%5E%7C)b&format=jsonl")),
+        "29\r\n{\"index\": 1, \"candidate\": \"b\", \"len\": 1}\n\r\n0\r\n\r\n"
+    );
+    assert!(get("/generate?pattern=(").starts_with("HTTP/1.1 400"));
+    assert!(get("/generate?skip=1").starts_with("HTTP/1.1 400"));
+    assert!(get("/other").starts_with("HTTP/1.1 404"));
+}
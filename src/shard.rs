@@ -0,0 +1,50 @@
+use num_bigint::BigUint;
+
+/// One of `count` equal slices of the enumeration, numbered from 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shard {
+    pub index: u32,
+    pub count: u32,
+}
+
+impl Shard {
+    /// The half-open range of candidate indices this shard covers, out of
+    /// `total`. The shards of a split cover every index exactly once.
+    pub fn range(&self, total: &BigUint) -> (BigUint, BigUint) {
+        let bound = |i: u32| total * i / self.count;
+        (bound(self.index - 1), bound(self.index))
+    }
+}
+
+/// Parses `I/N` for clap.
+pub fn parse_shard(s: &str) -> Result<Shard, String> {
+    let (index, count) = s
+        .split_once('/')
+        .ok_or_else(|| format!("expected I/N, got {s:?}"))?;
+    let index: u32 = index
+        .parse()
+        .map_err(|_| format!("invalid shard {index:?}"))?;
+    let count: u32 = count
+        .parse()
+        .map_err(|_| format!("invalid shard count {count:?}"))?;
+    if index == 0 || index > count {
+        return Err(format!("shard must be between 1 and {count}"));
+    }
+    Ok(Shard { index, count })
+}
+
+#[test]
+fn test_shard() {
+    assert_eq!(parse_shard("2/3"), Ok(Shard { index: 2, count: 3 }));
+    assert!(parse_shard("0/3").is_err());
+    assert!(parse_shard("4/3").is_err());
+    assert!(parse_shard("3").is_err());
+    let total = BigUint::from(10u32);
+    let ranges: Vec<_> = (1..=3)
+        .map(|index| Shard { index, count: 3 }.range(&total))
+        .collect();
+    assert_eq!(
+        ranges,
+        [(0u32, 3u32), (3, 6), (6, 10)].map(|(a, b)| (BigUint::from(a), BigUint::from(b)))
+    );
+}
@@ -0,0 +1,507 @@
+//! Sorted enumeration, done by walking a DFA of the pattern rather than its
+//! syntax tree, so that each distinct result comes out once and in order.
+
+use std::collections::HashMap;
+
+use num_bigint::BigUint;
+use num_traits::Zero;
+use regex_automata::{
+    Anchored, Input, MatchKind,
+    dfa::{Automaton as _, StartKind, dense},
+    nfa::thompson,
+};
+use regex_syntax::hir::Hir;
+
+use crate::{Candidate, ClassOrder, error::GenError};
+
+/// A pattern's DFA, trimmed to the states that can still reach a match.
+pub(crate) struct Automaton {
+    start: usize,
+    /// The transitions out of each state that don't lead to the dead state,
+    /// in byte order.
+    edges: Vec<Vec<(u8, usize)>>,
+    /// `reach[k][state]`: whether `state` can reach a match in exactly `k`
+    /// more bytes. Grown a layer at a time as longer results are needed.
+    reach: Vec<Vec<bool>>,
+}
+
+impl Automaton {
+    pub(crate) fn new(hir: &Hir) -> Result<Self, GenError> {
+        let unsupported = |error: &dyn std::error::Error| GenError::Unsorted(error.to_string());
+        let nfa = thompson::Compiler::new()
+            .build_from_hir(hir)
+            .map_err(|error| unsupported(&error))?;
+        let dfa = dense::Builder::new()
+            .configure(
+                dense::Config::new()
+                    .match_kind(MatchKind::All)
+                    .start_kind(StartKind::Anchored),
+            )
+            .build_from_nfa(&nfa)
+            .map_err(|error| unsupported(&error))?;
+        let start = dfa
+            .start_state_forward(&Input::new("").anchored(Anchored::Yes))
+            .map_err(|error| unsupported(&error))?;
+        let mut ids = HashMap::from([(start, 0)]);
+        let mut states = vec![start];
+        let mut edges = Vec::new();
+        let mut accepting = Vec::new();
+        while let Some(&state) = states.get(edges.len()) {
+            accepting.push(dfa.is_match_state(dfa.next_eoi_state(state)));
+            let mut out = Vec::new();
+            for byte in 0..=u8::MAX {
+                let next = dfa.next_state(state, byte);
+                if dfa.is_dead_state(next) {
+                    continue;
+                }
+                let id = *ids.entry(next).or_insert_with(|| {
+                    states.push(next);
+                    states.len() - 1
+                });
+                out.push((byte, id));
+            }
+            edges.push(out);
+        }
+        Ok(Self {
+            start: 0,
+            edges,
+            reach: vec![accepting],
+        })
+    }
+
+    fn grow_to(&mut self, k: usize) {
+        while self.reach.len() <= k {
+            let last = self.reach.last().unwrap();
+            let layer = self
+                .edges
+                .iter()
+                .map(|edges| edges.iter().any(|&(_, next)| last[next]))
+                .collect();
+            self.reach.push(layer);
+        }
+    }
+
+    /// `layers[k][state]`: the number of distinct strings of `k` bytes that
+    /// lead from `state` to a match, for each `k` up to `max_length`.
+    fn layers(&self, max_length: usize) -> Vec<Vec<BigUint>> {
+        let mut layers: Vec<Vec<BigUint>> =
+            vec![self.reach[0].iter().map(|&a| u8::from(a).into()).collect()];
+        for _ in 0..max_length {
+            let last = layers.last().unwrap();
+            let layer = self
+                .edges
+                .iter()
+                .map(|edges| edges.iter().map(|&(_, next)| &last[next]).sum())
+                .collect();
+            layers.push(layer);
+        }
+        layers
+    }
+
+    /// The number of distinct strings of each length up to `max_length`.
+    pub(crate) fn counts(&self, max_length: usize) -> Vec<BigUint> {
+        self.layers(max_length)
+            .into_iter()
+            .map(|layer| layer[self.start].clone())
+            .collect()
+    }
+}
+
+/// The distinct prefixes of the results of a pattern, counted in
+/// characters, with the distinct results that start with each.
+pub struct Prefixes {
+    automaton: Automaton,
+    chars: usize,
+    min_length: usize,
+    layers: Vec<Vec<BigUint>>,
+}
+
+impl Prefixes {
+    /// Fails if the pattern is unbounded and there is no max length.
+    pub fn new(
+        hir: &Hir,
+        chars: usize,
+        min_length: usize,
+        max_length: Option<usize>,
+    ) -> Result<Self, GenError> {
+        let max_length = max_length
+            .or(hir.properties().maximum_len())
+            .ok_or(GenError::Unbounded)?;
+        let automaton = Automaton::new(hir)?;
+        let layers = automaton.layers(max_length);
+        Ok(Self {
+            automaton,
+            chars,
+            min_length,
+            layers,
+        })
+    }
+
+    /// The distinct results between the length limits that finish from
+    /// `state`, reached after `depth` bytes.
+    fn finishing(&self, state: usize, depth: usize) -> BigUint {
+        let max_length = self.layers.len() - 1;
+        match depth <= max_length {
+            true => (self.min_length.saturating_sub(depth)..=max_length - depth)
+                .map(|k| &self.layers[k][state])
+                .sum(),
+            false => BigUint::ZERO,
+        }
+    }
+
+    /// The number of distinct results that start with `prefix`.
+    pub fn completions(&self, prefix: &[u8]) -> BigUint {
+        let mut state = self.automaton.start;
+        for byte in prefix {
+            let edges = &self.automaton.edges[state];
+            match edges.binary_search_by_key(byte, |&(byte, _)| byte) {
+                Ok(i) => state = edges[i].1,
+                Err(_) => return BigUint::ZERO,
+            }
+        }
+        self.finishing(state, prefix.len())
+    }
+
+    /// The number of distinct prefixes of `chars` characters, a result
+    /// shorter than that being a prefix of its own.
+    pub fn count(&self) -> BigUint {
+        self.count_from(self.automaton.start, 0, self.chars, &mut HashMap::new())
+    }
+
+    fn count_from(
+        &self,
+        state: usize,
+        depth: usize,
+        left: usize,
+        memo: &mut HashMap<(usize, usize, usize), BigUint>,
+    ) -> BigUint {
+        if left == 0 {
+            return u8::from(!self.finishing(state, depth).is_zero()).into();
+        }
+        if let Some(count) = memo.get(&(state, depth, left)) {
+            return count.clone();
+        }
+        let ends = depth >= self.min_length && self.automaton.reach[0][state];
+        let mut count = BigUint::from(u8::from(ends));
+        for &(byte, next) in &self.automaton.edges[state] {
+            let width = match byte {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            let mut states = vec![next];
+            for _ in 1..width {
+                states = states
+                    .iter()
+                    .flat_map(|&state| &self.automaton.edges[state])
+                    .filter(|&&(byte, _)| (0x80..0xc0).contains(&byte))
+                    .map(|&(_, next)| next)
+                    .collect();
+            }
+            for state in states {
+                if !self.finishing(state, depth + width).is_zero() {
+                    count += self.count_from(state, depth + width, left - 1, memo);
+                }
+            }
+        }
+        memo.insert((state, depth, left), count.clone());
+        count
+    }
+}
+
+enum Mode {
+    /// Walking the results of `length` bytes, one length after another.
+    Shortlex { length: usize },
+    /// Walking every length at once, each prefix before its extensions.
+    Lex { started: bool },
+}
+
+/// Yields each distinct result of a pattern once, in byte order, which for
+/// UTF-8 is also codepoint order.
+pub struct Sorted {
+    automaton: Automaton,
+    mode: Mode,
+    class_order: ClassOrder,
+    min_length: usize,
+    max_length: Option<usize>,
+    /// The states along the current prefix, with the index of the next edge
+    /// to try out of each.
+    stack: Vec<(usize, usize)>,
+    prefix: Vec<u8>,
+}
+
+impl Sorted {
+    fn new(
+        hir: &Hir,
+        mode: Mode,
+        min_length: usize,
+        max_length: Option<usize>,
+        class_order: ClassOrder,
+    ) -> Result<Self, GenError> {
+        Ok(Self {
+            automaton: Automaton::new(hir)?,
+            mode,
+            class_order,
+            min_length,
+            max_length: max_length.or(hir.properties().maximum_len()),
+            stack: Vec::new(),
+            prefix: Vec::new(),
+        })
+    }
+
+    /// Results between `min_length` and `max_length` bytes, shortest first,
+    /// in ascending order or in descending order within each length.
+    pub fn shortlex(
+        hir: &Hir,
+        min_length: usize,
+        max_length: Option<usize>,
+        class_order: ClassOrder,
+    ) -> Result<Self, GenError> {
+        let mode = Mode::Shortlex { length: min_length };
+        Self::new(hir, mode, min_length, max_length, class_order)
+    }
+
+    /// Results between `min_length` and `max_length` bytes in ascending or
+    /// descending lexicographic order. Fails if the pattern is unbounded and
+    /// there is no max length, since there'd be no first result.
+    pub fn lex(
+        hir: &Hir,
+        min_length: usize,
+        max_length: Option<usize>,
+        class_order: ClassOrder,
+    ) -> Result<Self, GenError> {
+        let mode = Mode::Lex { started: false };
+        let mut sorted = Self::new(hir, mode, min_length, max_length, class_order)?;
+        let max_length = sorted.max_length.ok_or(GenError::Unbounded)?;
+        sorted.automaton.grow_to(max_length);
+        Ok(sorted)
+    }
+
+    /// Whether `state`, reached after `depth` bytes, can still finish a
+    /// result that is wanted.
+    fn viable(&self, state: usize, depth: usize) -> bool {
+        let reach = &self.automaton.reach;
+        match self.mode {
+            Mode::Shortlex { length } => reach[length - depth][state],
+            Mode::Lex { .. } => match self.max_length {
+                Some(max_length) if depth <= max_length => (self.min_length.saturating_sub(depth)
+                    ..=max_length - depth)
+                    .any(|k| reach[k][state]),
+                _ => false,
+            },
+        }
+    }
+
+    /// Whether the current prefix is itself a result.
+    fn accepts(&self) -> bool {
+        let &(state, _) = self.stack.last().unwrap();
+        self.prefix.len() >= self.min_length && self.automaton.reach[0][state]
+    }
+
+    /// The next edge out of the top of the stack that can still finish a
+    /// wanted result.
+    fn next_edge(&mut self) -> Option<(u8, usize)> {
+        let (state, mut tried) = *self.stack.last()?;
+        let edges = &self.automaton.edges[state];
+        let mut found = None;
+        while found.is_none() && tried < edges.len() {
+            let index = match self.class_order {
+                ClassOrder::Asc => tried,
+                ClassOrder::Desc => edges.len() - 1 - tried,
+            };
+            tried += 1;
+            let (byte, next) = edges[index];
+            if self.viable(next, self.prefix.len() + 1) {
+                found = Some((byte, next));
+            }
+        }
+        self.stack.last_mut().unwrap().1 = tried;
+        found
+    }
+
+    /// Backs out of the top of the stack.
+    fn pop(&mut self) {
+        self.stack.pop();
+        if !self.stack.is_empty() {
+            self.prefix.pop();
+        }
+    }
+
+    fn next_shortlex(&mut self) -> Option<Candidate> {
+        loop {
+            let Mode::Shortlex { length } = self.mode else {
+                unreachable!("only called in shortlex mode")
+            };
+            if self.stack.is_empty() {
+                if self.max_length.is_some_and(|max| length > max) {
+                    return None;
+                }
+                self.automaton.grow_to(length);
+                let start = self.automaton.start;
+                if self.viable(start, 0) {
+                    self.stack.push((start, 0));
+                } else {
+                    self.mode = Mode::Shortlex { length: length + 1 };
+                    continue;
+                }
+            }
+            let result = if self.prefix.len() == length {
+                Some(Candidate::from_slice(&self.prefix))
+            } else if let Some((byte, next)) = self.next_edge() {
+                self.stack.push((next, 0));
+                self.prefix.push(byte);
+                continue;
+            } else {
+                None
+            };
+            self.pop();
+            if self.stack.is_empty() {
+                self.mode = Mode::Shortlex { length: length + 1 };
+            }
+            if result.is_some() {
+                return result;
+            }
+        }
+    }
+
+    /// Ascending, a prefix comes before its extensions, so it is yielded on
+    /// the way down; descending, it comes after, so on the way back up.
+    fn next_lex(&mut self) -> Option<Candidate> {
+        if let Mode::Lex { started: false } = self.mode {
+            self.mode = Mode::Lex { started: true };
+            let start = self.automaton.start;
+            if self.viable(start, 0) {
+                self.stack.push((start, 0));
+                if self.class_order == ClassOrder::Asc && self.accepts() {
+                    return Some(Candidate::from_slice(&self.prefix));
+                }
+            }
+        }
+        while !self.stack.is_empty() {
+            match self.next_edge() {
+                Some((byte, next)) => {
+                    self.stack.push((next, 0));
+                    self.prefix.push(byte);
+                    if self.class_order == ClassOrder::Asc && self.accepts() {
+                        return Some(Candidate::from_slice(&self.prefix));
+                    }
+                }
+                None => {
+                    let result = (self.class_order == ClassOrder::Desc && self.accepts())
+                        .then(|| Candidate::from_slice(&self.prefix));
+                    self.pop();
+                    if result.is_some() {
+                        return result;
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Iterator for Sorted {
+    type Item = Candidate;
+
+    fn next(&mut self) -> Option<Candidate> {
+        match self.mode {
+            Mode::Shortlex { .. } => self.next_shortlex(),
+            Mode::Lex { .. } => self.next_lex(),
+        }
+    }
+}
+
+#[test]
+fn test_shortlex() {
+    let parse = |pattern| regex_syntax::Parser::new().parse(pattern).unwrap();
+    let results: Vec<_> = Sorted::shortlex(&parse("a*b*"), 0, Some(2), ClassOrder::Asc)
+        .unwrap()
+        .map(|x| String::from_utf8(x.to_vec()).unwrap())
+        .collect();
+    assert_eq!(results, ["", "a", "b", "aa", "ab", "bb"]);
+    for pattern in ["(a|ab)(b|)c?", "[0-9é-ë]{1,2}|x+", "(?:z|y[a-c]){0,3}"] {
+        for class_order in [ClassOrder::Asc, ClassOrder::Desc] {
+            let hir = parse(pattern);
+            let mut expected: Vec<_> = crate::iterate_all(&hir, Some(4), class_order)
+                .filter(|x| !x.is_empty())
+                .collect();
+            expected.sort_by(|a, b| {
+                let order = match class_order {
+                    ClassOrder::Asc => a.cmp(b),
+                    ClassOrder::Desc => b.cmp(a),
+                };
+                a.len().cmp(&b.len()).then(order)
+            });
+            expected.dedup();
+            let sorted: Vec<_> = Sorted::shortlex(&hir, 1, Some(4), class_order)
+                .unwrap()
+                .collect();
+            assert_eq!(sorted, expected, "{pattern}");
+        }
+    }
+}
+
+#[test]
+fn test_lex() {
+    let parse = |pattern| regex_syntax::Parser::new().parse(pattern).unwrap();
+    let results: Vec<_> = Sorted::lex(&parse("a*b*"), 0, Some(2), ClassOrder::Asc)
+        .unwrap()
+        .map(|x| String::from_utf8(x.to_vec()).unwrap())
+        .collect();
+    assert_eq!(results, ["", "a", "aa", "ab", "b", "bb"]);
+    assert!(matches!(
+        Sorted::lex(&parse("a*"), 0, None, ClassOrder::Asc),
+        Err(GenError::Unbounded)
+    ));
+    for pattern in ["(a|ab)(b|)c?", "[0-9é-ë]{1,2}|x+", "(?:z|y[a-c]){0,3}"] {
+        for class_order in [ClassOrder::Asc, ClassOrder::Desc] {
+            let hir = parse(pattern);
+            let mut expected: Vec<_> = crate::iterate_all(&hir, Some(4), class_order)
+                .filter(|x| !x.is_empty())
+                .collect();
+            expected.sort();
+            expected.dedup();
+            if class_order == ClassOrder::Desc {
+                expected.reverse();
+            }
+            let sorted: Vec<_> = Sorted::lex(&hir, 1, Some(4), class_order)
+                .unwrap()
+                .collect();
+            assert_eq!(sorted, expected, "{pattern}");
+        }
+    }
+}
+
+#[test]
+fn test_prefixes() {
+    let parse = |pattern| regex_syntax::Parser::new().parse(pattern).unwrap();
+    for (pattern, chars, max_length) in [
+        ("[a-c][0-9]{3}", 1, None),
+        ("foo[0-9]|bar|b|(a|ab)c?", 2, None),
+        ("(?:é|e)[0-9ë]{0,2}", 2, None),
+        ("[a-c]*", 3, Some(5)),
+    ] {
+        let hir = parse(pattern);
+        let prefixes = Prefixes::new(&hir, chars, 1, max_length).unwrap();
+        let mut expected = HashMap::<String, std::collections::HashSet<_>>::new();
+        for result in crate::iterate_all(&hir, max_length, ClassOrder::Asc) {
+            let result = String::from_utf8(result.to_vec()).unwrap();
+            if !result.is_empty() {
+                let prefix = result.chars().take(chars).collect();
+                expected.entry(prefix).or_default().insert(result);
+            }
+        }
+        assert_eq!(prefixes.count(), BigUint::from(expected.len()), "{pattern}");
+        for (prefix, results) in expected.iter().filter(|(p, _)| p.chars().count() == chars) {
+            let completions = prefixes.completions(prefix.as_bytes());
+            assert_eq!(completions, BigUint::from(results.len()), "{pattern}");
+        }
+    }
+    assert_eq!(
+        Prefixes::new(&parse("[a-c]{2}x"), 1, 0, None)
+            .unwrap()
+            .completions(b"b"),
+        BigUint::from(3u32)
+    );
+}
@@ -0,0 +1,1615 @@
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use rand::{Rng, RngExt};
+use regex_syntax::hir::{self, Class, Hir, HirKind::*, Visitor};
+use std::{iter::empty, rc::Rc};
+
+use crate::{
+    Candidate, ClassOrder,
+    error::GenError,
+    max_repeats, ordered,
+    sample::Permutation,
+    walker::{Choice, Walker},
+};
+
+/// Number of results of each length, stored densely from the shortest
+/// length that has any.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Counts {
+    offset: usize,
+    counts: Vec<BigUint>,
+}
+
+impl Counts {
+    fn single(length: usize, count: BigUint) -> Self {
+        if count.is_zero() {
+            return Self::default();
+        }
+        Self {
+            offset: length,
+            counts: vec![count],
+        }
+    }
+
+    fn get(&self, length: usize) -> Option<&BigUint> {
+        length
+            .checked_sub(self.offset)
+            .and_then(|i| self.counts.get(i))
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (usize, &BigUint)> {
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(i, count)| (self.offset + i, count))
+    }
+
+    fn add(&mut self, other: &Counts) {
+        if other.counts.is_empty() {
+            return;
+        }
+        if self.counts.is_empty() {
+            *self = other.clone();
+            return;
+        }
+        let offset = self.offset.min(other.offset);
+        let end = (self.offset + self.counts.len()).max(other.offset + other.counts.len());
+        let mut counts = vec![BigUint::zero(); end - offset];
+        for (length, count) in self.iter().chain(other.iter()) {
+            counts[length - offset] += count;
+        }
+        *self = Self { offset, counts };
+    }
+
+    /// Counts of every way to join a result from `self` with one from
+    /// `other`, dropping any longer than `max_length`.
+    fn convolve(&self, other: &Counts, max_length: usize) -> Counts {
+        if self.counts.is_empty() || other.counts.is_empty() {
+            return Self::default();
+        }
+        let Some(offset) = self
+            .offset
+            .checked_add(other.offset)
+            .filter(|&offset| offset <= max_length)
+        else {
+            return Self::default();
+        };
+        let width = (self.counts.len() + other.counts.len() - 1)
+            .min((max_length - offset).saturating_add(1));
+        let mut counts = vec![BigUint::zero(); width];
+        for (i, a) in self.counts.iter().enumerate() {
+            for (j, b) in other
+                .counts
+                .iter()
+                .enumerate()
+                .take(width.saturating_sub(i))
+            {
+                counts[i + j] += a * b;
+            }
+        }
+        Self { offset, counts }.trimmed()
+    }
+
+    /// The same counts with every length reduced by `by`, dropping any
+    /// that would go below zero.
+    fn shifted(&self, by: usize) -> Counts {
+        match self.offset.checked_sub(by) {
+            Some(offset) => Self {
+                offset,
+                counts: self.counts.clone(),
+            },
+            None => Self {
+                offset: 0,
+                counts: self.counts.iter().skip(by - self.offset).cloned().collect(),
+            },
+        }
+    }
+
+    /// The sum over lengths of `self[length] * weights[length]`.
+    fn dot(&self, weights: &Counts) -> BigUint {
+        self.iter()
+            .filter_map(|(length, count)| Some(count * weights.get(length)?))
+            .sum()
+    }
+
+    fn trimmed(mut self) -> Self {
+        while self.counts.last().is_some_and(Zero::is_zero) {
+            self.counts.pop();
+        }
+        let leading = self
+            .counts
+            .iter()
+            .take_while(|count| count.is_zero())
+            .count();
+        self.counts.drain(..leading);
+        self.offset += leading;
+        self
+    }
+}
+
+/// A contiguous run of class members that all encode to `length` bytes.
+#[derive(Debug)]
+struct Segment {
+    start: u32,
+    end: u32,
+    length: usize,
+}
+
+impl Segment {
+    fn size(&self) -> BigUint {
+        BigUint::from(self.end - self.start + 1)
+    }
+}
+
+const UTF8_BANDS: [(u32, u32, usize); 5] = [
+    (0, 0x7f, 1),
+    (0x80, 0x7ff, 2),
+    (0x800, 0xd7ff, 3),
+    (0xe000, 0xffff, 3),
+    (0x10000, 0x10ffff, 4),
+];
+
+fn segments(class: &Class) -> Vec<Segment> {
+    match class {
+        Class::Unicode(class) => class
+            .ranges()
+            .iter()
+            .flat_map(|r| {
+                UTF8_BANDS.iter().filter_map(|&(low, high, length)| {
+                    let start = (r.start() as u32).max(low);
+                    let end = (r.end() as u32).min(high);
+                    (start <= end).then_some(Segment { start, end, length })
+                })
+            })
+            .collect(),
+        Class::Bytes(class) => class
+            .ranges()
+            .iter()
+            .map(|r| Segment {
+                start: r.start() as u32,
+                end: r.end() as u32,
+                length: 1,
+            })
+            .collect(),
+    }
+}
+
+#[derive(Debug)]
+enum Kind {
+    Nothing,
+    Literal(Vec<u8>),
+    Class {
+        segments: Vec<Segment>,
+        unicode: bool,
+    },
+    Alternation(Vec<Node>),
+    /// `prefixes[j]` holds the counts of the first `j` children joined.
+    Concat {
+        children: Vec<Node>,
+        prefixes: Vec<Counts>,
+    },
+    /// `powers[k]` holds the counts of `k` joined repeats of `sub`.
+    Repetition {
+        sub: Box<Node>,
+        min: usize,
+        powers: Vec<Counts>,
+    },
+}
+
+#[derive(Debug)]
+struct Node {
+    kind: Kind,
+    counts: Counts,
+}
+
+impl Drop for Node {
+    /// Frees the tree without recursing, however deeply it nests.
+    fn drop(&mut self) {
+        let mut pending = vec![std::mem::replace(&mut self.kind, Kind::Nothing)];
+        while let Some(kind) = pending.pop() {
+            let children = match kind {
+                Kind::Alternation(children) | Kind::Concat { children, .. } => children,
+                Kind::Repetition { sub, .. } => vec![*sub],
+                _ => continue,
+            };
+            for mut child in children {
+                pending.push(std::mem::replace(&mut child.kind, Kind::Nothing));
+            }
+        }
+    }
+}
+
+/// Builds the nodes of a pattern, children before parents, without
+/// recursing.
+struct Compiler {
+    stack: Vec<Node>,
+    max_length: Option<usize>,
+}
+
+impl Compiler {
+    fn pop(&mut self, count: usize) -> Vec<Node> {
+        self.stack.split_off(self.stack.len() - count)
+    }
+}
+
+impl Visitor for Compiler {
+    type Output = Node;
+    type Err = GenError;
+
+    fn finish(mut self) -> Result<Node, GenError> {
+        Ok(self.stack.pop().expect("the pattern compiles to one node"))
+    }
+
+    fn visit_post(&mut self, hir: &Hir) -> Result<(), GenError> {
+        let cap = self.max_length.unwrap_or(usize::MAX);
+        let node = match hir.kind() {
+            Empty => Node {
+                kind: Kind::Literal(Vec::new()),
+                counts: Counts::single(0, BigUint::one()),
+            },
+            Look(_) => Node {
+                kind: Kind::Nothing,
+                counts: Counts::default(),
+            },
+            Literal(literal) => Node {
+                counts: if literal.0.len() <= cap {
+                    Counts::single(literal.0.len(), BigUint::one())
+                } else {
+                    Counts::default()
+                },
+                kind: Kind::Literal(literal.0.to_vec()),
+            },
+            Class(class) => {
+                let segments = segments(class);
+                let mut counts = Counts::default();
+                for segment in segments.iter().filter(|segment| segment.length <= cap) {
+                    counts.add(&Counts::single(segment.length, segment.size()));
+                }
+                Node {
+                    kind: Kind::Class {
+                        segments,
+                        unicode: matches!(class, Class::Unicode(_)),
+                    },
+                    counts,
+                }
+            }
+            // A group is its sub-expression, which is already on the stack.
+            Capture(_) => return Ok(()),
+            Concat(hirs) => {
+                let children = self.pop(hirs.len());
+                let mut prefixes = vec![Counts::single(0, BigUint::one())];
+                for child in &children {
+                    let next = prefixes.last().unwrap().convolve(&child.counts, cap);
+                    prefixes.push(next);
+                }
+                Node {
+                    counts: prefixes.pop().unwrap(),
+                    kind: Kind::Concat { children, prefixes },
+                }
+            }
+            Alternation(hirs) => {
+                let children = self.pop(hirs.len());
+                let mut counts = Counts::default();
+                for child in &children {
+                    counts.add(&child.counts);
+                }
+                Node {
+                    kind: Kind::Alternation(children),
+                    counts,
+                }
+            }
+            Repetition(repetition) => {
+                let max = max_repeats(repetition, self.max_length).ok_or(GenError::Unbounded)?;
+                let min = repetition.min as usize;
+                let sub = self.pop(1).pop().unwrap();
+                let mut powers = vec![Counts::single(0, BigUint::one())];
+                while powers.len() <= max {
+                    let next = powers.last().unwrap().convolve(&sub.counts, cap);
+                    if next.counts.is_empty() {
+                        break;
+                    }
+                    powers.push(next);
+                }
+                let mut counts = Counts::default();
+                for power in powers.iter().skip(min) {
+                    counts.add(power);
+                }
+                Node {
+                    kind: Kind::Repetition {
+                        sub: Box::new(sub),
+                        min,
+                        powers,
+                    },
+                    counts,
+                }
+            }
+        };
+        self.stack.push(node);
+        Ok(())
+    }
+}
+
+fn compile(hir: &Hir, max_length: Option<usize>) -> Result<Node, GenError> {
+    hir::visit(
+        hir,
+        Compiler {
+            stack: Vec::new(),
+            max_length,
+        },
+    )
+}
+
+fn random_below(bound: &BigUint, rng: &mut impl Rng) -> BigUint {
+    let bits = bound.bits();
+    let mut bytes = vec![0; bits.div_ceil(8) as usize];
+    loop {
+        rng.fill_bytes(&mut bytes);
+        if !bits.is_multiple_of(8) {
+            *bytes.last_mut().unwrap() &= (1 << (bits % 8)) - 1;
+        }
+        let value = BigUint::from_bytes_le(&bytes);
+        if &value < bound {
+            return value;
+        }
+    }
+}
+
+/// Picks an index with probability proportional to its weight.
+fn choose(weights: &[BigUint], rng: &mut impl Rng) -> usize {
+    let total: BigUint = weights.iter().sum();
+    let mut target = random_below(&total, rng);
+    for (i, weight) in weights.iter().enumerate() {
+        if &target < weight {
+            return i;
+        }
+        target -= weight;
+    }
+    unreachable!("target is below the sum of the weights")
+}
+
+/// Picks lengths for a sequence of parts so that they add up to `length`,
+/// weighted by the number of results each split allows. `prefixes[j]` are
+/// the counts of the first `j` parts joined and `part(j)` those of part `j`.
+fn split_lengths<'a>(
+    length: usize,
+    prefixes: &[Counts],
+    part: impl Fn(usize) -> &'a Counts,
+    rng: &mut impl Rng,
+) -> Vec<usize> {
+    let mut lengths = vec![0; prefixes.len()];
+    let mut remaining = length;
+    for j in (0..prefixes.len()).rev() {
+        let options: Vec<(usize, BigUint)> = part(j)
+            .iter()
+            .filter(|&(length, _)| length <= remaining)
+            .filter_map(|(length, count)| {
+                let rest = prefixes[j].get(remaining - length)?;
+                Some((length, count * rest))
+            })
+            .collect();
+        let weights: Vec<_> = options.iter().map(|(_, weight)| weight.clone()).collect();
+        lengths[j] = options[choose(&weights, rng)].0;
+        remaining -= lengths[j];
+    }
+    lengths
+}
+
+fn sample_node(node: &Node, length: usize, rng: &mut impl Rng, out: &mut Vec<u8>) {
+    // Nodes still to sample, with the length each was given, the next on top.
+    let mut pending = vec![(node, length)];
+    while let Some((node, length)) = pending.pop() {
+        match &node.kind {
+            Kind::Nothing => unreachable!("nodes without results are never sampled"),
+            Kind::Literal(literal) => out.extend_from_slice(literal),
+            Kind::Class { segments, unicode } => {
+                let segments: Vec<_> = segments
+                    .iter()
+                    .filter(|segment| segment.length == length)
+                    .collect();
+                let weights: Vec<_> = segments.iter().map(|segment| segment.size()).collect();
+                let segment = segments[choose(&weights, rng)];
+                let member = segment.start + rng.random_range(0..=segment.end - segment.start);
+                encode_member(member, *unicode, out);
+            }
+            Kind::Alternation(children) => {
+                let weights: Vec<_> = children
+                    .iter()
+                    .map(|child| child.counts.get(length).cloned().unwrap_or_default())
+                    .collect();
+                pending.push((&children[choose(&weights, rng)], length));
+            }
+            Kind::Concat { children, prefixes } => {
+                let lengths = split_lengths(length, prefixes, |j| &children[j].counts, rng);
+                pending.extend(children.iter().zip(lengths).rev());
+            }
+            Kind::Repetition { sub, min, powers } => {
+                let weights: Vec<_> = powers
+                    .iter()
+                    .skip(*min)
+                    .map(|power| power.get(length).cloned().unwrap_or_default())
+                    .collect();
+                let repeats = min + choose(&weights, rng);
+                let lengths = split_lengths(length, &powers[..repeats], |_| &sub.counts, rng);
+                pending.extend(lengths.into_iter().rev().map(|length| (&**sub, length)));
+            }
+        }
+    }
+}
+
+// Ranking follows the generator's order, where the last part of a sequence
+// changes slowest. Every result of a node is followed by `weights[length]`
+// completions of the rest of the pattern, so a node's results start
+// `weights[length]` apart in the overall enumeration.
+
+/// Weights for the lengths of the last part of a sequence, given the
+/// `prefix` counts of the parts before it and `weights` for the whole.
+fn part_weights(part: &Counts, prefix: &Counts, weights: &Counts) -> Counts {
+    Counts {
+        offset: part.offset,
+        counts: part
+            .iter()
+            .map(|(length, _)| prefix.dot(&weights.shifted(length)))
+            .collect(),
+    }
+}
+
+fn ordered_segments(segments: &[Segment], order: ClassOrder) -> impl Iterator<Item = &Segment> {
+    ordered(segments.iter(), order)
+}
+
+/// The parts of a sequence: a concatenation's children, or copies of a
+/// repetition's sub-expression. `prefixes[j]` holds the counts of the first
+/// `j` parts joined.
+#[derive(Clone, Copy)]
+struct Sequence<'a> {
+    prefixes: &'a [Counts],
+    parts: Parts<'a>,
+}
+
+#[derive(Clone, Copy)]
+enum Parts<'a> {
+    Children(&'a [Node]),
+    Copies(&'a Node),
+}
+
+impl<'a> Parts<'a> {
+    fn get(self, j: usize) -> &'a Node {
+        match self {
+            Parts::Children(children) => &children[j],
+            Parts::Copies(sub) => sub,
+        }
+    }
+}
+
+impl<'a> Sequence<'a> {
+    fn len(&self) -> usize {
+        self.prefixes.len()
+    }
+
+    fn part(&self, j: usize) -> &'a Node {
+        self.parts.get(j)
+    }
+
+    /// Weights for part `j`, given `weights` for the parts after it.
+    fn part_weights(&self, j: usize, weights: &Counts) -> Counts {
+        part_weights(&self.part(j).counts, &self.prefixes[j], weights)
+    }
+}
+
+/// A sequence part way through [`unrank_node`], which unranks its parts
+/// last to first since the last changes slowest.
+struct Unranking<'a> {
+    sequence: Sequence<'a>,
+    /// Weights for the parts not yet unranked.
+    weights: Counts,
+    /// The parts unranked so far, last first.
+    pieces: Vec<Vec<u8>>,
+}
+
+/// Writes the result at `index` to `out` and the choices that lead to it to
+/// `choices`, returning the index among the completions that follow it.
+fn unrank_node(
+    node: &Node,
+    mut index: BigUint,
+    weights: &Counts,
+    order: ClassOrder,
+    out: &mut Vec<u8>,
+    choices: &mut Vec<Choice>,
+) -> BigUint {
+    let mut stack: Vec<Unranking> = Vec::new();
+    let mut next = Some((node, weights.clone()));
+    let mut piece = None;
+    loop {
+        if let Some((node, weights)) = next.take() {
+            match &node.kind {
+                Kind::Nothing => unreachable!("nodes without results are never unranked"),
+                Kind::Literal(literal) => piece = Some(literal.clone()),
+                Kind::Class { segments, unicode } => {
+                    let (member, within) = unrank_member(segments, index, &weights, order);
+                    let mut encoded = Vec::new();
+                    encode_member(member, *unicode, &mut encoded);
+                    choices.push(Choice::Member(member));
+                    piece = Some(encoded);
+                    index = within;
+                }
+                Kind::Alternation(children) => {
+                    let blocks = children.iter().map(|child| child.counts.dot(&weights));
+                    let branch = locate(&mut index, blocks);
+                    choices.push(Choice::Branch(branch));
+                    next = Some((&children[branch], weights));
+                }
+                Kind::Concat { children, prefixes } => stack.push(Unranking {
+                    sequence: Sequence {
+                        prefixes,
+                        parts: Parts::Children(children),
+                    },
+                    weights,
+                    pieces: Vec::new(),
+                }),
+                Kind::Repetition { sub, min, powers } => {
+                    let blocks = powers[*min..].iter().map(|power| power.dot(&weights));
+                    let repeats = min + locate(&mut index, blocks);
+                    choices.push(Choice::Repeats(repeats));
+                    stack.push(Unranking {
+                        sequence: Sequence {
+                            prefixes: &powers[..repeats],
+                            parts: Parts::Copies(sub),
+                        },
+                        weights,
+                        pieces: Vec::new(),
+                    });
+                }
+            }
+            continue;
+        }
+        let Some(unranking) = stack.last_mut() else {
+            break;
+        };
+        if let Some(piece) = piece.take() {
+            unranking.weights = unranking.weights.shifted(piece.len());
+            unranking.pieces.push(piece);
+        }
+        let done = unranking.pieces.len();
+        if done == unranking.sequence.len() {
+            let unranking = stack.pop().unwrap();
+            piece = Some(unranking.pieces.into_iter().rev().flatten().collect());
+        } else {
+            let j = unranking.sequence.len() - 1 - done;
+            let part_weights = unranking.sequence.part_weights(j, &unranking.weights);
+            next = Some((unranking.sequence.part(j), part_weights));
+        }
+    }
+    out.extend(piece.expect("the root has a result"));
+    index
+}
+
+/// Which of a run of blocks of results `index` falls in, leaving `index`
+/// within that block.
+fn locate(index: &mut BigUint, blocks: impl IntoIterator<Item = BigUint>) -> usize {
+    for (i, block) in blocks.into_iter().enumerate() {
+        if *index < block {
+            return i;
+        }
+        *index -= block;
+    }
+    unreachable!("index is below the weighted count")
+}
+
+/// The member of a class at `index`, and the index among the completions
+/// that follow it.
+fn unrank_member(
+    segments: &[Segment],
+    mut index: BigUint,
+    weights: &Counts,
+    order: ClassOrder,
+) -> (u32, BigUint) {
+    for segment in ordered_segments(segments, order) {
+        let Some(weight) = weights.get(segment.length) else {
+            continue;
+        };
+        let block = segment.size() * weight;
+        if index >= block {
+            index -= block;
+            continue;
+        }
+        let offset = u32::try_from(&index / weight).unwrap();
+        let member = match order {
+            ClassOrder::Asc => segment.start + offset,
+            ClassOrder::Desc => segment.end - offset,
+        };
+        return (member, index % weight);
+    }
+    unreachable!("index is below the weighted count")
+}
+
+fn class_member(candidate: &[u8], unicode: bool) -> Option<u32> {
+    if unicode {
+        let mut chars = std::str::from_utf8(candidate).ok()?.chars();
+        let c = chars.next()?;
+        chars.next().is_none().then_some(c as u32)
+    } else {
+        (candidate.len() == 1).then(|| candidate[0] as u32)
+    }
+}
+
+/// The split of a candidate between the parts of a sequence that is
+/// produced first, found part by part for [`rank_node`]. `best[end]` is the
+/// lowest rank of `candidate[..end]` among the parts seen so far, and
+/// `next` the same with part `j` added, which is tried over each split
+/// `candidate[start..end]` in turn.
+struct RankSequence<'a> {
+    sequence: Sequence<'a>,
+    candidate: &'a [u8],
+    weights: Rc<Counts>,
+    best: Vec<Option<BigUint>>,
+    next: Vec<Option<BigUint>>,
+    j: usize,
+    end: usize,
+    start: usize,
+    /// Weights for part `j` ending at `end`, once worked out.
+    part_weights: Option<Rc<Counts>>,
+    /// Whether the split at `start..end` is being ranked.
+    waiting: bool,
+}
+
+impl<'a> RankSequence<'a> {
+    fn new(sequence: Sequence<'a>, candidate: &'a [u8], weights: Rc<Counts>) -> Self {
+        let mut best = vec![None; candidate.len() + 1];
+        best[0] = Some(BigUint::zero());
+        Self {
+            sequence,
+            candidate,
+            weights,
+            best,
+            next: vec![None; candidate.len() + 1],
+            j: 0,
+            end: 0,
+            start: 0,
+            part_weights: None,
+            waiting: false,
+        }
+    }
+
+    /// Takes the rank of the part within the split being ranked.
+    fn record(&mut self, within: Option<BigUint>) {
+        if let (Some(before), Some(within)) = (&self.best[self.start], within) {
+            let rank = before + within;
+            let next = &mut self.next[self.end];
+            if next.as_ref().is_none_or(|current| &rank < current) {
+                *next = Some(rank);
+            }
+        }
+        self.start += 1;
+    }
+
+    /// The next split to rank a part over, or `None` once they all have been.
+    fn split(&mut self) -> Option<(&'a Node, &'a [u8], Rc<Counts>)> {
+        let length = self.candidate.len();
+        while self.j < self.sequence.len() {
+            if self.start > self.end {
+                self.end += 1;
+                self.start = 0;
+                self.part_weights = None;
+            }
+            if self.end > length {
+                self.best = std::mem::replace(&mut self.next, vec![None; length + 1]);
+                self.j += 1;
+                self.end = 0;
+                continue;
+            }
+            if self.best[self.start].is_none() {
+                self.start += 1;
+                continue;
+            }
+            let part_weights = self.part_weights.get_or_insert_with(|| {
+                let weights = self.weights.shifted(length - self.end);
+                Rc::new(self.sequence.part_weights(self.j, &weights))
+            });
+            let split = &self.candidate[self.start..self.end];
+            return Some((self.sequence.part(self.j), split, part_weights.clone()));
+        }
+        None
+    }
+}
+
+/// A node part way through [`rank_node`].
+enum Ranking<'a> {
+    /// Ranking the child of an alternation at `branch`, after `rank`
+    /// results from the branches before it.
+    Alternation {
+        children: &'a [Node],
+        branch: usize,
+        rank: BigUint,
+        candidate: &'a [u8],
+        weights: Rc<Counts>,
+    },
+    /// Ranking the copies of a repetition's sub-expression, after `rank`
+    /// results with fewer copies.
+    Repetition {
+        sub: &'a Node,
+        powers: &'a [Counts],
+        repeats: usize,
+        rank: BigUint,
+        candidate: &'a [u8],
+        weights: Rc<Counts>,
+    },
+    Sequence(RankSequence<'a>),
+}
+
+/// The weighted index of the first time `candidate` is produced, or `None`
+/// if it never is.
+fn rank_node(
+    node: &Node,
+    candidate: &[u8],
+    weights: &Counts,
+    order: ClassOrder,
+) -> Option<BigUint> {
+    let mut stack = Vec::new();
+    // Weights are shared by the nodes that use them, often many times over.
+    let mut next = Some((node, candidate, Rc::new(weights.clone())));
+    let mut rank = None;
+    loop {
+        if let Some((node, candidate, weights)) = next.take() {
+            match &node.kind {
+                Kind::Nothing => rank = None,
+                Kind::Literal(literal) => rank = (literal == candidate).then(BigUint::zero),
+                Kind::Class { segments, unicode } => {
+                    rank = class_member(candidate, *unicode)
+                        .and_then(|member| rank_member(segments, member, &weights, order));
+                }
+                Kind::Alternation(children) => {
+                    next = Some((&children[0], candidate, weights.clone()));
+                    stack.push(Ranking::Alternation {
+                        children,
+                        branch: 0,
+                        rank: BigUint::zero(),
+                        candidate,
+                        weights,
+                    });
+                }
+                Kind::Concat { children, prefixes } => {
+                    let sequence = Sequence {
+                        prefixes,
+                        parts: Parts::Children(children),
+                    };
+                    stack.push(Ranking::Sequence(RankSequence::new(
+                        sequence, candidate, weights,
+                    )));
+                }
+                Kind::Repetition { sub, min, powers } if *min < powers.len() => {
+                    let sequence = Sequence {
+                        prefixes: &powers[..*min],
+                        parts: Parts::Copies(sub),
+                    };
+                    let copies = RankSequence::new(sequence, candidate, weights.clone());
+                    stack.push(Ranking::Repetition {
+                        sub,
+                        powers,
+                        repeats: *min,
+                        rank: BigUint::zero(),
+                        candidate,
+                        weights,
+                    });
+                    stack.push(Ranking::Sequence(copies));
+                }
+                Kind::Repetition { .. } => rank = None,
+            }
+            continue;
+        }
+        let Some(ranking) = stack.last_mut() else {
+            return rank;
+        };
+        match ranking {
+            Ranking::Sequence(sequence) => {
+                if std::mem::take(&mut sequence.waiting) {
+                    sequence.record(rank.take());
+                }
+                match sequence.split() {
+                    Some(split) => {
+                        sequence.waiting = true;
+                        next = Some(split);
+                    }
+                    None => {
+                        rank = sequence.best.pop().unwrap();
+                        stack.pop();
+                    }
+                }
+            }
+            Ranking::Alternation {
+                children,
+                branch,
+                rank: before,
+                candidate,
+                weights,
+            } => {
+                if let Some(within) = rank.take() {
+                    rank = Some(&*before + within);
+                    stack.pop();
+                    continue;
+                }
+                *before += children[*branch].counts.dot(weights);
+                *branch += 1;
+                match children.get(*branch) {
+                    Some(child) => next = Some((child, *candidate, weights.clone())),
+                    None => {
+                        stack.pop();
+                    }
+                }
+            }
+            Ranking::Repetition {
+                sub,
+                powers,
+                repeats,
+                rank: before,
+                candidate,
+                weights,
+            } => {
+                if let Some(within) = rank.take() {
+                    rank = Some(&*before + within);
+                    stack.pop();
+                    continue;
+                }
+                *before += powers[*repeats].dot(weights);
+                *repeats += 1;
+                if *repeats == powers.len() {
+                    stack.pop();
+                    continue;
+                }
+                let sequence = Sequence {
+                    prefixes: &powers[..*repeats],
+                    parts: Parts::Copies(sub),
+                };
+                let copies = RankSequence::new(sequence, candidate, weights.clone());
+                stack.push(Ranking::Sequence(copies));
+            }
+        }
+    }
+}
+
+/// The weighted index of `member` among a class's results, or `None` if it
+/// isn't one of them.
+fn rank_member(
+    segments: &[Segment],
+    member: u32,
+    weights: &Counts,
+    order: ClassOrder,
+) -> Option<BigUint> {
+    let mut rank = BigUint::zero();
+    for segment in ordered_segments(segments, order) {
+        let Some(weight) = weights.get(segment.length) else {
+            continue;
+        };
+        if (segment.start..=segment.end).contains(&member) {
+            let offset = match order {
+                ClassOrder::Asc => member - segment.start,
+                ClassOrder::Desc => segment.end - member,
+            };
+            return Some(rank + BigUint::from(offset) * weight);
+        }
+        rank += segment.size() * weight;
+    }
+    None
+}
+
+fn encode_member(member: u32, unicode: bool, out: &mut Vec<u8>) {
+    if unicode {
+        let c = char::from_u32(member).unwrap();
+        out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+    } else {
+        out.push(member as u8);
+    }
+}
+
+/// A node part way through [`common_prefix`].
+enum Common<'a> {
+    /// Comparing the prefixes of an alternation's branches with results.
+    Alternation {
+        rest: std::slice::Iter<'a, Node>,
+        keep: usize,
+        shared: Option<Vec<u8>>,
+    },
+    /// Joining the prefixes of the first `count` parts of a sequence, all
+    /// of a concatenation's or a repetition's minimum number of copies.
+    /// `whole` is whether those are all the parts there can be.
+    Sequence {
+        parts: Parts<'a>,
+        count: usize,
+        j: usize,
+        keep: usize,
+        shared: Vec<u8>,
+        whole: bool,
+    },
+}
+
+/// The first `keep` bytes that every result of `node` starts with, or the
+/// whole of its only result if that is shorter. `None` if its results
+/// differ within their first `keep` bytes, or might.
+fn common_prefix(node: &Node, keep: usize) -> Option<Vec<u8>> {
+    let mut stack = Vec::new();
+    let mut next = Some((node, keep));
+    let mut prefix = None;
+    loop {
+        if let Some((node, keep)) = next.take() {
+            match &node.kind {
+                Kind::Nothing => return None,
+                Kind::Literal(literal) => {
+                    prefix = Some(literal[..keep.min(literal.len())].to_vec())
+                }
+                Kind::Class { segments, unicode } => match segments.as_slice() {
+                    [segment] if segment.start == segment.end => {
+                        let mut member = Vec::new();
+                        encode_member(segment.start, *unicode, &mut member);
+                        member.truncate(keep);
+                        prefix = Some(member);
+                    }
+                    _ if keep == 0 => prefix = Some(Vec::new()),
+                    _ => return None,
+                },
+                Kind::Alternation(children) => stack.push(Common::Alternation {
+                    rest: children.iter(),
+                    keep,
+                    shared: None,
+                }),
+                Kind::Concat { children, .. } => stack.push(Common::Sequence {
+                    parts: Parts::Children(children),
+                    count: children.len(),
+                    j: 0,
+                    keep,
+                    shared: Vec::new(),
+                    whole: true,
+                }),
+                // With a choice of how many repeats, a shorter result could
+                // end where a longer one goes on.
+                Kind::Repetition { sub, min, powers } => stack.push(Common::Sequence {
+                    parts: Parts::Copies(sub),
+                    count: *min,
+                    j: 0,
+                    keep,
+                    shared: Vec::new(),
+                    whole: powers.len() == min + 1,
+                }),
+            }
+            continue;
+        }
+        let Some(common) = stack.last_mut() else {
+            return prefix;
+        };
+        match common {
+            Common::Alternation { rest, keep, shared } => {
+                if let Some(prefix) = prefix.take()
+                    && shared.get_or_insert_with(|| prefix.clone()) != &prefix
+                {
+                    return None;
+                }
+                match rest.find(|child| !child.counts.counts.is_empty()) {
+                    Some(child) => next = Some((child, *keep)),
+                    None => {
+                        prefix = Some(shared.take()?);
+                        stack.pop();
+                    }
+                }
+            }
+            Common::Sequence {
+                parts,
+                count,
+                j,
+                keep,
+                shared,
+                whole,
+            } => {
+                if let Some(piece) = prefix.take() {
+                    shared.extend(piece);
+                }
+                if shared.len() == *keep || j == count {
+                    if !*whole && shared.len() < *keep {
+                        return None;
+                    }
+                    prefix = Some(std::mem::take(shared));
+                    stack.pop();
+                } else {
+                    next = Some((parts.get(*j), *keep - shared.len()));
+                    *j += 1;
+                }
+            }
+        }
+    }
+}
+
+/// The index, in the same terms as `index`, just past the run of results
+/// from `index` on that share their first `keep` bytes with it. Only
+/// called along the start of the pattern, where `weights` are all 0 or 1
+/// and so every result takes one index.
+fn skip_node(
+    node: &Node,
+    mut index: BigUint,
+    weights: &Counts,
+    order: ClassOrder,
+    mut keep: usize,
+) -> BigUint {
+    // Narrows down to the part of the node that `index` is in, adding the
+    // indices before that part to `base`.
+    let mut node = node;
+    let mut weights = weights.clone();
+    let mut base = BigUint::zero();
+    loop {
+        if common_prefix(node, keep).is_some_and(|prefix| prefix.len() == keep) {
+            return base + node.counts.dot(&weights);
+        }
+        let start = index.clone();
+        let sequence = match &node.kind {
+            Kind::Alternation(children) => {
+                let blocks = children.iter().map(|child| child.counts.dot(&weights));
+                node = &children[locate(&mut index, blocks)];
+                base += start - &index;
+                continue;
+            }
+            Kind::Concat { children, prefixes } => Sequence {
+                prefixes,
+                parts: Parts::Children(children),
+            },
+            Kind::Repetition { sub, min, powers } => {
+                let blocks = powers[*min..].iter().map(|power| power.dot(&weights));
+                let repeats = min + locate(&mut index, blocks);
+                Sequence {
+                    prefixes: &powers[..repeats],
+                    parts: Parts::Copies(sub),
+                }
+            }
+            _ => return base + index + 1u8,
+        };
+        // Parts with a single result shorter than the prefix don't change,
+        // so the run is that of the first part after them, whose results
+        // take consecutive indices while the later parts stay put.
+        let mut first = 0;
+        while first < sequence.len() {
+            match common_prefix(sequence.part(first), keep) {
+                Some(piece) if piece.len() < keep => keep -= piece.len(),
+                _ => break,
+            }
+            first += 1;
+        }
+        if first == sequence.len() {
+            return base + index + 1u8;
+        }
+        for j in (first + 1..sequence.len()).rev() {
+            let part_weights = sequence.part_weights(j, &weights);
+            let mut piece = Vec::new();
+            let part = sequence.part(j);
+            index = unrank_node(
+                part,
+                index,
+                &part_weights,
+                order,
+                &mut piece,
+                &mut Vec::new(),
+            );
+            weights = weights.shifted(piece.len());
+        }
+        weights = sequence.part_weights(first, &weights);
+        base += start - &index;
+        node = sequence.part(first);
+    }
+}
+
+/// The number of results of a pattern, broken down by length and by
+/// sub-expression, so results can be drawn without enumerating them.
+#[derive(Debug)]
+pub struct Space {
+    root: Node,
+    min_length: usize,
+    max_length: Option<usize>,
+    class_order: ClassOrder,
+}
+
+impl Space {
+    /// Fails if the pattern has an infinite repetition and `max_length` is `None`.
+    pub fn new(hir: &Hir, max_length: Option<usize>) -> Result<Self, GenError> {
+        Ok(Self {
+            root: compile(hir, max_length)?,
+            min_length: 0,
+            max_length,
+            class_order: ClassOrder::default(),
+        })
+    }
+
+    /// Order of class members assumed by [`Space::unrank`] and [`Space::rank`].
+    pub fn class_order(mut self, class_order: ClassOrder) -> Self {
+        self.class_order = class_order;
+        self
+    }
+
+    /// Leaves results shorter than `min_length` bytes out of the indices
+    /// used by [`Space::unrank`], [`Space::rank`] and [`Space::iterate_from`].
+    pub fn min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    fn unit_weights(&self) -> Counts {
+        let offset = self.root.counts.offset;
+        Counts {
+            offset,
+            counts: (offset..offset + self.root.counts.counts.len())
+                .map(|length| BigUint::from(u8::from(length >= self.min_length)))
+                .collect(),
+        }
+    }
+
+    /// The number of indexed results: those of at least the min length.
+    pub fn total(&self) -> BigUint {
+        self.count(self.min_length)
+    }
+
+    /// The result at `index` in enumeration order, or `None` if there are
+    /// not that many.
+    pub fn unrank(&self, index: &BigUint) -> Option<Vec<u8>> {
+        if index >= &self.total() {
+            return None;
+        }
+        let mut result = Vec::new();
+        unrank_node(
+            &self.root,
+            index.clone(),
+            &self.unit_weights(),
+            self.class_order,
+            &mut result,
+            &mut Vec::new(),
+        );
+        Some(result)
+    }
+
+    /// Enumerates `hir`, which must be the pattern this was built from,
+    /// starting at `index` without generating the results before it.
+    pub fn iterate_from<'a>(
+        &self,
+        hir: &'a Hir,
+        index: &BigUint,
+    ) -> Box<dyn Iterator<Item = Candidate> + 'a> {
+        Box::new(self.iterate_branches_from(hir, index).map(|(_, x)| x))
+    }
+
+    /// Like [`Space::iterate_from`], but tags each result with its top-level
+    /// alternation branch like [`crate::iterate_branches`].
+    pub fn iterate_branches_from<'a>(
+        &self,
+        hir: &'a Hir,
+        index: &BigUint,
+    ) -> Box<dyn Iterator<Item = (usize, Candidate)> + 'a> {
+        if index >= &self.total() {
+            return Box::new(empty());
+        }
+        // The walker is put straight onto the result at `index`, by the
+        // choices that picking it out makes on the way down.
+        let mut choices = Vec::new();
+        unrank_node(
+            &self.root,
+            index.clone(),
+            &self.unit_weights(),
+            self.class_order,
+            &mut Vec::new(),
+            &mut choices,
+        );
+        Box::new(
+            Walker::new(hir, self.max_length, self.class_order)
+                .min_length(self.min_length)
+                .seek(choices),
+        )
+    }
+
+    /// The index at which `candidate` is first produced, or `None` if it
+    /// never is.
+    pub fn rank(&self, candidate: &[u8]) -> Option<BigUint> {
+        if candidate.len() < self.min_length {
+            return None;
+        }
+        rank_node(
+            &self.root,
+            candidate,
+            &self.unit_weights(),
+            self.class_order,
+        )
+    }
+
+    /// An index past `index` such that every result in between starts with
+    /// the same `length` bytes as the one at `index`, or is the same result
+    /// if they are shorter. It falls short of the end of that run where
+    /// finding the end would take enumerating, but skips whole branches and
+    /// repeat counts whose results all share a prefix.
+    pub fn skip_prefix(&self, index: &BigUint, length: usize) -> BigUint {
+        if index >= &self.total() {
+            return index + 1u8;
+        }
+        skip_node(
+            &self.root,
+            index.clone(),
+            &self.unit_weights(),
+            self.class_order,
+            length,
+        )
+    }
+
+    /// The exact number of results of at least `min_length` bytes. Results
+    /// an ambiguous pattern produces more than once are counted each time.
+    pub fn count(&self, min_length: usize) -> BigUint {
+        self.root
+            .counts
+            .iter()
+            .filter(|&(length, _)| length >= min_length)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    /// The number of indexed results of each length that has any, shortest
+    /// first.
+    pub fn lengths(&self) -> Vec<(usize, BigUint)> {
+        self.root
+            .counts
+            .iter()
+            .filter(|&(length, count)| length >= self.min_length && !count.is_zero())
+            .map(|(length, count)| (length, count.clone()))
+            .collect()
+    }
+
+    /// The number of indexed results of each top-level alternation branch,
+    /// numbered as [`crate::iterate_branches`] numbers them.
+    pub fn branches(&self) -> Vec<BigUint> {
+        let weights = self.unit_weights();
+        match &self.root.kind {
+            Kind::Alternation(children) => children
+                .iter()
+                .map(|child| child.counts.dot(&weights))
+                .collect(),
+            _ => vec![self.total()],
+        }
+    }
+
+    /// Draws a result of at least `min_length` bytes at random, returning
+    /// `None` if there aren't any. Each way of producing a result is as likely
+    /// as the next, so results an ambiguous pattern produces more than once
+    /// are likelier than the rest; see [`Space::sample_distinct`].
+    pub fn sample(&self, min_length: usize, rng: &mut impl Rng) -> Option<Vec<u8>> {
+        self.sample_with_branch(min_length, rng)
+            .map(|(_, result)| result)
+    }
+
+    /// Like [`Space::sample`], but also returns which top-level alternation
+    /// branch the result came from.
+    pub fn sample_with_branch(
+        &self,
+        min_length: usize,
+        rng: &mut impl Rng,
+    ) -> Option<(usize, Vec<u8>)> {
+        self.sample_weighted(min_length, &[], rng)
+    }
+
+    /// Like [`Space::sample_with_branch`], but each result of top-level
+    /// branch `i` is `weights[i]` times as likely to be drawn. Branches past
+    /// the end of `weights` weigh 1.
+    pub fn sample_weighted(
+        &self,
+        min_length: usize,
+        weights: &[u32],
+        rng: &mut impl Rng,
+    ) -> Option<(usize, Vec<u8>)> {
+        let children = match &self.root.kind {
+            Kind::Alternation(children) => children,
+            _ => std::slice::from_ref(&self.root),
+        };
+        // The weight of each branch's results of a length, in total.
+        let block = |branch: usize, length: usize| {
+            let weight = weights.get(branch).copied().unwrap_or(1);
+            children[branch]
+                .counts
+                .get(length)
+                .cloned()
+                .unwrap_or_default()
+                * weight
+        };
+        let (lengths, totals): (Vec<_>, Vec<_>) = self
+            .root
+            .counts
+            .iter()
+            .filter(|&(length, _)| length >= min_length)
+            .map(|(length, _)| {
+                let total: BigUint = (0..children.len()).map(|i| block(i, length)).sum();
+                (length, total)
+            })
+            .filter(|(_, total)| !total.is_zero())
+            .unzip();
+        if lengths.is_empty() {
+            return None;
+        }
+        let length = lengths[choose(&totals, rng)];
+        let branch = match &self.root.kind {
+            Kind::Alternation(_) => {
+                let blocks: Vec<_> = (0..children.len()).map(|i| block(i, length)).collect();
+                choose(&blocks, rng)
+            }
+            _ => 0,
+        };
+        let mut result = Vec::with_capacity(length);
+        sample_node(&children[branch], length, rng, &mut result);
+        Some((branch, result))
+    }
+
+    /// Every distinct result once, in an order shuffled by `rng`, without
+    /// keeping a set of the ones drawn.
+    pub fn shuffled(&self, rng: &mut impl Rng) -> impl Iterator<Item = Vec<u8>> + '_ {
+        let total = self.total();
+        let permutation = Permutation::new(total.clone(), rng);
+        std::iter::successors(Some(BigUint::zero()), |i| Some(i + 1u8))
+            .take_while(move |i| i < &total)
+            .filter_map(move |i| {
+                let index = permutation.get(&i);
+                let result = self.unrank(&index)?;
+                (self.rank(&result) == Some(index)).then_some(result)
+            })
+    }
+
+    /// Draws a distinct result uniformly at random, returning `None` if
+    /// there aren't any. Unlike [`Space::sample`], results an ambiguous
+    /// pattern produces more than once are no more likely than the rest:
+    /// an index is only kept if it is where its result is first produced.
+    pub fn sample_distinct(&self, rng: &mut impl Rng) -> Option<Vec<u8>> {
+        let total = self.total();
+        if total.is_zero() {
+            return None;
+        }
+        loop {
+            let index = random_below(&total, rng);
+            let result = self.unrank(&index)?;
+            if self.rank(&result) == Some(index) {
+                return Some(result);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_counts() {
+    let parse = |pattern| regex_syntax::Parser::new().parse(pattern).unwrap();
+    let space = Space::new(&parse("[a-c]{1,2}|é"), None).unwrap();
+    assert_eq!(
+        space.root.counts.iter().collect::<Vec<_>>(),
+        [(1, &BigUint::from(3u32)), (2, &BigUint::from(10u32))]
+    );
+    let space = Space::new(&parse("(a|bb)*"), Some(3)).unwrap();
+    assert_eq!(
+        space.root.counts.counts.iter().sum::<BigUint>(),
+        BigUint::from(7u32)
+    );
+    assert!(matches!(
+        Space::new(&parse("a*"), None),
+        Err(GenError::Unbounded)
+    ));
+}
+
+#[test]
+fn test_count_matches_generator() {
+    let parse = |pattern| regex_syntax::Parser::new().parse(pattern).unwrap();
+    for (pattern, max_length) in [
+        ("[a-c]{0,3}(x|yz)?", None),
+        ("(é|[0-9]{2})+", Some(4)),
+        ("(a?)*b", Some(4)),
+        ("[0-9é-ë]{3}", Some(5)),
+    ] {
+        let hir = parse(pattern);
+        let space = Space::new(&hir, max_length).unwrap();
+        let generated = crate::iterate_all(&hir, max_length, crate::ClassOrder::Asc).count();
+        assert_eq!(space.count(0), BigUint::from(generated), "{pattern}");
+    }
+    let space = Space::new(&parse("[a-z]{40}"), None).unwrap();
+    assert_eq!(space.count(0), BigUint::from(26u32).pow(40));
+}
+
+#[test]
+fn test_rank_unrank() {
+    let parse = |pattern| regex_syntax::Parser::new().parse(pattern).unwrap();
+    for (pattern, max_length, class_order) in [
+        ("[a-c][0-1]", None, ClassOrder::Asc),
+        ("[a-c]{0,3}(x|yz)?", None, ClassOrder::Desc),
+        ("(a|ab)(b|)", None, ClassOrder::Asc),
+        ("(é|[0-9]{2})+", Some(4), ClassOrder::Asc),
+        ("(a?)*b", Some(4), ClassOrder::Desc),
+        ("[0-9é-ë]{3}", Some(5), ClassOrder::Desc),
+    ] {
+        let hir = parse(pattern);
+        let space = Space::new(&hir, max_length)
+            .unwrap()
+            .class_order(class_order);
+        let generated: Vec<_> = crate::iterate_all(&hir, max_length, class_order).collect();
+        let mut first = std::collections::HashMap::new();
+        for (index, result) in generated.iter().enumerate() {
+            first.entry(result).or_insert(index);
+            let rank = BigUint::from(first[result]);
+            assert_eq!(
+                space.unrank(&index.into()).unwrap(),
+                result.as_slice(),
+                "{pattern}"
+            );
+            assert_eq!(space.rank(result), Some(rank), "{pattern}");
+        }
+        assert_eq!(space.unrank(&BigUint::from(generated.len())), None);
+    }
+    let space = Space::new(&parse("[a-c][0-1]"), None).unwrap();
+    assert_eq!(space.rank(b"d1"), None);
+    let space = Space::new(&parse("[a-z]{40}"), None).unwrap();
+    let last = BigUint::from(26u32).pow(40) - 1u32;
+    assert_eq!(space.unrank(&last).unwrap(), [b'z'; 40]);
+    assert_eq!(space.rank(&[b'z'; 40]), Some(last));
+}
+
+#[test]
+fn test_iterate_from() {
+    let parse = |pattern| regex_syntax::Parser::new().parse(pattern).unwrap();
+    for (pattern, max_length, class_order) in [
+        ("[a-c]{0,3}(x|yz)?", None, ClassOrder::Desc),
+        ("((a|ab)(b|))", None, ClassOrder::Asc),
+        ("(é|[0-9]{2})+|x", Some(4), ClassOrder::Asc),
+        ("(a?)*b", Some(4), ClassOrder::Desc),
+    ] {
+        let hir = parse(pattern);
+        let space = Space::new(&hir, max_length)
+            .unwrap()
+            .class_order(class_order);
+        let generated: Vec<_> = crate::iterate_branches(&hir, 0, max_length, class_order).collect();
+        let step = generated.len() / 20 + 1;
+        for start in (0..8.min(generated.len())).chain((0..=generated.len()).step_by(step)) {
+            let resumed: Vec<_> = space.iterate_branches_from(&hir, &start.into()).collect();
+            assert_eq!(resumed, generated[start..], "{pattern} from {start}");
+        }
+    }
+    // Seeking into a class takes no longer the more members it skips.
+    let hir = parse(r"[^a]{3}");
+    let space = Space::new(&hir, None).unwrap();
+    let last = space.total() - 1u8;
+    let resumed: Vec<_> = space.iterate_from(&hir, &last).collect();
+    assert_eq!(resumed, [Candidate::from(space.unrank(&last).unwrap())]);
+}
+
+#[test]
+fn test_min_length_indices() {
+    let hir = regex_syntax::Parser::new()
+        .parse("[a-c]{0,3}|x(yz)?")
+        .unwrap();
+    let space = Space::new(&hir, None).unwrap().min_length(2);
+    let generated: Vec<_> = crate::iterate_branches(&hir, 2, None, ClassOrder::Asc).collect();
+    assert_eq!(space.total(), BigUint::from(generated.len()));
+    for (index, (_, result)) in generated.iter().enumerate() {
+        assert_eq!(space.unrank(&index.into()).unwrap(), result.as_slice());
+        assert_eq!(space.rank(result), Some(index.into()));
+        let resumed: Vec<_> = space.iterate_branches_from(&hir, &index.into()).collect();
+        assert_eq!(resumed, generated[index..]);
+    }
+    assert_eq!(space.rank(b"a"), None);
+}
+
+#[test]
+fn test_skip_prefix() {
+    let parse = |pattern| regex_syntax::Parser::new().parse(pattern).unwrap();
+    for (pattern, max_length, class_order) in [
+        ("foo[0-9]{2}|ba[rz][0-9]|b", None, ClassOrder::Asc),
+        ("(?:x|yy)[a-c]{1,2}", None, ClassOrder::Desc),
+        ("[a-c][0-9]{2}", None, ClassOrder::Asc),
+        ("(?:ab|ac)+d|é[0-9]", Some(7), ClassOrder::Asc),
+        ("(?:xy){2,3}[0-1]|(?:)", None, ClassOrder::Desc),
+    ] {
+        let hir = parse(pattern);
+        let space = Space::new(&hir, max_length)
+            .unwrap()
+            .class_order(class_order)
+            .min_length(1);
+        let generated: Vec<_> = crate::iterate_all(&hir, max_length, class_order)
+            .filter(|x| !x.is_empty())
+            .collect();
+        for length in 1..=4 {
+            for (index, result) in generated.iter().enumerate() {
+                let end = space.skip_prefix(&index.into(), length);
+                assert!(end > index.into() && end <= generated.len().into());
+                let end = usize::try_from(end).unwrap();
+                let prefix = &result[..length.min(result.len())];
+                for other in &generated[index + 1..end] {
+                    match result.len() < length {
+                        true => assert_eq!(other, result, "{pattern} at {index}"),
+                        false => assert!(other.starts_with(prefix), "{pattern} at {index}"),
+                    }
+                }
+            }
+        }
+    }
+    let space = Space::new(&parse("foo[0-9]{3}|bar"), None).unwrap();
+    assert_eq!(
+        space.skip_prefix(&BigUint::zero(), 1),
+        BigUint::from(1000u32)
+    );
+    assert_eq!(space.skip_prefix(&5u8.into(), 4), BigUint::from(6u32));
+    let space = Space::new(&parse("x(?:a[0-9]{2}|b)"), None).unwrap();
+    assert_eq!(space.skip_prefix(&3u8.into(), 2), BigUint::from(100u32));
+}
+
+#[test]
+fn test_sample() {
+    use crate::sample::seeded_rng;
+
+    let parse = |pattern| regex_syntax::Parser::new().parse(pattern).unwrap();
+    let hir = parse("[a-c][0-1]|xyz|(?:)");
+    let space = Space::new(&hir, None).unwrap();
+    let mut rng = seeded_rng(Some("3"));
+    let mut hits = std::collections::HashMap::new();
+    for _ in 0..8000 {
+        let result = space.sample(0, &mut rng).unwrap();
+        assert!(crate::matcher::matches(&hir, &result, None));
+        *hits.entry(result).or_insert(0) += 1;
+    }
+    assert_eq!(hits.len(), 8);
+    assert!(hits.values().all(|&hit| (850..1150).contains(&hit)));
+    assert_eq!(space.sample(3, &mut rng).unwrap(), b"xyz");
+    assert_eq!(space.sample(4, &mut rng), None);
+
+    let space = Space::new(&parse("a(b|c)*"), Some(4)).unwrap();
+    for _ in 0..100 {
+        assert!(space.sample(0, &mut rng).unwrap().len() <= 4);
+    }
+
+    // abc is produced two of the four ways, so is drawn half the time.
+    let space = Space::new(&parse("(a|ab)(c|bc)"), None).unwrap();
+    let abc = (0..2000)
+        .filter(|_| space.sample(0, &mut rng).unwrap() == b"abc")
+        .count();
+    assert!((900..1100).contains(&abc));
+
+    let space = Space::new(&parse("[a-c][0-1]|xyz|(?:)"), None)
+        .unwrap()
+        .min_length(1);
+    assert_eq!(
+        space.lengths(),
+        [(2, BigUint::from(6u8)), (3, BigUint::from(1u8))]
+    );
+    assert_eq!(
+        space.branches(),
+        [BigUint::from(6u8), BigUint::from(1u8), BigUint::zero()]
+    );
+
+    // Seeded draws must not change between machines or releases.
+    let space = Space::new(&parse("[a-z]{8}"), None).unwrap();
+    let mut seeded = seeded_rng(Some("bug-1234"));
+    assert_eq!(space.sample(0, &mut seeded).unwrap(), b"ivjgtmyx");
+    assert_eq!(space.sample(0, &mut seeded).unwrap(), b"ycosfkvt");
+
+    let space = Space::new(&parse("a|a|a|b"), None).unwrap();
+    let distinct = (0..4000)
+        .filter(|_| space.sample_distinct(&mut rng).unwrap() == b"a")
+        .count();
+    assert!((1800..2200).contains(&distinct));
+    let space = Space::new(&parse("x|[0-9]"), None).unwrap();
+    let xs = (0..4000)
+        .filter(|_| space.sample_weighted(0, &[30], &mut rng).unwrap() == (0, b"x".to_vec()))
+        .count();
+    assert!((2800..3200).contains(&xs));
+
+    let space = Space::new(&parse("a|a|b|[cd]"), None).unwrap();
+    let mut shuffled: Vec<_> = space.shuffled(&mut rng).collect();
+    assert_eq!(shuffled.len(), 4);
+    shuffled.sort();
+    assert_eq!(shuffled, [b"a", b"b", b"c", b"d"]);
+    let space = Space::new(&parse("[ab]"), None).unwrap().min_length(2);
+    assert_eq!(space.sample_distinct(&mut rng), None);
+}
+
+#[test]
+fn test_deep_nesting() {
+    let depth = 3000;
+    let pattern = format!("{}[ab]{}", "(?:".repeat(depth), ")?".repeat(depth));
+    let pattern = crate::Pattern::new(&pattern).unwrap();
+    let space = pattern.space().unwrap();
+    assert_eq!(space.total(), BigUint::from(depth + 2));
+    let last = BigUint::from(depth + 1);
+    assert_eq!(space.unrank(&last).unwrap(), b"b");
+    assert_eq!(space.rank(b"b"), Some(last.clone()));
+    let resumed: Vec<_> = space.iterate_from(pattern.hir(), &last).collect();
+    assert_eq!(resumed, [Candidate::from(&b"b"[..])]);
+    let mut rng = crate::sample::seeded_rng(Some("1"));
+    assert!(space.sample(1, &mut rng).is_some());
+    let skipped = space.skip_prefix(&BigUint::from(depth), 1);
+    assert_eq!(skipped, last);
+}
@@ -0,0 +1,55 @@
+//! `--stats`, a summary of a run printed to stderr once it ends.
+
+use std::{cell::RefCell, fmt::Write, time::Duration};
+
+/// How many results each filter dropped, in the order the filters first
+/// dropped one.
+#[derive(Default)]
+pub struct Drops(RefCell<Vec<(&'static str, u64)>>);
+
+impl Drops {
+    /// Passes `keep` through, counting a drop against `filter` if it's false.
+    pub fn keep(&self, filter: &'static str, keep: bool) -> bool {
+        if !keep {
+            let mut drops = self.0.borrow_mut();
+            match drops.iter_mut().find(|(name, _)| *name == filter) {
+                Some((_, count)) => *count += 1,
+                None => drops.push((filter, 1)),
+            }
+        }
+        keep
+    }
+}
+
+pub fn summary(emitted: usize, bytes: u64, elapsed: Duration, drops: &Drops) -> String {
+    let seconds = elapsed.as_secs_f64().max(1e-9);
+    let mut summary = String::new();
+    writeln!(summary, "results: {emitted}").unwrap();
+    writeln!(summary, "bytes: {bytes}").unwrap();
+    writeln!(summary, "time: {:.3}s", elapsed.as_secs_f64()).unwrap();
+    writeln!(
+        summary,
+        "throughput: {:.0} results/s, {:.1} MiB/s",
+        emitted as f64 / seconds,
+        bytes as f64 / (1 << 20) as f64 / seconds
+    )
+    .unwrap();
+    for (filter, count) in drops.0.borrow().iter() {
+        writeln!(summary, "dropped by {filter}: {count}").unwrap();
+    }
+    summary
+}
+
+#[test]
+fn test_summary() {
+    let drops = Drops::default();
+    assert!(drops.keep("--exclude", true));
+    assert!(!drops.keep("--exclude", false));
+    assert!(!drops.keep("--require", false));
+    assert!(!drops.keep("--exclude", false));
+    assert_eq!(
+        summary(10, 1 << 20, Duration::from_secs(2), &drops),
+        "results: 10\nbytes: 1048576\ntime: 2.000s\nthroughput: 5 results/s, 0.5 MiB/s\n\
+         dropped by --exclude: 2\ndropped by --require: 1\n"
+    );
+}
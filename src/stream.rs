@@ -0,0 +1,71 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{Stream, stream};
+use regex_syntax::hir::Hir;
+
+use crate::{ClassOrder, iterate_all};
+
+pub struct StreamOptions {
+    pub max_length: Option<usize>,
+    pub class_order: ClassOrder,
+    /// Number of results produced between each yield back to the executor
+    pub chunk_size: usize,
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        Self {
+            max_length: None,
+            class_order: ClassOrder::Asc,
+            chunk_size: 1024,
+        }
+    }
+}
+
+/// Returns `Pending` once, so other tasks get a chance to run.
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Generates the results of `hir` as a stream, yielding to the executor
+/// after every `chunk_size` results.
+pub fn generate_stream(hir: &Hir, options: StreamOptions) -> impl Stream<Item = Vec<u8>> + '_ {
+    let chunk_size = options.chunk_size.max(1);
+    let iter = iterate_all(hir, options.max_length, options.class_order);
+    stream::unfold((iter, 0usize), move |(mut iter, emitted)| async move {
+        if emitted > 0 && emitted.is_multiple_of(chunk_size) {
+            YieldNow(false).await;
+        }
+        iter.next()
+            .map(|result| (result.to_vec(), (iter, emitted + 1)))
+    })
+}
+
+#[test]
+fn test_generate_stream() {
+    use futures::StreamExt;
+
+    let hir = regex_syntax::Parser::new().parse("[a-c]{2}").unwrap();
+    let options = StreamOptions {
+        chunk_size: 2,
+        ..Default::default()
+    };
+    let results: Vec<_> = futures::executor::block_on(generate_stream(&hir, options).collect());
+    assert_eq!(results.len(), 9);
+    assert_eq!(results[1], b"ba");
+}
@@ -0,0 +1,355 @@
+use std::{collections::HashSet, fs};
+
+use crate::error::GenError;
+
+/// A named list of whole strings, given on the command line as `NAME=foo,bar,baz`.
+#[derive(Clone, Debug)]
+pub struct NamedSet {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+fn check_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        Err(format!(
+            "set name {name:?} must be non-empty and only contain letters, digits and underscores"
+        ))?
+    }
+    Ok(())
+}
+
+pub fn parse_set(definition: &str) -> Result<NamedSet, String> {
+    let (name, values) = definition
+        .split_once('=')
+        .ok_or("expected NAME=value,value,...")?;
+    check_name(name)?;
+    Ok(NamedSet {
+        name: name.to_string(),
+        values: values.split(',').map(String::from).collect(),
+    })
+}
+
+/// Reads a set from a dictionary file given as `NAME=PATH`, one value per
+/// line. Blank lines are skipped.
+pub fn parse_wordlist(definition: &str) -> Result<NamedSet, String> {
+    let (name, path) = definition.split_once('=').ok_or("expected NAME=PATH")?;
+    check_name(name)?;
+    let contents = fs::read_to_string(path).map_err(|error| format!("{path}: {error}"))?;
+    Ok(NamedSet {
+        name: name.to_string(),
+        values: contents
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect(),
+    })
+}
+
+/// A pattern matching exactly the given strings.
+pub fn alternation(values: &[String]) -> String {
+    let alternatives: Vec<_> = values
+        .iter()
+        .map(|value| regex_syntax::escape(value))
+        .collect();
+    format!("(?:{})", alternatives.join("|"))
+}
+
+/// A class matching exactly the given characters.
+pub fn class(members: &str) -> String {
+    let members: String = members
+        .chars()
+        .map(|c| regex_syntax::escape(c.encode_utf8(&mut [0; 4])))
+        .collect();
+    format!("[{members}]")
+}
+
+/// Replaces every `{{NAME}}` in `pattern` with an alternation of the set's
+/// values, escaped so they match literally.
+pub fn expand_sets(pattern: &str, sets: &[NamedSet]) -> Result<String, GenError> {
+    let mut expanded = String::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        let name = &rest[start + 2..start + end];
+        let set = sets
+            .iter()
+            .find(|set| set.name == name)
+            .ok_or_else(|| GenError::UnknownSet(name.to_string()))?;
+        expanded += &rest[..start];
+        expanded += &alternation(&set.values);
+        rest = &rest[start + end + 2..];
+    }
+    expanded += rest;
+    Ok(expanded)
+}
+
+/// Alternatives of digit ranges matching each number from `low` to `high`
+/// once, both written with the same number of digits, in ascending order.
+fn digit_ranges(low: &[u8], high: &[u8]) -> Vec<String> {
+    let Some((&first, low_rest)) = low.split_first() else {
+        return vec![String::new()];
+    };
+    let (&last, high_rest) = high.split_first().unwrap();
+    let prefixed = |digit: u8, pieces: Vec<String>| {
+        pieces
+            .into_iter()
+            .map(move |piece| format!("{}{piece}", digit as char))
+    };
+    if first == last {
+        return prefixed(first, digit_ranges(low_rest, high_rest)).collect();
+    }
+    let (zeros, nines) = (vec![b'0'; low_rest.len()], vec![b'9'; low_rest.len()]);
+    let mut pieces = Vec::new();
+    // Whole runs of numbers with the same first digit come between those
+    // that only run from `low` or up to `high`.
+    let mut whole = first..=last;
+    if low_rest != zeros {
+        pieces.extend(prefixed(first, digit_ranges(low_rest, &nines)));
+        whole = first + 1..=*whole.end();
+    }
+    if high_rest != nines {
+        whole = *whole.start()..=last - 1;
+    }
+    if !whole.is_empty() {
+        let digits = match whole.start() == whole.end() {
+            true => (*whole.start() as char).to_string(),
+            false => format!("[{}-{}]", *whole.start() as char, *whole.end() as char),
+        };
+        let any = match low_rest.len() {
+            0 => String::new(),
+            1 => "[0-9]".to_string(),
+            n => format!("[0-9]{{{n}}}"),
+        };
+        pieces.push(digits + &any);
+    }
+    if high_rest != nines {
+        pieces.extend(prefixed(last, digit_ranges(&zeros, high_rest)));
+    }
+    pieces
+}
+
+/// Replaces every `(?N:FIRST-LAST)` in `pattern` with a pattern of digit
+/// ranges matching each number from FIRST to LAST once, shorter numbers
+/// first. Numbers of the same length vary in their first digit fastest, so
+/// only come out in numeric order when sorted. If FIRST is written with a
+/// leading zero, as in `(?N:01-31)`, every number is padded to the width of
+/// LAST.
+pub fn expand_ranges(pattern: &str) -> Result<String, GenError> {
+    let mut expanded = String::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find("(?N:") {
+        let range_start = start + "(?N:".len();
+        let end = rest[range_start..]
+            .find(')')
+            .ok_or_else(|| GenError::Range(format!("{:?} has no closing )", &rest[start..])))?;
+        let range = &rest[range_start..range_start + end];
+        let (first, last) = range
+            .split_once('-')
+            .ok_or_else(|| GenError::Range(format!("expected FIRST-LAST, got {range:?}")))?;
+        let number = |number: &str| {
+            number
+                .parse::<u64>()
+                .ok()
+                .filter(|_| number.chars().all(|c| c.is_ascii_digit()))
+                .ok_or_else(|| GenError::Range(format!("{number:?} isn't a number")))
+        };
+        let (low, high) = (number(first)?, number(last)?);
+        if low > high {
+            Err(GenError::Range(format!("{first} comes after {last}")))?
+        }
+        let width = match first.len() > 1 && first.starts_with('0') {
+            true => last.len().max(first.len()),
+            false => 0,
+        };
+        let (low, high) = (format!("{low:0width$}"), format!("{high:0width$}"));
+        // Unpadded numbers are split into runs of the same length.
+        let mut pieces = Vec::new();
+        for length in low.len()..=high.len() {
+            let shortest = match length {
+                1 => "0".to_string(),
+                _ => format!("1{}", "0".repeat(length - 1)),
+            };
+            let from = match length == low.len() {
+                true => low.clone(),
+                false => shortest,
+            };
+            let to = match length == high.len() {
+                true => high.clone(),
+                false => "9".repeat(length),
+            };
+            pieces.extend(digit_ranges(from.as_bytes(), to.as_bytes()));
+        }
+        expanded += &rest[..start];
+        expanded += &format!("(?:{})", pieces.join("|"));
+        rest = &rest[range_start + end + 1..];
+    }
+    expanded += rest;
+    Ok(expanded)
+}
+
+fn is_leap(year: u32) -> bool {
+    year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400))
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Writes a date in `format`, where YYYY and YY are the year, MM and DD the
+/// zero-padded month and day, M and D the unpadded ones, and anything else
+/// is literal.
+fn format_date(format: &str, year: u32, month: u32, day: u32) -> String {
+    let mut formatted = String::new();
+    let mut rest = format;
+    while let Some(c) = rest.chars().next() {
+        let (field, length) = match rest {
+            _ if rest.starts_with("YYYY") => (format!("{year:04}"), 4),
+            _ if rest.starts_with("YY") => (format!("{:02}", year % 100), 2),
+            _ if rest.starts_with("MM") => (format!("{month:02}"), 2),
+            _ if rest.starts_with("DD") => (format!("{day:02}"), 2),
+            _ if rest.starts_with('M') => (month.to_string(), 1),
+            _ if rest.starts_with('D') => (day.to_string(), 1),
+            _ => (c.to_string(), c.len_utf8()),
+        };
+        formatted += &field;
+        rest = &rest[length..];
+    }
+    formatted
+}
+
+/// Replaces every `{date:FORMAT:FIRST-LAST}` in `pattern` with an alternation
+/// of each calendar date from the start of year FIRST to the end of year
+/// LAST, in order and written in FORMAT, such as `DDMMYYYY` or `M/D/YY`. A
+/// date that's written the same as an earlier one is left out.
+pub fn expand_dates(pattern: &str) -> Result<String, GenError> {
+    let mut expanded = String::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find("{date:") {
+        let token_start = start + "{date:".len();
+        let end = rest[token_start..]
+            .find('}')
+            .ok_or_else(|| GenError::Date(format!("{:?} has no closing }}", &rest[start..])))?;
+        let token = &rest[token_start..token_start + end];
+        let (format, years) = token
+            .rsplit_once(':')
+            .ok_or_else(|| GenError::Date(format!("expected FORMAT:FIRST-LAST, got {token:?}")))?;
+        let (first, last) = years.split_once('-').unwrap_or((years, years));
+        let year = |year: &str| {
+            year.parse::<u32>()
+                .map_err(|_| GenError::Date(format!("{year:?} isn't a year")))
+        };
+        let (first, last) = (year(first)?, year(last)?);
+        if first > last {
+            Err(GenError::Date(format!("{first} comes after {last}")))?
+        }
+        let mut seen = HashSet::new();
+        let mut dates = Vec::new();
+        for year in first..=last {
+            for month in 1..=12 {
+                for day in 1..=days_in_month(year, month) {
+                    let date = format_date(format, year, month, day);
+                    if seen.insert(date.clone()) {
+                        dates.push(regex_syntax::escape(&date));
+                    }
+                }
+            }
+        }
+        expanded += &rest[..start];
+        expanded += &format!("(?:{})", dates.join("|"));
+        rest = &rest[token_start + end + 1..];
+    }
+    expanded += rest;
+    Ok(expanded)
+}
+
+#[test]
+fn test_expand_dates() {
+    let dates = |pattern| {
+        let expanded = expand_dates(pattern).unwrap();
+        let alternatives = expanded
+            .strip_prefix("(?:")
+            .and_then(|expanded| expanded.strip_suffix(')'))
+            .unwrap();
+        alternatives
+            .split('|')
+            .map(String::from)
+            .collect::<Vec<_>>()
+    };
+    let leap = dates("{date:DDMMYYYY:2000}");
+    assert_eq!(leap.len(), 366);
+    assert_eq!(leap[0], "01012000");
+    assert!(leap.contains(&"29022000".to_string()));
+    assert_eq!(dates("{date:YYMMDD:1900}").len(), 365);
+    assert_eq!(dates("{date:M.D.YY:1999-2000}")[0], "1\\.1\\.99");
+    // 1/11 and 11/1 are both written 111, and so on up to 1/29 and 12/9.
+    assert_eq!(dates("{date:MD:2001}").len(), 365 - 18);
+    assert!(expand_dates("{date:DDMM:2010-1980}").is_err());
+    assert!(expand_dates("{date:DDMM}").is_err());
+}
+
+#[test]
+fn test_expand_ranges() {
+    assert_eq!(expand_ranges("a(?N:8-11)").unwrap(), "a(?:[8-9]|1[0-1])");
+    assert_eq!(
+        expand_ranges("(?N:01-03)/(?N:1-2)").unwrap(),
+        "(?:0[1-3])/(?:[1-2])"
+    );
+    assert_eq!(
+        expand_ranges("(?N:123-456)").unwrap(),
+        "(?:12[3-9]|1[3-9][0-9]|[2-3][0-9]{2}|4[0-4][0-9]|45[0-6])"
+    );
+    // Each number in the range is matched once, and nothing else is.
+    for (range, width) in [("0-1000", 0), ("7-93", 0), ("0042-9000", 4), ("5-5", 0)] {
+        let (low, high) = range.split_once('-').unwrap();
+        let (low, high): (u32, u32) = (low.parse().unwrap(), high.parse().unwrap());
+        let pattern = crate::Pattern::new(&expand_ranges(&format!("(?N:{range})")).unwrap());
+        let mut results: Vec<_> = pattern.unwrap().iter().collect();
+        results.sort_by_key(|result| result.parse::<u32>().unwrap());
+        let expected: Vec<_> = (low..=high).map(|n| format!("{n:0width$}")).collect();
+        assert_eq!(results, expected, "{range}");
+    }
+    let pattern = crate::Pattern::new(&expand_ranges("(?N:0-9999999)").unwrap()).unwrap();
+    assert_eq!(pattern.space().unwrap().total(), 10_000_000u32.into());
+    assert_eq!(expand_ranges("(?:1-2)").unwrap(), "(?:1-2)");
+    assert!(expand_ranges("(?N:3-1)").is_err());
+    assert!(expand_ranges("(?N:a-z)").is_err());
+    assert!(expand_ranges("(?N:1-2").is_err());
+}
+
+#[test]
+fn test_expand_sets() {
+    let sets = [
+        parse_set("month=jan,feb,mar").unwrap(),
+        parse_set("sym=!,.").unwrap(),
+    ];
+    assert_eq!(
+        expand_sets("{{month}}[0-9]{2}{{sym}}", &sets).unwrap(),
+        "(?:jan|feb|mar)[0-9]{2}(?:!|\\.)"
+    );
+    assert!(matches!(
+        expand_sets("{{day}}", &sets),
+        Err(GenError::UnknownSet(name)) if name == "day"
+    ));
+    assert_eq!(class("a-]^é"), "[a\\-\\]\\^é]");
+    assert!(parse_set("no-equals").is_err());
+    assert!(parse_set("bad name=x").is_err());
+}
+
+#[test]
+fn test_parse_wordlist() {
+    let path = std::env::temp_dir().join(format!("passwd-gen-wordlist-{}", std::process::id()));
+    fs::write(&path, "alice\r\nbob\n\ncarol\n").unwrap();
+    let set = parse_wordlist(&format!("names={}", path.display())).unwrap();
+    assert_eq!(set.name, "names");
+    assert_eq!(set.values, ["alice", "bob", "carol"]);
+    fs::remove_file(&path).unwrap();
+    assert!(parse_wordlist("names=/nonexistent/words.txt").is_err());
+    assert!(parse_wordlist("no-equals").is_err());
+}
@@ -0,0 +1,790 @@
+//! The generator behind [`crate::iterate_all`]: a cursor per sub-expression,
+//! advanced by an explicit stack so that deeply nested patterns can't
+//! overflow the call stack.
+
+use std::convert::Infallible;
+
+use regex_syntax::hir::{self, Class, Hir, HirKind, Visitor};
+
+use crate::{Candidate, ClassOrder, max_repeats};
+
+enum Op {
+    Nothing,
+    Literal(Vec<u8>),
+    /// Ranges in enumeration order.
+    Class {
+        ranges: Vec<(u32, u32)>,
+        unicode: bool,
+    },
+    Alternation(Vec<usize>),
+    Concat(Vec<usize>),
+    Repetition {
+        sub: usize,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+/// Flattens a pattern into ops, children before parents, without recursing.
+struct Compiler {
+    ops: Vec<Op>,
+    /// The longest result of each op, if it has one.
+    max_lens: Vec<Option<usize>>,
+    stack: Vec<usize>,
+    max_length: Option<usize>,
+    class_order: ClassOrder,
+}
+
+impl Compiler {
+    fn push(&mut self, op: Op, max_len: Option<usize>) {
+        self.ops.push(op);
+        self.max_lens.push(max_len);
+        self.stack.push(self.ops.len() - 1);
+    }
+
+    fn pop(&mut self, count: usize) -> Vec<usize> {
+        self.stack.split_off(self.stack.len() - count)
+    }
+}
+
+impl Visitor for Compiler {
+    type Output = (Vec<Op>, Vec<Option<usize>>);
+    type Err = Infallible;
+
+    fn finish(self) -> Result<Self::Output, Infallible> {
+        Ok((self.ops, self.max_lens))
+    }
+
+    fn visit_post(&mut self, hir: &Hir) -> Result<(), Infallible> {
+        let op = match hir.kind() {
+            HirKind::Empty => Op::Literal(Vec::new()),
+            HirKind::Look(_) => Op::Nothing,
+            HirKind::Literal(literal) => Op::Literal(literal.0.to_vec()),
+            HirKind::Class(class) => {
+                let (mut ranges, unicode): (Vec<_>, _) = match class {
+                    // Split around the surrogates, which aren't chars.
+                    Class::Unicode(class) => (
+                        class
+                            .ranges()
+                            .iter()
+                            .flat_map(|r| {
+                                let (start, end) = (r.start() as u32, r.end() as u32);
+                                [(start, end.min(0xd7ff)), (start.max(0xe000), end)]
+                                    .into_iter()
+                                    .filter(|(start, end)| start <= end)
+                            })
+                            .collect(),
+                        true,
+                    ),
+                    Class::Bytes(class) => (
+                        class
+                            .ranges()
+                            .iter()
+                            .map(|r| (r.start() as u32, r.end() as u32))
+                            .collect(),
+                        false,
+                    ),
+                };
+                if self.class_order == ClassOrder::Desc {
+                    ranges.reverse();
+                }
+                Op::Class { ranges, unicode }
+            }
+            HirKind::Capture(_) => return Ok(()),
+            HirKind::Repetition(repetition) => Op::Repetition {
+                sub: self.pop(1)[0],
+                min: repetition.min as usize,
+                max: max_repeats(repetition, self.max_length),
+            },
+            HirKind::Concat(hirs) => Op::Concat(self.pop(hirs.len())),
+            HirKind::Alternation(hirs) => Op::Alternation(self.pop(hirs.len())),
+        };
+        self.push(op, hir.properties().maximum_len());
+        Ok(())
+    }
+}
+
+/// The current position of one op's cursor. Children are indices into
+/// [`Walker::cursors`].
+enum State {
+    Leaf,
+    Class {
+        range: usize,
+        offset: u32,
+    },
+    Alternation {
+        branch: usize,
+        child: usize,
+    },
+    Concat {
+        children: Vec<usize>,
+    },
+    Repetition {
+        repeats: usize,
+        children: Vec<usize>,
+    },
+}
+
+struct Cursor {
+    op: usize,
+    /// The longest the rest of the result around this cursor can be.
+    rest: Option<usize>,
+    state: State,
+}
+
+/// A suspended step of [`Walker::run`], resumed with the result of the
+/// step pushed after it.
+enum Frame {
+    /// Moves a fresh cursor to its first result.
+    First(usize),
+    /// Moves a cursor to its next result.
+    Next(usize),
+    /// The alternation's current branch has found its first result, or has none.
+    BranchFirst(usize),
+    /// The alternation's current branch has advanced, or is exhausted.
+    BranchNext(usize),
+    /// Part `j` of a concatenation or repetition has found its first result.
+    PartFirst { cursor: usize, j: usize },
+    /// Part `j` has advanced, or is exhausted and must be reset.
+    PartNext { cursor: usize, j: usize },
+    /// Part `j` has been reset after running out, so part `j + 1` goes next.
+    PartReset { cursor: usize, j: usize },
+}
+
+pub(crate) struct Walker {
+    ops: Vec<Op>,
+    max_lens: Vec<Option<usize>>,
+    cursors: Vec<Cursor>,
+    free: Vec<usize>,
+    stack: Vec<Frame>,
+    /// Scratch space for [`Walker::render`].
+    pending: Vec<usize>,
+    min_length: usize,
+    max_length: Option<usize>,
+    class_order: ClassOrder,
+    reverse: bool,
+    started: bool,
+    /// Whether the cursors were put on a result by [`Walker::seek`], which
+    /// is yielded before moving on.
+    seeked: bool,
+    done: bool,
+}
+
+/// A decision made along the way to one result, in the order
+/// [`Walker::seek`] takes them.
+pub(crate) enum Choice {
+    Branch(usize),
+    Repeats(usize),
+    Member(u32),
+}
+
+impl Walker {
+    pub(crate) fn new(hir: &Hir, max_length: Option<usize>, class_order: ClassOrder) -> Self {
+        let Ok((ops, max_lens)) = hir::visit(
+            hir,
+            Compiler {
+                ops: Vec::new(),
+                max_lens: Vec::new(),
+                stack: Vec::new(),
+                max_length,
+                class_order,
+            },
+        );
+        let root = ops.len() - 1;
+        let mut walker = Self {
+            ops,
+            max_lens,
+            cursors: Vec::new(),
+            free: Vec::new(),
+            stack: Vec::new(),
+            pending: Vec::new(),
+            min_length: 0,
+            max_length,
+            class_order,
+            reverse: false,
+            started: false,
+            seeked: false,
+            done: false,
+        };
+        walker.cursor(root, Some(0));
+        walker
+    }
+
+    /// Only yield results of at least `min_length` bytes, skipping branches
+    /// and repeat counts that can only produce shorter ones.
+    pub(crate) fn min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    /// Yields the results in the opposite order: branches and class members
+    /// backwards, and repetitions from the most copies down. Every
+    /// repetition must be bounded, by the pattern or by the max length.
+    pub(crate) fn reverse(mut self) -> Self {
+        for op in &mut self.ops {
+            match op {
+                Op::Alternation(branches) => branches.reverse(),
+                Op::Class { ranges, .. } => ranges.reverse(),
+                Op::Repetition { max: None, .. } => panic!("can't reverse an unbounded repetition"),
+                _ => {}
+            }
+        }
+        self.class_order = match self.class_order {
+            ClassOrder::Asc => ClassOrder::Desc,
+            ClassOrder::Desc => ClassOrder::Asc,
+        };
+        self.reverse = !self.reverse;
+        self
+    }
+
+    /// Puts the cursors on the result that `choices` lead to, so it is the
+    /// next one yielded. The choices come depth first from the root, with
+    /// the parts of a sequence taken last to first: a branch for each
+    /// alternation, a number of copies for each repetition and a member for
+    /// each class. Only works forwards.
+    pub(crate) fn seek(mut self, choices: impl IntoIterator<Item = Choice>) -> Self {
+        assert!(!self.reverse, "can't seek backwards");
+        let mut choices = choices.into_iter();
+        let mut choice = || choices.next().expect("too few choices for the pattern");
+        let mut pending = vec![0];
+        while let Some(id) = pending.pop() {
+            let state = match &self.ops[self.cursors[id].op] {
+                Op::Nothing => unreachable!("no result goes through nothing"),
+                Op::Literal(_) => State::Leaf,
+                Op::Class { ranges, .. } => {
+                    let Choice::Member(member) = choice() else {
+                        unreachable!("classes take a member")
+                    };
+                    let range = ranges
+                        .iter()
+                        .position(|&(start, end)| (start..=end).contains(&member))
+                        .expect("the member is in the class");
+                    let (start, end) = ranges[range];
+                    let offset = match self.class_order {
+                        ClassOrder::Asc => member - start,
+                        ClassOrder::Desc => end - member,
+                    };
+                    State::Class { range, offset }
+                }
+                Op::Alternation(branches) => {
+                    let Choice::Branch(branch) = choice() else {
+                        unreachable!("alternations take a branch")
+                    };
+                    let op = branches[branch];
+                    let child = self.cursor(op, self.cursors[id].rest);
+                    pending.push(child);
+                    State::Alternation { branch, child }
+                }
+                Op::Concat(ops) => {
+                    let children = self.concat_children(id, &ops.clone());
+                    pending.extend(&children);
+                    State::Concat { children }
+                }
+                Op::Repetition { .. } => {
+                    let Choice::Repeats(repeats) = choice() else {
+                        unreachable!("repetitions take a number of copies")
+                    };
+                    let children = self.copies(id, repeats);
+                    pending.extend(&children);
+                    State::Repetition { repeats, children }
+                }
+            };
+            self.cursors[id].state = state;
+        }
+        self.started = true;
+        self.seeked = true;
+        self
+    }
+
+    fn cursor(&mut self, op: usize, rest: Option<usize>) -> usize {
+        let cursor = Cursor {
+            op,
+            rest,
+            state: State::Leaf,
+        };
+        match self.free.pop() {
+            Some(id) => {
+                self.cursors[id] = cursor;
+                id
+            }
+            None => {
+                self.cursors.push(cursor);
+                self.cursors.len() - 1
+            }
+        }
+    }
+
+    /// Frees every cursor below `id`, leaving it ready for [`Frame::First`].
+    fn clear(&mut self, id: usize) {
+        let mut pending = vec![id];
+        while let Some(current) = pending.pop() {
+            match std::mem::replace(&mut self.cursors[current].state, State::Leaf) {
+                State::Alternation { child, .. } => pending.push(child),
+                State::Concat { children } | State::Repetition { children, .. } => {
+                    pending.extend(children)
+                }
+                State::Leaf | State::Class { .. } => {}
+            }
+            if current != id {
+                self.free.push(current);
+            }
+        }
+    }
+
+    fn release(&mut self, id: usize) {
+        self.clear(id);
+        self.free.push(id);
+    }
+
+    fn parts(&self, id: usize) -> &[usize] {
+        match &self.cursors[id].state {
+            State::Concat { children } | State::Repetition { children, .. } => children,
+            _ => unreachable!("only sequences have parts"),
+        }
+    }
+
+    /// Whether every result of `op` would leave the whole result shorter
+    /// than the min length, given `rest` bytes around it at most.
+    fn too_short(&self, op: usize, rest: Option<usize>) -> bool {
+        match (self.max_lens[op], rest) {
+            (Some(max_len), Some(rest)) => max_len.saturating_add(rest) < self.min_length,
+            _ => false,
+        }
+    }
+
+    /// The fewest copies a repetition needs to reach the min length, or
+    /// `None` if no number of copies will.
+    fn repeats_needed(&self, id: usize) -> Option<usize> {
+        let Op::Repetition { sub, .. } = self.ops[self.cursors[id].op] else {
+            unreachable!("only repetitions have repeats")
+        };
+        let (Some(max_len), Some(rest)) = (self.max_lens[sub], self.cursors[id].rest) else {
+            return Some(0);
+        };
+        let missing = self.min_length.saturating_sub(rest);
+        match max_len {
+            0 if missing > 0 => None,
+            0 => Some(0),
+            max_len => Some(missing.div_ceil(max_len)),
+        }
+    }
+
+    // The helpers below return `Some(found)` when they finish immediately,
+    // or `None` after pushing the steps that will set it.
+
+    /// Points an alternation at branch `branch`, or reports that there are
+    /// no branches left.
+    fn try_branch(&mut self, id: usize, branch: usize) -> Option<bool> {
+        let Op::Alternation(branches) = &self.ops[self.cursors[id].op] else {
+            unreachable!("only alternations have branches")
+        };
+        let rest = self.cursors[id].rest;
+        let Some((branch, &op)) = branches
+            .iter()
+            .enumerate()
+            .skip(branch)
+            .find(|&(_, &op)| !self.too_short(op, rest))
+        else {
+            self.cursors[id].state = State::Leaf;
+            return Some(false);
+        };
+        let child = self.cursor(op, rest);
+        self.cursors[id].state = State::Alternation { branch, child };
+        self.stack.push(Frame::BranchFirst(id));
+        self.stack.push(Frame::First(child));
+        None
+    }
+
+    /// Starts a repetition at `repeats` copies, or reports that there are
+    /// no more to try.
+    fn try_repeats(&mut self, id: usize, repeats: usize) -> Option<bool> {
+        let Op::Repetition { min, max, .. } = self.ops[self.cursors[id].op] else {
+            unreachable!("only repetitions have repeats")
+        };
+        let Some(needed) = self.repeats_needed(id) else {
+            return Some(false);
+        };
+        // Forwards, skip ahead to the copies needed; backwards, stop there.
+        let repeats = if self.reverse {
+            repeats
+        } else {
+            repeats.max(needed)
+        };
+        if repeats < min.max(needed) || max.is_some_and(|max| repeats > max) {
+            return Some(false);
+        }
+        let children = self.copies(id, repeats);
+        self.cursors[id].state = State::Repetition { repeats, children };
+        self.start_parts(id)
+    }
+
+    /// Fresh cursors for `repeats` copies of a repetition's sub-expression.
+    fn copies(&mut self, id: usize, repeats: usize) -> Vec<usize> {
+        let Op::Repetition { sub, .. } = self.ops[self.cursors[id].op] else {
+            unreachable!("only repetitions have copies")
+        };
+        // Each copy is surrounded by the others as well as the repetition's
+        // own surroundings.
+        let rest = self.max_lens[sub].and_then(|max_len| {
+            let others = max_len.checked_mul(repeats.saturating_sub(1))?;
+            others.checked_add(self.cursors[id].rest?)
+        });
+        (0..repeats).map(|_| self.cursor(sub, rest)).collect()
+    }
+
+    /// Fresh cursors for the parts of a concatenation.
+    fn concat_children(&mut self, id: usize, ops: &[usize]) -> Vec<usize> {
+        let rests = self.part_rests(id, ops);
+        ops.iter()
+            .zip(rests)
+            .map(|(&op, rest)| self.cursor(op, rest))
+            .collect()
+    }
+
+    fn start_parts(&mut self, id: usize) -> Option<bool> {
+        let Some(&first) = self.parts(id).first() else {
+            return Some(true);
+        };
+        self.stack.push(Frame::PartFirst { cursor: id, j: 0 });
+        self.stack.push(Frame::First(first));
+        None
+    }
+
+    /// Runs `frame` to completion, returning whether the cursor it names
+    /// has a result.
+    fn run(&mut self, frame: Frame) -> bool {
+        self.stack.push(frame);
+        let mut found = false;
+        while let Some(frame) = self.stack.pop() {
+            let result = match frame {
+                Frame::First(id) => match &self.ops[self.cursors[id].op] {
+                    Op::Nothing => Some(false),
+                    Op::Literal(_) => Some(true),
+                    Op::Class { ranges, .. } => {
+                        let any = !ranges.is_empty();
+                        self.cursors[id].state = State::Class {
+                            range: 0,
+                            offset: 0,
+                        };
+                        Some(any)
+                    }
+                    Op::Alternation(_) => self.try_branch(id, 0),
+                    Op::Concat(ops) => {
+                        let children = self.concat_children(id, &ops.clone());
+                        self.cursors[id].state = State::Concat { children };
+                        self.start_parts(id)
+                    }
+                    &Op::Repetition { min, max, .. } => {
+                        let repeats = if self.reverse { max.unwrap() } else { min };
+                        self.try_repeats(id, repeats)
+                    }
+                },
+                Frame::Next(id) => match &mut self.cursors[id] {
+                    Cursor {
+                        state: State::Leaf, ..
+                    } => Some(false),
+                    Cursor {
+                        op,
+                        state: State::Class { range, offset },
+                        ..
+                    } => {
+                        let Op::Class { ranges, .. } = &self.ops[*op] else {
+                            unreachable!("class cursors belong to classes")
+                        };
+                        let (start, end) = ranges[*range];
+                        if *offset < end - start {
+                            *offset += 1;
+                            Some(true)
+                        } else {
+                            *range += 1;
+                            *offset = 0;
+                            Some(*range < ranges.len())
+                        }
+                    }
+                    Cursor {
+                        state: State::Alternation { child, .. },
+                        ..
+                    } => {
+                        let child = *child;
+                        self.stack.push(Frame::BranchNext(id));
+                        self.stack.push(Frame::Next(child));
+                        None
+                    }
+                    Cursor {
+                        state: State::Concat { children } | State::Repetition { children, .. },
+                        ..
+                    } => match children.first() {
+                        Some(&first) => {
+                            self.stack.push(Frame::PartNext { cursor: id, j: 0 });
+                            self.stack.push(Frame::Next(first));
+                            None
+                        }
+                        None => self.exhausted(id),
+                    },
+                },
+                Frame::BranchFirst(_) | Frame::BranchNext(_) if found => Some(true),
+                Frame::BranchFirst(id) | Frame::BranchNext(id) => {
+                    let State::Alternation { branch, child } = self.cursors[id].state else {
+                        unreachable!("branch frames belong to alternations")
+                    };
+                    self.release(child);
+                    self.try_branch(id, branch + 1)
+                }
+                Frame::PartFirst { cursor, j } => {
+                    if !found && self.retry_copies(cursor) {
+                        self.exhausted(cursor)
+                    } else if !found {
+                        // A part with no results leaves the whole sequence
+                        // without any, as well as every longer repetition.
+                        Some(false)
+                    } else if let Some(&next) = self.parts(cursor).get(j + 1) {
+                        self.stack.push(Frame::PartFirst { cursor, j: j + 1 });
+                        self.stack.push(Frame::First(next));
+                        None
+                    } else {
+                        Some(true)
+                    }
+                }
+                Frame::PartNext { .. } if found => Some(true),
+                Frame::PartNext { cursor, j } => {
+                    let part = self.parts(cursor)[j];
+                    self.clear(part);
+                    self.stack.push(Frame::PartReset { cursor, j });
+                    self.stack.push(Frame::First(part));
+                    None
+                }
+                Frame::PartReset { cursor, j } => match self.parts(cursor).get(j + 1) {
+                    Some(&next) => {
+                        self.stack.push(Frame::PartNext { cursor, j: j + 1 });
+                        self.stack.push(Frame::Next(next));
+                        None
+                    }
+                    None => self.exhausted(cursor),
+                },
+            };
+            if let Some(result) = result {
+                found = result;
+            }
+        }
+        found
+    }
+
+    /// How long the surroundings of each part of a concatenation can be: the
+    /// concatenation's own plus the other parts'.
+    fn part_rests(&self, id: usize, ops: &[usize]) -> Vec<Option<usize>> {
+        let rest = self.cursors[id].rest;
+        let unbounded = ops
+            .iter()
+            .filter(|&&op| self.max_lens[op].is_none())
+            .count();
+        let total = ops
+            .iter()
+            .filter_map(|&op| self.max_lens[op])
+            .try_fold(rest.unwrap_or(0), usize::checked_add);
+        ops.iter()
+            .map(|&op| match (self.max_lens[op], rest, total) {
+                (Some(max_len), Some(_), Some(total)) if unbounded == 0 => Some(total - max_len),
+                (None, Some(_), Some(total)) if unbounded == 1 => Some(total),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Whether a repetition whose copies have no results should still try
+    /// another number of them. Backwards, the next has fewer copies, down to
+    /// none. Forwards, the copies may only have been pruned as too short, and
+    /// with more surrounding them they may no longer be.
+    fn retry_copies(&self, id: usize) -> bool {
+        let State::Repetition { children, .. } = &self.cursors[id].state else {
+            return false;
+        };
+        self.reverse
+            || children.first().is_some_and(|&child| {
+                self.cursors[child]
+                    .rest
+                    .is_some_and(|rest| rest < self.min_length)
+            })
+    }
+
+    /// Moves on from a sequence whose every combination has been visited:
+    /// a repetition tries one more copy, or one fewer backwards, and a
+    /// concatenation is done.
+    fn exhausted(&mut self, id: usize) -> Option<bool> {
+        match self.cursors[id].state {
+            State::Repetition { repeats: 0, .. } if self.reverse => {
+                self.clear(id);
+                Some(false)
+            }
+            State::Repetition { repeats, .. } => {
+                self.clear(id);
+                let next = if self.reverse {
+                    repeats - 1
+                } else {
+                    repeats + 1
+                };
+                self.try_repeats(id, next)
+            }
+            _ => Some(false),
+        }
+    }
+
+    /// Writes the current result, or returns `None` if it is too long or too
+    /// short.
+    fn render(&mut self) -> Option<Candidate> {
+        let mut result = Candidate::new();
+        let mut fits = true;
+        let mut pending = std::mem::take(&mut self.pending);
+        pending.push(0);
+        while let Some(id) = pending.pop() {
+            let cursor = &self.cursors[id];
+            match (&self.ops[cursor.op], &cursor.state) {
+                (Op::Literal(literal), _) => result.extend_from_slice(literal),
+                (Op::Class { ranges, unicode }, &State::Class { range, offset }) => {
+                    let (start, end) = ranges[range];
+                    let member = match self.class_order {
+                        ClassOrder::Asc => start + offset,
+                        ClassOrder::Desc => end - offset,
+                    };
+                    if *unicode {
+                        let c = char::from_u32(member).unwrap();
+                        result.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                    } else {
+                        result.push(member as u8);
+                    }
+                }
+                (_, State::Alternation { child, .. }) => pending.push(*child),
+                (_, State::Concat { children } | State::Repetition { children, .. }) => {
+                    pending.extend(children.iter().rev())
+                }
+                _ => {}
+            }
+            if self
+                .max_length
+                .is_some_and(|max_length| result.len() > max_length)
+            {
+                fits = false;
+                pending.clear();
+                break;
+            }
+        }
+        self.pending = pending;
+        (fits && result.len() >= self.min_length).then_some(result)
+    }
+
+    /// The top-level alternation branch of the current result.
+    fn branch(&self) -> usize {
+        match (&self.ops[self.cursors[0].op], &self.cursors[0].state) {
+            (Op::Alternation(branches), State::Alternation { branch, .. }) if self.reverse => {
+                branches.len() - 1 - branch
+            }
+            (_, State::Alternation { branch, .. }) => *branch,
+            _ => 0,
+        }
+    }
+}
+
+impl Iterator for Walker {
+    type Item = (usize, Candidate);
+
+    fn next(&mut self) -> Option<(usize, Candidate)> {
+        while !self.done {
+            if std::mem::take(&mut self.seeked) {
+                if let Some(result) = self.render() {
+                    return Some((self.branch(), result));
+                }
+                continue;
+            }
+            let frame = if self.started {
+                Frame::Next(0)
+            } else {
+                self.started = true;
+                Frame::First(0)
+            };
+            if !self.run(frame) {
+                self.done = true;
+                break;
+            }
+            if let Some(result) = self.render() {
+                return Some((self.branch(), result));
+            }
+        }
+        None
+    }
+}
+
+#[test]
+fn test_deep_nesting() {
+    let depth = 2000;
+    let pattern = format!("{}a{}", "(?:".repeat(depth), ")?".repeat(depth));
+    let pattern = crate::Pattern::new(&pattern).unwrap();
+    let results: Vec<_> = crate::iterate_all(pattern.hir(), None, ClassOrder::Asc).collect();
+    assert_eq!(results.len(), depth + 1);
+    assert_eq!(results.last().unwrap().as_slice(), b"a");
+}
+
+#[test]
+fn test_surrogate_gap() {
+    let hir = regex_syntax::Parser::new()
+        .parse("[\\x{D700}-\\x{E100}]")
+        .unwrap();
+    for class_order in [ClassOrder::Asc, ClassOrder::Desc] {
+        let mut results: Vec<_> = Walker::new(&hir, None, class_order)
+            .map(|(_, x)| String::from_utf8(x.to_vec()).unwrap())
+            .collect();
+        assert_eq!(results.len(), 0x100 + 0x101);
+        if class_order == ClassOrder::Desc {
+            results.reverse();
+        }
+        assert!(results.is_sorted());
+        let gap = results.iter().position(|x| x == "\u{d7ff}").unwrap();
+        assert_eq!(results[gap + 1], "\u{e000}");
+    }
+    let hir = regex_syntax::Parser::new().parse(".").unwrap();
+    assert_eq!(
+        Walker::new(&hir, None, ClassOrder::Asc).count(),
+        0x10ffff - 0x800
+    );
+}
+
+#[test]
+fn test_min_length() {
+    for pattern in [
+        "[a-c]{1,3}",
+        "short|longer|[0-9]{2,4}",
+        "((a|bb)c?){1,4}",
+        "(x?)*y|z",
+        "(a|)(b|cc)(d{0,2})",
+    ] {
+        let hir = regex_syntax::Parser::new().parse(pattern).unwrap();
+        for min_length in 0..6 {
+            let expected: Vec<_> = Walker::new(&hir, Some(6), ClassOrder::Asc)
+                .filter(|(_, x)| x.len() >= min_length)
+                .collect();
+            let pruned: Vec<_> = Walker::new(&hir, Some(6), ClassOrder::Asc)
+                .min_length(min_length)
+                .collect();
+            assert_eq!(pruned, expected, "{pattern} with min length {min_length}");
+        }
+    }
+}
+
+#[test]
+fn test_reverse() {
+    for (pattern, min_length, max_length) in [
+        ("[a-c]{0,3}(x|yz)?|é", 0, None),
+        ("((a|bb)c?){1,4}", 3, Some(6)),
+        ("(x?)*y|z", 0, Some(4)),
+        ("(a|)(b|cc)(d{0,2})", 2, None),
+    ] {
+        let hir = regex_syntax::Parser::new().parse(pattern).unwrap();
+        let mut expected: Vec<_> = Walker::new(&hir, max_length, ClassOrder::Asc)
+            .min_length(min_length)
+            .collect();
+        expected.reverse();
+        let reversed: Vec<_> = Walker::new(&hir, max_length, ClassOrder::Asc)
+            .min_length(min_length)
+            .reverse()
+            .collect();
+        assert_eq!(reversed, expected, "{pattern}");
+    }
+}